@@ -0,0 +1,22 @@
+//! Compares saving with a fresh salt against reusing the salt across saves.
+use criterion::{criterion_group, criterion_main, Criterion};
+use passmate::Vault;
+
+fn bench_save(c: &mut Criterion) {
+    let mut vault = Vault::open("bench.vault", "benchpwd").expect("failed to open vault");
+    for i in 0..100 {
+        vault
+            .set(format!("entry{i}"), format!("secret{i}"))
+            .expect("failed to set entry");
+    }
+
+    let mut group = c.benchmark_group("save_bytes");
+    group.sample_size(10);
+    group.bench_function("fresh_salt", |b| b.iter(|| vault.save_bytes()));
+    vault.set_reuse_salt(true);
+    group.bench_function("reuse_salt", |b| b.iter(|| vault.save_bytes()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_save);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+//! Measures opening and saving vaults of different sizes, and the key
+//! derivation that dominates both for small vaults.
+//!
+//! Entries, salts and nonces come from a seeded RNG so runs are comparable.
+use argon2::{Algorithm, Argon2, Params, Version};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use passmate::{KdfParams, Vault};
+use rand::{distributions::Alphanumeric, Rng};
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+const SEED: u64 = 42;
+const SIZES: [usize; 3] = [10, 1_000, 10_000];
+
+/// Returns a vault of `size` entries with random 20-character secrets,
+/// and its encrypted bytes.
+fn vault_of(size: usize) -> (Vault, Vec<u8>) {
+    let mut rng = ChaCha20Rng::seed_from_u64(SEED);
+    let mut vault = Vault::open("bench.vault", "benchpwd").expect("failed to open vault");
+    for i in 0..size {
+        let secret: String = (&mut rng)
+            .sample_iter(Alphanumeric)
+            .take(20)
+            .map(char::from)
+            .collect();
+        vault
+            .set(format!("entry{i}"), secret)
+            .expect("failed to set entry");
+    }
+    let bytes = vault
+        .save_bytes_with_rng(&mut rng)
+        .expect("failed to save vault");
+    (vault, bytes)
+}
+
+fn bench_open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("open_bytes");
+    group.sample_size(10);
+    for size in SIZES {
+        let (_, bytes) = vault_of(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &bytes, |b, bytes| {
+            b.iter(|| Vault::open_bytes(bytes, "benchpwd").expect("failed to open vault"));
+        });
+    }
+    group.finish();
+}
+
+fn bench_save(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save_bytes_with_rng");
+    group.sample_size(10);
+    for size in SIZES {
+        let (mut vault, _) = vault_of(size);
+        // Reusing the salt leaves serializing and encrypting to measure.
+        vault.set_reuse_salt(true);
+        let mut rng = ChaCha20Rng::seed_from_u64(SEED);
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| vault.save_bytes_with_rng(&mut rng));
+        });
+    }
+    group.finish();
+}
+
+fn bench_kdf(c: &mut Criterion) {
+    let kdf = KdfParams::default();
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .expect("default parameters are valid");
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let salt: [u8; 16] = ChaCha20Rng::seed_from_u64(SEED).gen();
+
+    let mut group = c.benchmark_group("kdf");
+    group.sample_size(10);
+    group.bench_function("argon2id_default", |b| {
+        b.iter(|| {
+            let mut key = [0u8; 32];
+            argon2
+                .hash_password_into(b"benchpwd", &salt, &mut key)
+                .expect("failed to derive key");
+            key
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_open, bench_save, bench_kdf);
+criterion_main!(benches);
@@ -0,0 +1,237 @@
+//! Defaults read from the optional `config.toml` and the environment.
+//!
+//! Settings are resolved in order of precedence: command-line flags, then
+//! environment variables, then the config file, then built-in defaults.
+use crate::{KdfParams, PassmateError};
+use serde::Deserialize;
+use std::{io::ErrorKind, path::Path};
+
+/// The name of the config file in the config directory.
+pub const CONFIG_FILE: &str = "config.toml";
+/// The vault used when none is configured.
+pub const DEFAULT_VAULT: &str = "default";
+
+/// One layer of settings. Unset fields fall through to the next layer.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of the vault to use.
+    pub vault: Option<String>,
+    /// Key derivation parameters for new vaults.
+    pub kdf: Option<KdfParams>,
+    /// Describes the vault in the passphrase prompt, as in "Enter {label} password:".
+    pub prompt_label: Option<String>,
+    /// Whether commands print a placeholder instead of secrets unless revealed.
+    pub mask_secrets: Option<bool>,
+    /// Block size in bytes that vault data is padded to a multiple of before
+    /// encrypting, so the file size doesn't reveal how much it holds.
+    pub padding: Option<u32>,
+}
+
+impl Config {
+    /// Reads the config file at the given path, or returns an
+    /// empty config if there is no file.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or isn't valid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PassmateError> {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(PassmateError::IO(e)),
+        }
+    }
+
+    /// Parses the contents of a config file.
+    ///
+    /// # Errors
+    /// Returns an error if the contents aren't valid TOML, have unknown
+    /// keys, have key derivation parameters no vault could be opened with,
+    /// or have a padding block size that isn't a power of two up to 1 MiB.
+    pub fn parse(contents: &str) -> Result<Self, PassmateError> {
+        let config: Self = toml::from_str(contents)
+            .map_err(|e| PassmateError::InvalidConfig(e.message().into()))?;
+        if let Some(kdf) = &config.kdf {
+            crate::format::check_kdf(kdf)
+                .map_err(|reason| PassmateError::InvalidConfig(format!("[kdf] {reason}")))?;
+        }
+        if let Some(padding) = config.padding {
+            crate::format::check_padding(padding)
+                .map_err(|reason| PassmateError::InvalidConfig(format!("padding {reason}")))?;
+        }
+        Ok(config)
+    }
+
+    /// Reads the settings given by the `PASSMATE_VAULT` environment variable.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            vault: var("PASSMATE_VAULT").filter(|vault| !vault.is_empty()),
+            ..Self::default()
+        }
+    }
+
+    /// Fills the settings missing from this layer with those of a
+    /// lower-precedence layer.
+    #[must_use]
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            vault: self.vault.or(fallback.vault),
+            kdf: self.kdf.or(fallback.kdf),
+            prompt_label: self.prompt_label.or(fallback.prompt_label),
+            mask_secrets: self.mask_secrets.or(fallback.mask_secrets),
+            padding: self.padding.or(fallback.padding),
+        }
+    }
+
+    /// Returns the configured vault name or [`DEFAULT_VAULT`].
+    #[must_use]
+    pub fn vault(&self) -> &str {
+        self.vault.as_deref().unwrap_or(DEFAULT_VAULT)
+    }
+
+    /// Returns the configured key derivation parameters or the defaults.
+    #[must_use]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.unwrap_or_default()
+    }
+
+    /// Returns whether secrets are masked, which they are unless configured otherwise.
+    #[must_use]
+    pub fn mask_secrets(&self) -> bool {
+        self.mask_secrets.unwrap_or(true)
+    }
+
+    /// Returns the text asking for the passphrase, naming the vault
+    /// with the configured prompt label if there is one.
+    #[must_use]
+    pub fn password_prompt(&self) -> String {
+        match self
+            .prompt_label
+            .as_deref()
+            .filter(|label| !label.is_empty())
+        {
+            Some(label) => format!("Enter {label} password: "),
+            None => "Enter password: ".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::env, Argon2Variant};
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse_reads_every_setting() {
+        let config = Config::parse(
+            "vault = \"work\"\nprompt_label = \"work vault\"\nmask_secrets = false\npadding = 4096\n[kdf]\nvariant = \"argon2i\"\nm_cost = 65536\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.vault(), "work");
+        assert_eq!(config.password_prompt(), "Enter work vault password: ");
+        assert!(!config.mask_secrets());
+        assert_eq!(config.padding, Some(4096));
+        assert_eq!(
+            config.kdf(),
+            KdfParams {
+                variant: Argon2Variant::Argon2i,
+                m_cost: 65536,
+                ..KdfParams::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        assert!(matches!(
+            Config::parse("vualt = \"work\""),
+            Err(PassmateError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_kdf_params_no_vault_could_be_opened_with() {
+        for (kdf, reason) in [
+            ("t_cost = 1001", "time cost of 1001"),
+            ("p_cost = 256", "parallelism of 256"),
+            ("m_cost = 4", "memory cost of 4 KiB"),
+        ] {
+            let Err(PassmateError::InvalidConfig(message)) =
+                Config::parse(&format!("[kdf]\n{kdf}\n"))
+            else {
+                panic!("accepted {kdf}");
+            };
+            assert!(message.starts_with(&format!("[kdf] {reason}")), "{message}");
+        }
+    }
+
+    #[test]
+    fn parse_rejects_a_padding_that_is_not_a_small_power_of_two() {
+        for padding in ["1000", "2097152"] {
+            let Err(PassmateError::InvalidConfig(message)) =
+                Config::parse(&format!("padding = {padding}"))
+            else {
+                panic!("accepted {padding}");
+            };
+            assert!(message.starts_with("padding block size"), "{message}");
+        }
+    }
+
+    #[test]
+    fn load_returns_an_empty_config_if_the_file_is_missing() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::load(temp_dir.path().join(CONFIG_FILE)).unwrap();
+
+        assert_eq!(config, Config::default());
+        assert_eq!(config.vault(), DEFAULT_VAULT);
+        assert_eq!(config.kdf(), KdfParams::default());
+        assert_eq!(config.password_prompt(), "Enter password: ");
+        assert!(config.mask_secrets());
+    }
+
+    #[test]
+    fn prompt_label_from_flags_overrides_the_file() {
+        let file = Config::parse("prompt_label = \"personal\"").unwrap();
+        let flags = Config {
+            prompt_label: Some("work vault".into()),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            flags.or(file.clone()).password_prompt(),
+            "Enter work vault password: "
+        );
+        assert_eq!(
+            Config::default().or(file).password_prompt(),
+            "Enter personal password: "
+        );
+    }
+
+    #[test]
+    fn settings_are_taken_from_flags_then_env_then_file_then_defaults() {
+        let file = Config::parse("vault = \"file\"\nmask_secrets = false").unwrap();
+        let env = Config::from_vars(env(&[("PASSMATE_VAULT", "env")]));
+        let flags = Config {
+            vault: Some("flag".into()),
+            ..Config::default()
+        };
+
+        let config = flags.or(env.clone()).or(file.clone());
+        assert_eq!(config.vault(), "flag");
+        assert!(!config.mask_secrets());
+        assert_eq!(config.kdf(), KdfParams::default());
+
+        let config = Config::default().or(env).or(file.clone());
+        assert_eq!(config.vault(), "env");
+
+        let config = Config::default().or(Config::default()).or(file);
+        assert_eq!(config.vault(), "file");
+    }
+}
@@ -0,0 +1,309 @@
+//! Non-destructive checks for common setup problems.
+//!
+//! None of the checks decrypt a vault or change anything on disk.
+use crate::{config::Config, format, paths::VaultDirs, PassmateError, UpgradeReason};
+use std::{fmt, io::ErrorKind, path::Path};
+
+/// The outcome of a check, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        })
+    }
+}
+
+/// The result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub status: Status,
+    pub message: String,
+    /// How to fix the problem, if there is one.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(message: String) -> Self {
+        Self {
+            status: Status::Ok,
+            message,
+            hint: None,
+        }
+    }
+
+    fn problem(status: Status, message: String, hint: impl Into<String>) -> Self {
+        Self {
+            status,
+            message,
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<9}{}", self.status, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n{:<9}{hint}", "")?;
+        }
+        Ok(())
+    }
+}
+
+/// The results of every check, in the order they ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Returns the worst status of any check.
+    #[must_use]
+    pub fn status(&self) -> Status {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Ok)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, check) in self.checks.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{check}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check against the vault directories, the config file in the
+/// legacy directory and the vault file.
+#[must_use]
+pub fn diagnose(dirs: &VaultDirs, vault: &Path) -> Report {
+    Report {
+        checks: vec![
+            check_dir("data directory", &dirs.data_dir),
+            check_dir("config directory", &dirs.legacy_dir),
+            check_config(&dirs.legacy_dir.join(crate::config::CONFIG_FILE)),
+            check_vault(vault),
+        ],
+    }
+}
+
+/// Checks that the directory exists and, on Unix, that only its owner can access it.
+#[must_use]
+pub fn check_dir(label: &str, dir: &Path) -> Check {
+    let shown = dir.display();
+    let metadata = match std::fs::metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Check::problem(
+                Status::Warning,
+                format!("{label} {shown} does not exist"),
+                "It's created when a vault is first saved there; check --config-dir and the XDG variables if you expected it",
+            );
+        }
+        Err(e) => {
+            return Check::problem(
+                Status::Error,
+                format!("{label} {shown} can't be read: {e}"),
+                "Check the permissions of its parent directories",
+            );
+        }
+    };
+    if !metadata.is_dir() {
+        return Check::problem(
+            Status::Error,
+            format!("{label} {shown} is not a directory"),
+            "Move the file out of the way",
+        );
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Check::problem(
+                Status::Warning,
+                format!("{label} {shown} can be accessed by other users (mode {mode:03o})"),
+                format!("Run: chmod 700 {shown}"),
+            );
+        }
+    }
+    Check::ok(format!("{label} {shown}"))
+}
+
+/// Checks that the config file, if there is one, is valid.
+#[must_use]
+pub fn check_config(path: &Path) -> Check {
+    let shown = path.display();
+    match Config::load(path) {
+        Ok(_) if !path.exists() => Check::ok(format!("no config file at {shown}, using defaults")),
+        Ok(_) => Check::ok(format!("config file {shown}")),
+        Err(PassmateError::IO(e)) => Check::problem(
+            Status::Error,
+            format!("config file {shown} can't be read: {e}"),
+            "Check the permissions of the file",
+        ),
+        Err(e) => Check::problem(
+            Status::Error,
+            format!("config file {shown} is invalid: {e}"),
+            "Fix or remove the file",
+        ),
+    }
+}
+
+/// Checks that the vault file can be read and has a valid, current
+/// header, without decrypting it.
+#[must_use]
+pub fn check_vault(path: &Path) -> Check {
+    let shown = path.display();
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Check::problem(
+                Status::Warning,
+                format!("vault {shown} does not exist"),
+                "It's created when the first entry is set; check --vault and $PASSMATE_VAULT if you expected it",
+            );
+        }
+        Err(e) => {
+            return Check::problem(
+                Status::Error,
+                format!("vault {shown} can't be read: {e}"),
+                "Check the permissions of the file",
+            );
+        }
+    };
+    if format::is_plaintext(&bytes) {
+        return Check::problem(
+            Status::Warning,
+            format!("vault {shown} is NOT encrypted"),
+            "Only keep non-secret data in it",
+        );
+    }
+    match format::inspect(&bytes) {
+        Ok(info) => {
+            let described = format!("vault {shown} (format version {})", info.version);
+            match UpgradeReason::of(info.version, &info.kdf, info.structured_entries) {
+                None => Check::ok(described),
+                Some(reason @ UpgradeReason::WeakKdf) => Check::problem(
+                    Status::Warning,
+                    format!("{described} is out of date: {reason}"),
+                    "Re-create the vault to derive its key with the default parameters",
+                ),
+                Some(reason) => Check::problem(
+                    Status::Warning,
+                    format!("{described} is out of date: {reason}"),
+                    "Run: passmate upgrade-entries",
+                ),
+            }
+        }
+        Err(PassmateError::InvalidFormat(reason)) => Check::problem(
+            Status::Error,
+            format!("vault {shown} is corrupt: {reason}"),
+            "Restore it from a backup",
+        ),
+        Err(e) => Check::problem(
+            Status::Error,
+            format!("vault {shown} can't be inspected: {e}"),
+            "Restore it from a backup",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn diagnose_reports_each_problem_of_a_misconfigured_setup() {
+        let temp_dir = TempDir::new().unwrap();
+        let dirs = VaultDirs {
+            data_dir: temp_dir.path().join("data"),
+            legacy_dir: temp_dir.path().join("config"),
+        };
+        std::fs::create_dir(&dirs.data_dir).unwrap();
+        std::fs::write(&dirs.legacy_dir, "not a directory").unwrap();
+        let vault = dirs.data_dir.join("default.vault");
+        std::fs::write(&vault, b"PASSMATE\x09").unwrap();
+
+        let report = diagnose(&dirs, &vault);
+        let statuses: Vec<Status> = report.checks.iter().map(|check| check.status).collect();
+
+        assert_eq!(report.status(), Status::Error);
+        assert_eq!(statuses[1..], [Status::Error, Status::Error, Status::Error]);
+        assert!(report.checks[3].message.contains("corrupt"));
+        assert!(report
+            .checks
+            .iter()
+            .skip(1)
+            .all(|check| check.hint.is_some()));
+    }
+
+    #[test]
+    fn diagnose_reports_no_problems_for_a_healthy_setup() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("passmate");
+        let dirs = VaultDirs {
+            data_dir: dir.clone(),
+            legacy_dir: dir.clone(),
+        };
+        crate::Vault::open(dir.join("default.vault"), "testpwd")
+            .unwrap()
+            .save()
+            .unwrap();
+
+        let report = diagnose(&dirs, &dir.join("default.vault"));
+
+        assert_eq!(report.status(), Status::Ok, "{report}");
+    }
+
+    #[test]
+    fn check_vault_warns_about_an_out_of_date_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("default.vault");
+        let mut vault = crate::Vault::open(&path, "testpwd").unwrap();
+        vault
+            .set_kdf_params(crate::KdfParams {
+                m_cost: 8,
+                t_cost: 1,
+                p_cost: 1,
+                ..crate::KdfParams::default()
+            })
+            .unwrap();
+        vault.save().unwrap();
+
+        let check = check_vault(&path);
+
+        assert_eq!(check.status, Status::Warning);
+        assert!(check.message.contains("weaker than the defaults"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_dir_warns_about_directories_other_users_can_access() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::set_permissions(temp_dir.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let check = check_dir("data directory", temp_dir.path());
+
+        assert_eq!(check.status, Status::Warning);
+        assert!(check.hint.unwrap().starts_with("Run: chmod 700"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Resolution of the external programs used to edit and view secrets.
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+#[cfg(windows)]
+const DEFAULT_PAGER: &str = "more";
+#[cfg(not(windows))]
+const DEFAULT_PAGER: &str = "less";
+
+/// Returns the command used to edit files: `$VISUAL`, then `$EDITOR`,
+/// then the platform's default editor.
+#[must_use]
+pub fn editor() -> String {
+    editor_from(env_var)
+}
+
+/// Returns the command used to view text: `$PAGER`, then
+/// the platform's default pager.
+#[must_use]
+pub fn pager() -> String {
+    pager_from(env_var)
+}
+
+fn editor_from(var: impl Fn(&str) -> Option<String>) -> String {
+    resolve(&var, &["VISUAL", "EDITOR"], DEFAULT_EDITOR)
+}
+
+fn pager_from(var: impl Fn(&str) -> Option<String>) -> String {
+    resolve(&var, &["PAGER"], DEFAULT_PAGER)
+}
+
+fn resolve(var: impl Fn(&str) -> Option<String>, names: &[&str], default: &str) -> String {
+    names
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.into())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::env;
+
+    #[test]
+    fn editor_prefers_visual_over_editor() {
+        let var = env(&[("VISUAL", "code --wait"), ("EDITOR", "nano")]);
+        assert_eq!(editor_from(var), "code --wait");
+    }
+
+    #[test]
+    fn editor_falls_back_to_editor() {
+        assert_eq!(editor_from(env(&[("EDITOR", "nano")])), "nano");
+        assert_eq!(
+            editor_from(env(&[("VISUAL", ""), ("EDITOR", "nano")])),
+            "nano"
+        );
+    }
+
+    #[test]
+    fn editor_falls_back_to_the_platform_default() {
+        assert_eq!(editor_from(env(&[])), DEFAULT_EDITOR);
+    }
+
+    #[test]
+    fn pager_uses_pager_or_the_platform_default() {
+        assert_eq!(pager_from(env(&[("PAGER", "most")])), "most");
+        assert_eq!(pager_from(env(&[("EDITOR", "nano")])), DEFAULT_PAGER);
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// The names of the fields an entry is serialized with.
+pub(crate) const FIELDS: &[&str] = &[
+    "secret",
+    "username",
+    "url",
+    "notes",
+    "tags",
+    "fields",
+    "created_at",
+    "updated_at",
+    "history",
+];
+
+/// Shown in place of a secret that isn't revealed.
+pub const MASKED_SECRET: &str = "********";
+
+/// A secret along with its metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub secret: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Custom fields by name, for attributes beyond the fixed ones.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    pub fields: HashMap<String, String>,
+    /// When the entry was created, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// When the secret was last changed or the entry was touched,
+    /// in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// Previous secrets, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<String>,
+}
+
+impl Entry {
+    /// Creates an entry holding only a secret.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the secret, pushing the previous one to the history
+    /// and recording when it changed.
+    pub fn update_secret(&mut self, secret: String, now: u64) {
+        if secret != self.secret {
+            let previous = std::mem::replace(&mut self.secret, secret);
+            self.history.push(previous);
+            self.updated_at = Some(now);
+        }
+    }
+
+    /// Keeps the secrets of the entry this one replaces, pushing the ones
+    /// missing from its history to it like [`Entry::update_secret`] does.
+    pub(crate) fn keep_history_of(&mut self, replaced: &Entry) {
+        for secret in replaced.history.iter().chain([&replaced.secret]) {
+            if *secret != self.secret && !self.history.contains(secret) {
+                self.history.push(secret.clone());
+            }
+        }
+    }
+
+    /// Returns a displayable block of the entry's populated fields, with
+    /// the secret and custom field values masked unless `reveal` is true.
+    #[must_use]
+    pub fn details(&self, reveal: bool) -> EntryDetails<'_> {
+        EntryDetails {
+            entry: self,
+            reveal,
+        }
+    }
+
+    /// Returns true if the entry holds nothing but a secret.
+    pub(crate) fn is_bare(&self) -> bool {
+        *self == Self::new(self.secret.as_str())
+    }
+}
+
+/// Serializes the map in the order of its keys, so equal entries always
+/// serialize to the same bytes.
+fn serialize_sorted<S: Serializer>(
+    map: &HashMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// The populated fields of an entry, one per line.
+pub struct EntryDetails<'a> {
+    entry: &'a Entry,
+    reveal: bool,
+}
+
+impl fmt::Display for EntryDetails<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entry = self.entry;
+        let secret = if self.reveal {
+            entry.secret.as_str()
+        } else {
+            MASKED_SECRET
+        };
+        write!(f, "secret: {secret}")?;
+        if let Some(username) = &entry.username {
+            write!(f, "\nusername: {username}")?;
+        }
+        if let Some(url) = &entry.url {
+            write!(f, "\nurl: {url}")?;
+        }
+        if let Some(notes) = &entry.notes {
+            write!(f, "\nnotes: {notes}")?;
+        }
+        if !entry.tags.is_empty() {
+            write!(f, "\ntags: {}", entry.tags.join(", "))?;
+        }
+        let mut fields: Vec<_> = entry.fields.iter().collect();
+        fields.sort();
+        for (name, value) in fields {
+            let value = if self.reveal {
+                value.as_str()
+            } else {
+                MASKED_SECRET
+            };
+            write!(f, "\n{name}: {value}")?;
+        }
+        if let Some(created_at) = entry.created_at {
+            write!(f, "\ncreated: {}", format_timestamp(created_at))?;
+        }
+        if let Some(updated_at) = entry.updated_at {
+            write!(f, "\nupdated: {}", format_timestamp(updated_at))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_timestamp(secs: u64) -> humantime::Rfc3339Timestamp {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// The on-disk form of an entry. Entries without metadata are
+/// stored as plain strings, which keeps older vaults readable.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum StoredEntry<S, E> {
+    Secret(S),
+    Entry(E),
+}
+
+impl<'a> From<&'a Entry> for StoredEntry<&'a str, &'a Entry> {
+    fn from(entry: &'a Entry) -> Self {
+        if entry.is_bare() {
+            StoredEntry::Secret(&entry.secret)
+        } else {
+            StoredEntry::Entry(entry)
+        }
+    }
+}
+
+impl From<StoredEntry<String, Entry>> for Entry {
+    fn from(stored: StoredEntry<String, Entry>) -> Self {
+        match stored {
+            StoredEntry::Secret(secret) => Entry::new(secret),
+            StoredEntry::Entry(entry) => entry,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn details_lists_every_populated_field_with_the_secrets_masked() {
+        let entry = Entry {
+            username: Some("me".into()),
+            url: Some("https://example.com".into()),
+            notes: Some("personal account".into()),
+            tags: vec!["work".into(), "email".into()],
+            fields: HashMap::from([
+                ("pin".into(), "1234".into()),
+                ("account".into(), "42".into()),
+            ]),
+            created_at: Some(1_700_000_000),
+            updated_at: Some(1_700_086_400),
+            history: vec!["old".into()],
+            ..Entry::new("test")
+        };
+
+        assert_eq!(
+            entry.details(false).to_string(),
+            "secret: ********\n\
+             username: me\n\
+             url: https://example.com\n\
+             notes: personal account\n\
+             tags: work, email\n\
+             account: ********\n\
+             pin: ********\n\
+             created: 2023-11-14T22:13:20Z\n\
+             updated: 2023-11-15T22:13:20Z"
+        );
+        assert!(entry.details(true).to_string().contains("\npin: 1234\n"));
+    }
+
+    #[test]
+    fn fields_lists_every_serialized_field() {
+        let entry = Entry {
+            username: Some("me".into()),
+            url: Some("https://example.com".into()),
+            notes: Some("notes".into()),
+            tags: vec!["work".into()],
+            fields: HashMap::from([("pin".into(), "1234".into())]),
+            created_at: Some(1),
+            updated_at: Some(2),
+            history: vec!["old".into()],
+            ..Entry::new("test")
+        };
+
+        let serde_json::Value::Object(serialized) = serde_json::to_value(entry).unwrap() else {
+            panic!("an entry should serialize to an object");
+        };
+
+        assert_eq!(serialized.len(), FIELDS.len());
+        assert!(serialized.keys().all(|key| FIELDS.contains(&key.as_str())));
+    }
+
+    #[test]
+    fn details_of_a_secret_only_entry_lists_only_the_secret() {
+        let entry = Entry::new("test");
+
+        assert_eq!(entry.details(false).to_string(), "secret: ********");
+        assert_eq!(entry.details(true).to_string(), "secret: test");
+    }
+
+    #[test]
+    fn keep_history_of_pushes_the_replaced_secrets_it_does_not_have() {
+        let replaced = Entry {
+            history: vec!["first".into(), "shared".into()],
+            ..Entry::new("current")
+        };
+        let mut entry = Entry {
+            history: vec!["shared".into()],
+            ..Entry::new("first")
+        };
+
+        entry.keep_history_of(&replaced);
+
+        assert_eq!(entry.secret, "first");
+        assert_eq!(entry.history, ["shared", "current"]);
+    }
+}
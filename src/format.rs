@@ -0,0 +1,794 @@
+//! The on-disk vault format.
+//!
+//! A vault file starts with a header followed by the encrypted data:
+//!
+//! ```text
+//! magic (8) | version (1) | kdf (1) | m_cost (4) | t_cost (4) | p_cost (4) | hint length (2) | hint | salt (16) | nonce (12) | ciphertext
+//! ```
+//!
+//! Integers are little-endian. The passphrase hint is UTF-8 and was added
+//! in version 2; version 1 headers have no hint. Vaults written before the header was
+//! introduced (version 0) contain only the salt, nonce and ciphertext,
+//! and use the default key derivation parameters.
+//!
+//! The key derivation function is 1 for Argon2id, 2 for Argon2i
+//! and 3 for Argon2d.
+//!
+//! Version 3 has the same layout as version 2, and marks vaults whose entries
+//! are all stored in structured form. Earlier versions store entries without
+//! metadata as bare strings.
+//!
+//! Version 4 adds two fields after the hint:
+//!
+//! ```text
+//! ... | hint | flags (1) | padding (4) | salt (16) | ...
+//! ```
+//!
+//! Bit 0 of the flags is set when entries are stored in structured form,
+//! which version 3 implies and earlier versions never do. The padding is the
+//! block size the plaintext was padded to a multiple of before encrypting,
+//! or 0 if it wasn't padded.
+//!
+//! Version 5 prefixes the salt and the nonce with their lengths, so key
+//! derivation functions and ciphers with other sizes fit the same layout:
+//!
+//! ```text
+//! ... | padding (4) | salt length (1) | salt | nonce length (1) | nonce | ciphertext
+//! ```
+//!
+//! The nonce length picks the cipher: 12 bytes for AES-256-GCM and 24 for
+//! XChaCha20-Poly1305. Earlier versions always use a 16-byte salt and
+//! AES-256-GCM.
+//!
+//! Version 6 keeps the layout of version 5 and changes the data: rather than
+//! the entries alone, it's an object holding the entries and a random key
+//! that fingerprints of the vault's secrets are computed with, so they stay
+//! the same across saves.
+//!
+//! Plaintext vaults, which are never the default, have a key derivation
+//! function of 0 and store the unencrypted data right after it:
+//!
+//! ```text
+//! magic (8) | version (1) | kdf = 0 (1) | data
+//! ```
+use crate::{Argon2Variant, KdfParams, PassmateError};
+use std::fmt::{self, Write};
+
+/// Identifies a vault file written with a header.
+pub const MAGIC: &[u8; 8] = b"PASSMATE";
+/// The current format version.
+pub const VERSION: u8 = 6;
+/// The newest version whose entries may be stored as bare strings.
+pub const COMPACT_ENTRIES_VERSION: u8 = 2;
+/// The first version with length-prefixed salts and nonces.
+pub const LENGTH_PREFIXED_VERSION: u8 = 5;
+/// The first version whose data holds a fingerprint key alongside the entries.
+pub const FINGERPRINT_KEY_VERSION: u8 = 6;
+/// The length of the salts passmate generates, and of every salt
+/// before [`LENGTH_PREFIXED_VERSION`].
+pub const SALT_LEN: usize = 16;
+/// The shortest salt accepted from a header, the minimum of Argon2.
+pub const MIN_SALT_LEN: usize = 8;
+/// The length of the nonce of [`Cipher::Aes256Gcm`].
+pub const NONCE_LEN: usize = 12;
+/// The length of the nonce of [`Cipher::XChaCha20Poly1305`].
+pub const XNONCE_LEN: usize = 24;
+/// The length of the authentication tag ending every ciphertext.
+pub const TAG_LEN: usize = 16;
+/// The largest Argon2 memory cost accepted from a header or for saving,
+/// in KiB (4 GiB). Larger values would make opening the vault exhaust memory.
+pub const MAX_M_COST: u32 = 4 * 1024 * 1024;
+/// The largest Argon2 time cost accepted from a header or for saving.
+pub const MAX_T_COST: u32 = 1000;
+/// The largest Argon2 degree of parallelism accepted from a header or for saving.
+pub const MAX_P_COST: u32 = 255;
+/// The largest padding block size accepted from a header or for saving,
+/// in bytes (1 MiB). Larger ones would make every save allocate that much.
+pub const MAX_PADDING: u32 = 1024 * 1024;
+/// Set in the header flags when entries are stored in structured form.
+const FLAG_STRUCTURED_ENTRIES: u8 = 1;
+
+const KDF_NONE: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+const KDF_ARGON2I: u8 = 2;
+const KDF_ARGON2D: u8 = 3;
+
+/// The unencrypted fields at the start of a vault file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub kdf: KdfParams,
+    /// A plaintext reminder of the passphrase.
+    pub hint: Option<String>,
+    /// Whether entries are stored in structured form rather than as bare strings.
+    pub structured_entries: bool,
+    /// The block size the plaintext was padded to, or 0 for none.
+    pub padding: u32,
+    pub salt: Vec<u8>,
+    /// The cipher the payload is encrypted with, always AES-256-GCM
+    /// before [`LENGTH_PREFIXED_VERSION`].
+    pub cipher: Cipher,
+}
+
+/// An authenticated cipher the vault data is encrypted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Aes256Gcm,
+    /// Only readable from headers, passmate never writes it.
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Returns the cipher whose nonces have the given length.
+    #[must_use]
+    pub const fn from_nonce_len(len: usize) -> Option<Self> {
+        match len {
+            NONCE_LEN => Some(Cipher::Aes256Gcm),
+            XNONCE_LEN => Some(Cipher::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the nonce the payload starts with.
+    #[must_use]
+    pub const fn nonce_len(self) -> usize {
+        match self {
+            Cipher::Aes256Gcm => NONCE_LEN,
+            Cipher::XChaCha20Poly1305 => XNONCE_LEN,
+        }
+    }
+}
+
+/// A vault file split into its header and encrypted payload.
+#[derive(Debug)]
+pub struct Decoded<'a> {
+    pub header: Header,
+    /// The nonce followed by the ciphertext.
+    pub payload: &'a [u8],
+}
+
+/// Encodes the header and encrypted payload into the bytes of a vault file.
+///
+/// # Panics
+/// Panics if the hint is longer than [`u16::MAX`] bytes, or the salt
+/// longer than [`u8::MAX`] bytes.
+#[must_use]
+pub fn encode(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(header.version);
+    bytes.push(match header.kdf.variant {
+        Argon2Variant::Argon2id => KDF_ARGON2ID,
+        Argon2Variant::Argon2i => KDF_ARGON2I,
+        Argon2Variant::Argon2d => KDF_ARGON2D,
+    });
+    bytes.extend_from_slice(&header.kdf.m_cost.to_le_bytes());
+    bytes.extend_from_slice(&header.kdf.t_cost.to_le_bytes());
+    bytes.extend_from_slice(&header.kdf.p_cost.to_le_bytes());
+    if header.version >= 2 {
+        let hint = header.hint.as_deref().unwrap_or_default();
+        let len = u16::try_from(hint.len()).expect("hint is too long");
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(hint.as_bytes());
+    }
+    if header.version >= 4 {
+        let flags = if header.structured_entries {
+            FLAG_STRUCTURED_ENTRIES
+        } else {
+            0
+        };
+        bytes.push(flags);
+        bytes.extend_from_slice(&header.padding.to_le_bytes());
+    }
+    if header.version >= LENGTH_PREFIXED_VERSION {
+        bytes.push(u8::try_from(header.salt.len()).expect("salt is too long"));
+        bytes.extend_from_slice(&header.salt);
+        let nonce_len = header.cipher.nonce_len();
+        bytes.push(u8::try_from(nonce_len).expect("nonce lengths fit a byte"));
+    } else {
+        bytes.extend_from_slice(&header.salt);
+    }
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Encodes unencrypted data into the bytes of a plaintext vault file.
+#[must_use]
+pub fn encode_plaintext(structured_entries: bool, data: &[u8]) -> Vec<u8> {
+    let version = if structured_entries {
+        VERSION
+    } else {
+        COMPACT_ENTRIES_VERSION
+    };
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&[version, KDF_NONE]);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+/// Returns the format version and the unencrypted data of a plaintext vault
+/// file, or `None` if the file is encrypted or malformed.
+#[must_use]
+pub fn decode_plaintext(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    let rest = bytes.strip_prefix(MAGIC.as_slice())?;
+    match rest {
+        [version, KDF_NONE, data @ ..] if (2..=VERSION).contains(version) => Some((*version, data)),
+        _ => None,
+    }
+}
+
+/// Returns true if the bytes are a plaintext vault file.
+#[must_use]
+pub fn is_plaintext(bytes: &[u8]) -> bool {
+    decode_plaintext(bytes).is_some()
+}
+
+/// Splits the bytes of a vault file into its header and encrypted payload.
+///
+/// # Errors
+/// Returns an error if the header is incomplete or has unsupported values.
+pub fn decode(bytes: &[u8]) -> Result<Decoded<'_>, PassmateError> {
+    let mut reader = Reader { bytes };
+    let header = if bytes.starts_with(MAGIC) {
+        reader.take(MAGIC.len())?;
+        let version = reader.u8()?;
+        if version == 0 || version > VERSION {
+            return Err(invalid(format!("unsupported version {version}")));
+        }
+        let kdf = reader.u8()?;
+        if kdf == KDF_NONE {
+            return Err(invalid("the vault is not encrypted".into()));
+        }
+        let variant = match kdf {
+            KDF_ARGON2ID => Argon2Variant::Argon2id,
+            KDF_ARGON2I => Argon2Variant::Argon2i,
+            KDF_ARGON2D => Argon2Variant::Argon2d,
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported key derivation function {kdf}"
+                )))
+            }
+        };
+        let kdf = KdfParams {
+            variant,
+            m_cost: reader.u32()?,
+            t_cost: reader.u32()?,
+            p_cost: reader.u32()?,
+        };
+        check_kdf(&kdf).map_err(invalid)?;
+        let hint = if version >= 2 { reader.hint()? } else { None };
+        let (structured_entries, padding) = if version >= 4 {
+            let flags = reader.u8()?;
+            if flags & !FLAG_STRUCTURED_ENTRIES != 0 {
+                return Err(invalid(format!("unsupported flags {flags:#04x}")));
+            }
+            let padding = reader.u32()?;
+            check_padding(padding).map_err(invalid)?;
+            (flags & FLAG_STRUCTURED_ENTRIES != 0, padding)
+        } else {
+            (version > COMPACT_ENTRIES_VERSION, 0)
+        };
+        let (salt, cipher) = if version >= LENGTH_PREFIXED_VERSION {
+            let salt = reader.prefixed("salt")?;
+            if salt.len() < MIN_SALT_LEN {
+                return Err(invalid(format!(
+                    "salt of {} bytes is shorter than {MIN_SALT_LEN}",
+                    salt.len()
+                )));
+            }
+            let nonce_len = reader.u8()?;
+            let cipher = Cipher::from_nonce_len(nonce_len.into())
+                .ok_or_else(|| invalid(format!("unsupported nonce length {nonce_len}")))?;
+            (salt.to_vec(), cipher)
+        } else {
+            (reader.take(SALT_LEN)?.to_vec(), Cipher::Aes256Gcm)
+        };
+        Header {
+            version,
+            kdf,
+            hint,
+            structured_entries,
+            padding,
+            salt,
+            cipher,
+        }
+    } else {
+        Header {
+            version: 0,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: false,
+            padding: 0,
+            salt: reader.take(SALT_LEN)?.to_vec(),
+            cipher: Cipher::Aes256Gcm,
+        }
+    };
+    let nonce_len = header.cipher.nonce_len();
+    if reader.bytes.len() < nonce_len {
+        return Err(invalid("truncated nonce".into()));
+    }
+    if reader.bytes.len() < nonce_len + TAG_LEN {
+        return Err(invalid("truncated ciphertext".into()));
+    }
+    Ok(Decoded {
+        header,
+        payload: reader.bytes,
+    })
+}
+
+/// Describes the layout of a vault file without decrypting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatInfo {
+    pub version: u8,
+    pub kdf: KdfParams,
+    pub hint: Option<String>,
+    pub structured_entries: bool,
+    pub padding: u32,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext_len: usize,
+}
+
+/// Parses the header fields of a vault file for debugging.
+/// The encrypted data is never decrypted.
+///
+/// # Errors
+/// Returns an error if the header is incomplete or has unsupported values.
+pub fn inspect(bytes: &[u8]) -> Result<FormatInfo, PassmateError> {
+    let Decoded { header, payload } = decode(bytes)?;
+    let (nonce, ciphertext) = payload.split_at(header.cipher.nonce_len());
+    Ok(FormatInfo {
+        version: header.version,
+        kdf: header.kdf,
+        hint: header.hint,
+        structured_entries: header.structured_entries,
+        padding: header.padding,
+        salt: header.salt,
+        nonce: nonce.to_vec(),
+        ciphertext_len: ciphertext.len(),
+    })
+}
+
+impl fmt::Display for FormatInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let magic = if self.version == 0 {
+            "none".into()
+        } else {
+            String::from_utf8_lossy(MAGIC)
+        };
+        writeln!(f, "magic: {magic}")?;
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "kdf: {}", self.kdf.variant)?;
+        writeln!(
+            f,
+            "params: m_cost={} t_cost={} p_cost={}",
+            self.kdf.m_cost, self.kdf.t_cost, self.kdf.p_cost
+        )?;
+        if let Some(hint) = &self.hint {
+            writeln!(f, "hint: {hint}")?;
+        }
+        if self.padding > 0 {
+            writeln!(f, "padding: {} bytes", self.padding)?;
+        }
+        writeln!(f, "salt: {}", hex(&self.salt))?;
+        writeln!(f, "nonce: {}", hex(&self.nonce))?;
+        write!(f, "ciphertext length: {}", self.ciphertext_len)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PassmateError> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated header".into()));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, PassmateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PassmateError> {
+        let mut buf = [0u8; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> Result<u32, PassmateError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn hint(&mut self) -> Result<Option<String>, PassmateError> {
+        let len = self.u16()?.into();
+        let hint = std::str::from_utf8(self.take(len)?)
+            .map_err(|_| invalid("hint is not valid UTF-8".into()))?;
+        Ok((!hint.is_empty()).then(|| hint.to_string()))
+    }
+
+    /// Reads a field prefixed with its length in one byte.
+    fn prefixed(&mut self, field: &str) -> Result<&'a [u8], PassmateError> {
+        let len = self.u8()?.into();
+        if self.bytes.len() < len {
+            return Err(invalid(format!(
+                "{field} length of {len} runs past the end of the file"
+            )));
+        }
+        self.take(len)
+    }
+}
+
+/// Rejects key derivation parameters that Argon2 can't use or that would
+/// make opening the vault exhaust memory or time, returning the reason.
+/// Headers are checked so a corrupt one isn't mistaken for a key derivation
+/// failure, and new parameters so a vault is never saved with ones that
+/// can't open it again.
+pub(crate) fn check_kdf(kdf: &KdfParams) -> Result<(), String> {
+    if !(1..=MAX_P_COST).contains(&kdf.p_cost) {
+        return Err(format!(
+            "parallelism of {} is outside the supported range of 1 to {MAX_P_COST}",
+            kdf.p_cost
+        ));
+    }
+    if !(1..=MAX_T_COST).contains(&kdf.t_cost) {
+        return Err(format!(
+            "time cost of {} is outside the supported range of 1 to {MAX_T_COST}",
+            kdf.t_cost
+        ));
+    }
+    let min_m_cost = 8 * kdf.p_cost;
+    if !(min_m_cost..=MAX_M_COST).contains(&kdf.m_cost) {
+        return Err(format!(
+            "memory cost of {} KiB is outside the supported range of {min_m_cost} to {MAX_M_COST} KiB",
+            kdf.m_cost
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects a padding block size that isn't 0 or a power of two up to
+/// [`MAX_PADDING`], returning the reason.
+pub(crate) fn check_padding(block_size: u32) -> Result<(), String> {
+    if block_size == 0 || (block_size.is_power_of_two() && block_size <= MAX_PADDING) {
+        Ok(())
+    } else {
+        Err(format!(
+            "block size of {block_size} bytes is not a power of two up to {MAX_PADDING}"
+        ))
+    }
+}
+
+fn invalid(reason: String) -> PassmateError {
+    PassmateError::InvalidFormat(reason)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::assert_err;
+    use proptest::prelude::*;
+
+    fn known_header() -> Vec<u8> {
+        let mut bytes = b"PASSMATE".to_vec();
+        bytes.push(2);
+        bytes.push(1);
+        bytes.extend_from_slice(&[0x00, 0x4c, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x04, 0x00]);
+        bytes.extend_from_slice(b"pets");
+        bytes.extend_from_slice(&[0xaa; SALT_LEN]);
+        bytes.extend_from_slice(&[0xbb; NONCE_LEN]);
+        bytes.extend_from_slice(&[0xcc; 20]);
+        bytes
+    }
+
+    #[test]
+    fn inspect_parses_the_header_fields() {
+        let info = inspect(&known_header()).unwrap();
+
+        assert_eq!(
+            info,
+            FormatInfo {
+                version: 2,
+                kdf: KdfParams {
+                    m_cost: 19456,
+                    t_cost: 2,
+                    p_cost: 1,
+                    ..KdfParams::default()
+                },
+                hint: Some("pets".into()),
+                structured_entries: false,
+                padding: 0,
+                salt: vec![0xaa; SALT_LEN],
+                nonce: vec![0xbb; NONCE_LEN],
+                ciphertext_len: 20,
+            }
+        );
+        assert_eq!(
+            info.to_string(),
+            format!(
+                "magic: PASSMATE\nversion: 2\nkdf: argon2id\nparams: m_cost=19456 t_cost=2 p_cost=1\nhint: pets\nsalt: {}\nnonce: {}\nciphertext length: 20",
+                "aa".repeat(SALT_LEN),
+                "bb".repeat(NONCE_LEN)
+            )
+        );
+    }
+
+    #[test]
+    fn decode_reads_vaults_without_a_header_as_version_0() {
+        let bytes = [
+            [0xaa; SALT_LEN].as_slice(),
+            &[0xbb; NONCE_LEN],
+            &[0xcc; TAG_LEN],
+        ]
+        .concat();
+
+        let decoded = decode(&bytes).unwrap();
+
+        assert_eq!(decoded.header.version, 0);
+        assert_eq!(decoded.header.kdf, KdfParams::default());
+        assert_eq!(decoded.header.salt, [0xaa; SALT_LEN]);
+        assert_eq!(decoded.header.cipher, Cipher::Aes256Gcm);
+        assert_eq!(decoded.payload.len(), NONCE_LEN + TAG_LEN);
+    }
+
+    #[test]
+    fn encode_and_decode_round_trip() {
+        let header = Header {
+            version: VERSION,
+            kdf: KdfParams {
+                variant: Argon2Variant::Argon2i,
+                ..KdfParams::default()
+            },
+            hint: Some("pets".into()),
+            structured_entries: true,
+            padding: 4096,
+            salt: vec![7; SALT_LEN],
+            cipher: Cipher::Aes256Gcm,
+        };
+        let payload = [9; NONCE_LEN + TAG_LEN + 8];
+
+        let bytes = encode(&header, &payload);
+        let decoded = decode(&bytes).unwrap();
+
+        assert_eq!(decoded.header, header);
+        assert_eq!(decoded.payload, payload);
+    }
+
+    #[test]
+    fn decode_reads_the_declared_salt_and_nonce_lengths() {
+        let header = Header {
+            version: VERSION,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: vec![7; 32],
+            cipher: Cipher::XChaCha20Poly1305,
+        };
+        let payload = [9; XNONCE_LEN + TAG_LEN];
+
+        let bytes = encode(&header, &payload);
+        let decoded = decode(&bytes).unwrap();
+        let info = inspect(&bytes).unwrap();
+
+        assert_eq!(decoded.header, header);
+        assert_eq!(decoded.payload, payload);
+        assert_eq!(info.salt.len(), 32);
+        assert_eq!(info.nonce.len(), XNONCE_LEN);
+        assert_eq!(info.ciphertext_len, TAG_LEN);
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_length_fields_that_overrun_or_are_unsupported() {
+        let header = Header {
+            version: VERSION,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: vec![7; SALT_LEN],
+            cipher: Cipher::Aes256Gcm,
+        };
+        let bytes = encode(&header, &[9; NONCE_LEN + TAG_LEN]);
+        let salt_len = MAGIC.len() + 2 + 12 + 2 + 5;
+        let nonce_len = salt_len + 1 + SALT_LEN;
+        assert_eq!(usize::from(bytes[salt_len]), SALT_LEN);
+        assert_eq!(usize::from(bytes[nonce_len]), NONCE_LEN);
+
+        for (offset, value, reason) in [
+            (salt_len, 255, "salt length of 255 runs past the end"),
+            (salt_len, 4, "salt of 4 bytes is shorter than 8"),
+            (nonce_len, 16, "unsupported nonce length 16"),
+            (nonce_len, 24, "truncated ciphertext"),
+        ] {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+
+            assert!(matches!(
+                decode(&bytes),
+                Err(PassmateError::InvalidFormat(message)) if message.starts_with(reason)
+            ));
+        }
+        assert!(matches!(
+            decode(&bytes[..=salt_len]),
+            Err(PassmateError::InvalidFormat(message)) if message.starts_with("salt length of 16")
+        ));
+    }
+
+    #[test]
+    fn decode_reads_version_1_headers_without_a_hint() {
+        let header = Header {
+            version: 1,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: false,
+            padding: 0,
+            salt: vec![7; SALT_LEN],
+            cipher: Cipher::Aes256Gcm,
+        };
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 1]);
+        bytes.extend_from_slice(&[0x00, 0x4c, 0x00, 0x00, 0x02, 0, 0, 0, 0x01, 0, 0, 0]);
+        bytes.extend_from_slice(&[7; SALT_LEN]);
+        bytes.extend_from_slice(&[9; NONCE_LEN + TAG_LEN]);
+
+        let decoded = decode(&bytes).unwrap();
+
+        assert_eq!(decoded.header, header);
+        assert_eq!(encode(&header, decoded.payload), bytes);
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_an_excessive_memory_cost() {
+        let mut bytes = known_header();
+        let offset = MAGIC.len() + 2;
+        bytes[offset..offset + 4].copy_from_slice(&(MAX_M_COST + 1).to_le_bytes());
+
+        assert!(matches!(
+            decode(&bytes),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_out_of_range_kdf_params() {
+        let m_cost = MAGIC.len() + 2;
+        for (offset, value, reason) in [
+            (m_cost + 4, 0, "time cost of 0"),
+            (m_cost + 4, MAX_T_COST + 1, "time cost of 1001"),
+            (m_cost + 8, 0, "parallelism of 0"),
+            (m_cost + 8, MAX_P_COST + 1, "parallelism of 256"),
+            (m_cost, 7, "memory cost of 7 KiB"),
+        ] {
+            let mut bytes = known_header();
+            bytes[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+
+            assert!(matches!(
+                decode(&bytes),
+                Err(PassmateError::InvalidFormat(message)) if message.starts_with(reason)
+            ));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn decode_never_panics_on_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..128)) {
+            let _ = decode(&bytes);
+            let _ = inspect(&bytes);
+        }
+
+        #[test]
+        fn decode_never_panics_on_arbitrary_headers(
+            version in 0..=VERSION + 1,
+            rest in prop::collection::vec(any::<u8>(), 0..128),
+        ) {
+            let bytes = [MAGIC.as_slice(), &[version], &rest].concat();
+            let _ = decode(&bytes);
+            let _ = inspect(&bytes);
+        }
+    }
+
+    #[test]
+    fn plaintext_files_round_trip_and_are_told_apart_from_encrypted_ones() {
+        let bytes = encode_plaintext(true, b"{}");
+
+        assert_eq!(decode_plaintext(&bytes), Some((VERSION, b"{}".as_slice())));
+        assert_eq!(
+            decode_plaintext(&encode_plaintext(false, b"{}")),
+            Some((COMPACT_ENTRIES_VERSION, b"{}".as_slice()))
+        );
+        assert!(is_plaintext(&bytes));
+        assert!(!is_plaintext(&known_header()));
+        assert!(!is_plaintext(b"{}"));
+        assert!(matches!(
+            decode(&bytes),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_a_truncated_header() {
+        let bytes = known_header();
+        assert_err!(decode(&bytes[..20]));
+        assert_err!(decode(&bytes[..4]));
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_a_ciphertext_shorter_than_the_tag() {
+        let bytes = known_header();
+        let end = bytes.len() - 20 + TAG_LEN;
+
+        assert!(decode(&bytes[..end]).is_ok());
+        assert!(matches!(
+            decode(&bytes[..end - 1]),
+            Err(PassmateError::InvalidFormat(message)) if message.contains("truncated ciphertext")
+        ));
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_unsupported_flags() {
+        let header = Header {
+            version: VERSION,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: vec![7; SALT_LEN],
+            cipher: Cipher::Aes256Gcm,
+        };
+        let mut bytes = encode(&header, &[9; NONCE_LEN + TAG_LEN]);
+        let offset = MAGIC.len() + 2 + 12 + 2;
+        assert_eq!(bytes[offset], FLAG_STRUCTURED_ENTRIES);
+        bytes[offset] |= 0x80;
+
+        assert!(matches!(
+            decode(&bytes),
+            Err(PassmateError::InvalidFormat(message)) if message.contains("unsupported flags")
+        ));
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_an_unsupported_padding() {
+        for padding in [1000, MAX_PADDING * 2] {
+            let header = Header {
+                version: VERSION,
+                kdf: KdfParams::default(),
+                hint: None,
+                structured_entries: true,
+                padding,
+                salt: vec![7; SALT_LEN],
+                cipher: Cipher::Aes256Gcm,
+            };
+
+            assert!(matches!(
+                decode(&encode(&header, &[9; NONCE_LEN + TAG_LEN])),
+                Err(PassmateError::InvalidFormat(message)) if message.starts_with(&format!("block size of {padding}"))
+            ));
+        }
+    }
+
+    #[test]
+    fn decode_returns_an_error_for_an_unsupported_version() {
+        let mut bytes = known_header();
+        bytes[MAGIC.len()] = 99;
+        assert!(matches!(
+            decode(&bytes),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,527 @@
+//! Password generation.
+use crate::PassmateError;
+use rand::{seq::SliceRandom, CryptoRng, Rng};
+use std::{
+    fmt,
+    io::{BufRead, Write},
+};
+
+/// A class of characters a password may contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+}
+
+impl CharClass {
+    pub const ALL: [CharClass; 4] = [
+        CharClass::Lowercase,
+        CharClass::Uppercase,
+        CharClass::Digits,
+        CharClass::Symbols,
+    ];
+
+    /// Returns the characters belonging to the class.
+    #[must_use]
+    pub fn chars(self) -> &'static str {
+        match self {
+            CharClass::Lowercase => "abcdefghijklmnopqrstuvwxyz",
+            CharClass::Uppercase => "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            CharClass::Digits => "0123456789",
+            CharClass::Symbols => "!@#$%^&*()-_=+[]{};:,.<>?/",
+        }
+    }
+
+    /// Returns the class a character belongs to, counting characters
+    /// outside of every class as symbols.
+    #[must_use]
+    pub fn of(c: char) -> CharClass {
+        CharClass::ALL
+            .into_iter()
+            .find(|class| class.chars().contains(c))
+            .unwrap_or(CharClass::Symbols)
+    }
+
+    /// Returns a human-readable name for the class.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            CharClass::Lowercase => "lowercase letters",
+            CharClass::Uppercase => "uppercase letters",
+            CharClass::Digits => "digits",
+            CharClass::Symbols => "symbols",
+        }
+    }
+}
+
+/// Describes the passwords to generate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    /// Classes that every generated password must include.
+    pub classes: Vec<CharClass>,
+    /// Characters that generated passwords must never contain.
+    pub exclude: String,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            classes: CharClass::ALL.to_vec(),
+            exclude: String::new(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Returns the default policy with the given length.
+    #[must_use]
+    pub fn with_length(length: usize) -> Self {
+        Self {
+            length,
+            ..Self::default()
+        }
+    }
+
+    /// Returns a policy for passwords with the same profile as the given
+    /// one: the same length and character classes, such as to replace it
+    /// on a site with specific rules.
+    #[must_use]
+    pub fn matching(password: &str) -> Self {
+        Self {
+            length: password.chars().count(),
+            classes: CharClass::ALL
+                .into_iter()
+                .filter(|class| password.chars().any(|c| CharClass::of(c) == *class))
+                .collect(),
+            exclude: String::new(),
+        }
+    }
+
+    /// Generates a password containing at least one character
+    /// from each of the policy's character classes.
+    ///
+    /// # Errors
+    /// Returns an error if no character classes are enabled, every character
+    /// of a class is excluded, or the length is too short to include one
+    /// character from each class.
+    pub fn generate<R: Rng + CryptoRng>(&self, rng: &mut R) -> Result<String, PassmateError> {
+        let classes = self
+            .classes
+            .iter()
+            .map(|class| {
+                let chars: String = class
+                    .chars()
+                    .chars()
+                    .filter(|c| !self.exclude.contains(*c))
+                    .collect();
+                if chars.is_empty() {
+                    return Err(PassmateError::InvalidPolicy(format!(
+                        "all {} are excluded",
+                        class.name()
+                    )));
+                }
+                Ok(chars)
+            })
+            .collect::<Result<Vec<String>, PassmateError>>()?;
+        if classes.is_empty() {
+            return Err(PassmateError::InvalidPolicy(
+                "no character classes enabled".into(),
+            ));
+        }
+        if self.length < classes.len() {
+            return Err(PassmateError::InvalidPolicy(format!(
+                "length must be at least {}",
+                classes.len()
+            )));
+        }
+
+        let charset: Vec<char> = classes.concat().chars().collect();
+        let mut password: Vec<char> = classes.iter().map(|class| pick(class, rng)).collect();
+        while password.len() < self.length {
+            password.push(charset[rng.gen_range(0..charset.len())]);
+        }
+        password.shuffle(rng);
+
+        Ok(password.into_iter().collect())
+    }
+}
+
+/// The consonants of pronounceable passwords, leaving out those whose
+/// sound depends on the letters around them.
+pub const CONSONANTS: &str = "bdfghjklmnprstvz";
+/// The vowels of pronounceable passwords.
+pub const VOWELS: &str = "aeiou";
+
+/// Generates a pronounceable password of lowercase letters, alternating
+/// between [`CONSONANTS`] and [`VOWELS`] starting with a consonant, like
+/// `tobaruvi`.
+///
+/// Such passwords are easier to read aloud and type, but a character adds
+/// fewer bits than in one from a [`PasswordPolicy`]; see
+/// [`pronounceable_entropy`].
+///
+/// # Errors
+/// Returns an error if the length is zero.
+pub fn generate_pronounceable<R: Rng + CryptoRng>(
+    length: usize,
+    rng: &mut R,
+) -> Result<String, PassmateError> {
+    if length == 0 {
+        return Err(PassmateError::InvalidPolicy(
+            "length must be at least 1".into(),
+        ));
+    }
+    Ok((0..length)
+        .map(|i| pick(if i % 2 == 0 { CONSONANTS } else { VOWELS }, rng))
+        .collect())
+}
+
+/// Returns the entropy in bits of a password generated by
+/// [`generate_pronounceable`] with the given length.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn pronounceable_entropy(length: usize) -> f64 {
+    let consonants = length.div_ceil(2) as f64 * (CONSONANTS.len() as f64).log2();
+    let vowels = (length / 2) as f64 * (VOWELS.len() as f64).log2();
+    consonants + vowels
+}
+
+/// Shows passwords from `generate` on `output` until one is accepted
+/// from `input`, with an empty answer or `y`, returning it. `r` asks for
+/// another password, while `n`, `q` or the end of the input returns
+/// `None` to give up without one. Other answers are asked again.
+///
+/// `display` turns a password into what's shown, such as a placeholder
+/// for passwords that mustn't appear on screen.
+///
+/// # Errors
+/// Returns an error if generating a password, reading or writing fails.
+pub fn review(
+    mut generate: impl FnMut() -> Result<String, PassmateError>,
+    display: impl Fn(&str) -> String,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<Option<String>, PassmateError> {
+    let mut password = generate()?;
+    loop {
+        write!(
+            output,
+            "{}\nAccept this password? [Y]es, [r]egenerate, [n]o: ",
+            display(&password)
+        )
+        .and_then(|()| output.flush())
+        .map_err(PassmateError::IO)?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer).map_err(PassmateError::IO)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(Some(password)),
+            "r" | "regenerate" => password = generate()?,
+            "n" | "no" | "q" | "quit" => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// A rule of a [`PasswordPolicy`] that a password breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The password has fewer characters than the policy's length.
+    TooShort { length: usize, minimum: usize },
+    /// The password has no character from a required class.
+    MissingClass(CharClass),
+    /// The password contains a character the policy excludes.
+    ExcludedChar(char),
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::TooShort { length, minimum } => {
+                write!(f, "has {length} characters, fewer than {minimum}")
+            }
+            PolicyViolation::MissingClass(class) => write!(f, "has no {}", class.name()),
+            PolicyViolation::ExcludedChar(c) => write!(f, "contains excluded character {c:?}"),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Returns every rule of the policy that the password breaks,
+    /// or an empty list if it complies.
+    #[must_use]
+    pub fn violations(&self, password: &str) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.length {
+            violations.push(PolicyViolation::TooShort {
+                length,
+                minimum: self.length,
+            });
+        }
+        for class in &self.classes {
+            if !password.chars().any(|c| class.chars().contains(c)) {
+                violations.push(PolicyViolation::MissingClass(*class));
+            }
+        }
+        let mut excluded: Vec<char> = password
+            .chars()
+            .filter(|c| self.exclude.contains(*c))
+            .collect();
+        excluded.sort_unstable();
+        excluded.dedup();
+        violations.extend(excluded.into_iter().map(PolicyViolation::ExcludedChar));
+        violations
+    }
+}
+
+/// A rough rating of how hard a password is to guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Strength {
+    Weak,
+    Fair,
+    Good,
+    Strong,
+}
+
+impl Strength {
+    pub const ALL: [Strength; 4] = [
+        Strength::Weak,
+        Strength::Fair,
+        Strength::Good,
+        Strength::Strong,
+    ];
+}
+
+impl fmt::Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strength::Weak => "weak",
+            Strength::Fair => "fair",
+            Strength::Good => "good",
+            Strength::Strong => "strong",
+        })
+    }
+}
+
+/// Estimates the strength of a password from its length and the character
+/// classes it uses, as if every character were picked at random.
+///
+/// This is an upper bound: it doesn't detect words or patterns.
+#[must_use]
+pub fn estimate_strength(password: &str) -> Strength {
+    let mut classes: Vec<CharClass> = password.chars().map(CharClass::of).collect();
+    classes.sort_unstable_by_key(|class| *class as u8);
+    classes.dedup();
+    let pool: usize = classes.iter().map(|class| class.chars().len()).sum();
+    #[allow(clippy::cast_precision_loss)]
+    let bits = password.chars().count() as f64 * (pool.max(1) as f64).log2();
+    match bits {
+        bits if bits < 40.0 => Strength::Weak,
+        bits if bits < 60.0 => Strength::Fair,
+        bits if bits < 80.0 => Strength::Good,
+        _ => Strength::Strong,
+    }
+}
+
+fn pick<R: Rng>(class: &str, rng: &mut R) -> char {
+    let chars: Vec<char> = class.chars().collect();
+    chars[rng.gen_range(0..chars.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::assert_err;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn review_regenerates_until_a_password_is_accepted() {
+        let mut passwords = ["first", "second", "third"].into_iter();
+        let mut output = Vec::new();
+
+        let accepted = review(
+            || Ok(passwords.next().unwrap().to_string()),
+            str::to_uppercase,
+            "r\nwhat\nY\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(accepted.as_deref(), Some("second"));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("FIRST\nAccept this password?"));
+        assert_eq!(output.matches("SECOND\n").count(), 2);
+        assert!(!output.contains("THIRD"));
+    }
+
+    #[test]
+    fn review_returns_none_when_declined_or_the_input_ends() {
+        for input in ["n\n", "r\nq\n", ""] {
+            let declined = review(
+                || Ok("secret".to_string()),
+                str::to_string,
+                input.as_bytes(),
+                std::io::sink(),
+            );
+            assert_eq!(declined.unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn generate_produces_a_password_of_the_given_length() {
+        let password = PasswordPolicy::with_length(32)
+            .generate(&mut OsRng)
+            .unwrap();
+        assert_eq!(password.chars().count(), 32);
+    }
+
+    #[test]
+    fn generate_includes_a_character_from_each_enabled_class() {
+        let policy = PasswordPolicy::with_length(4);
+        for _ in 0..50 {
+            let password = policy.generate(&mut OsRng).unwrap();
+            for class in CharClass::ALL {
+                assert!(password.chars().any(|c| class.chars().contains(c)));
+            }
+        }
+    }
+
+    #[test]
+    fn generate_only_uses_characters_from_enabled_classes() {
+        let policy = PasswordPolicy {
+            classes: vec![CharClass::Lowercase, CharClass::Digits],
+            ..PasswordPolicy::default()
+        };
+        let password = policy.generate(&mut OsRng).unwrap();
+        assert!(password
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn estimate_strength_rates_longer_and_more_varied_passwords_higher() {
+        assert_eq!(estimate_strength(""), Strength::Weak);
+        assert_eq!(estimate_strength("password"), Strength::Weak);
+        assert_eq!(estimate_strength("password12"), Strength::Fair);
+        assert_eq!(estimate_strength("correcthorse12"), Strength::Good);
+        let generated = PasswordPolicy::default().generate(&mut OsRng).unwrap();
+        assert_eq!(estimate_strength(&generated), Strength::Strong);
+    }
+
+    #[test]
+    fn generate_never_uses_excluded_characters() {
+        let policy = PasswordPolicy {
+            exclude: "<>&abc0123".into(),
+            ..PasswordPolicy::with_length(64)
+        };
+        for _ in 0..20 {
+            let password = policy.generate(&mut OsRng).unwrap();
+            assert!(!password.contains(|c| policy.exclude.contains(c)));
+        }
+    }
+
+    #[test]
+    fn generate_returns_an_error_if_a_whole_class_is_excluded() {
+        let policy = PasswordPolicy {
+            exclude: CharClass::Digits.chars().into(),
+            ..PasswordPolicy::default()
+        };
+        assert!(matches!(
+            policy.generate(&mut OsRng),
+            Err(PassmateError::InvalidPolicy(message)) if message == "all digits are excluded"
+        ));
+    }
+
+    #[test]
+    fn generate_pronounceable_alternates_consonants_and_vowels() {
+        for length in [1, 2, 9, 20] {
+            let password = generate_pronounceable(length, &mut OsRng).unwrap();
+
+            assert_eq!(password.len(), length);
+            for (i, c) in password.chars().enumerate() {
+                let letters = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+                assert!(letters.contains(c), "{c:?} at {i} in {password:?}");
+            }
+        }
+        assert_err!(generate_pronounceable(0, &mut OsRng));
+    }
+
+    #[test]
+    fn pronounceable_entropy_counts_the_bits_of_each_letter() {
+        assert!(pronounceable_entropy(0).abs() < f64::EPSILON);
+        assert!((pronounceable_entropy(1) - 4.0).abs() < 1e-9);
+        assert!((pronounceable_entropy(20) - (40.0 + 10.0 * 5f64.log2())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matching_generates_passwords_with_the_same_profile() {
+        for source in ["abc123", "PIN0000", "Tr0ub4dor&3", "~~~lower"] {
+            let policy = PasswordPolicy::matching(source);
+
+            let generated = policy.generate(&mut OsRng).unwrap();
+
+            assert_eq!(PasswordPolicy::matching(&generated), policy, "{source}");
+        }
+        assert_eq!(
+            PasswordPolicy::matching("PIN0000").classes,
+            [CharClass::Uppercase, CharClass::Digits]
+        );
+    }
+
+    #[test]
+    fn violations_lists_every_broken_rule() {
+        let policy = PasswordPolicy {
+            exclude: "&".into(),
+            ..PasswordPolicy::with_length(12)
+        };
+
+        assert_eq!(
+            policy.violations("abc&"),
+            [
+                PolicyViolation::TooShort {
+                    length: 4,
+                    minimum: 12
+                },
+                PolicyViolation::MissingClass(CharClass::Uppercase),
+                PolicyViolation::MissingClass(CharClass::Digits),
+                PolicyViolation::ExcludedChar('&'),
+            ]
+        );
+        assert!(policy.violations("Abcdefgh1234!").is_empty());
+    }
+
+    #[test]
+    fn violations_lists_each_excluded_character_once() {
+        let policy = PasswordPolicy {
+            exclude: "ab".into(),
+            ..PasswordPolicy::with_length(3)
+        };
+
+        assert_eq!(
+            policy.violations("aba"),
+            [
+                PolicyViolation::MissingClass(CharClass::Uppercase),
+                PolicyViolation::MissingClass(CharClass::Digits),
+                PolicyViolation::MissingClass(CharClass::Symbols),
+                PolicyViolation::ExcludedChar('a'),
+                PolicyViolation::ExcludedChar('b'),
+            ]
+        );
+    }
+
+    #[test]
+    fn generate_returns_an_error_if_the_policy_cannot_be_satisfied() {
+        assert_err!(PasswordPolicy::with_length(3).generate(&mut OsRng));
+        let policy = PasswordPolicy {
+            classes: Vec::new(),
+            ..PasswordPolicy::default()
+        };
+        assert_err!(policy.generate(&mut OsRng));
+    }
+}
@@ -0,0 +1,116 @@
+//! Reading the CSV files KeePass and KeePassXC export.
+use crate::{Entry, PassmateError};
+
+/// The group KeePass puts every other group in, left out of entry names.
+const ROOT_GROUP: &str = "Root";
+
+/// Parses a KeePass CSV export into entries named by their group path and
+/// title, like `Internet/github`, with the password as the secret and the
+/// username, URL and notes as metadata.
+///
+/// Columns are found by their names in the header row, in any order;
+/// other columns are ignored. Empty values are left unset.
+///
+/// # Errors
+/// Returns an error if the CSV is malformed or has no Title or Password column.
+pub fn parse_csv(text: &str) -> Result<Vec<(String, Entry)>, PassmateError> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(PassmateError::Csv)?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+    };
+    let required =
+        |name: &str| column(name).ok_or_else(|| PassmateError::MissingColumn(name.into()));
+    let (title, password) = (required("Title")?, required("Password")?);
+    let (group, username, url, notes) = (
+        column("Group"),
+        column("Username"),
+        column("URL"),
+        column("Notes"),
+    );
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(PassmateError::Csv)?;
+        let value = |index: Option<usize>| {
+            index
+                .and_then(|index| record.get(index))
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        let mut name: Vec<&str> = group
+            .and_then(|index| record.get(index))
+            .unwrap_or_default()
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect();
+        if name.first() == Some(&ROOT_GROUP) {
+            name.remove(0);
+        }
+        name.push(record.get(title).unwrap_or_default());
+        let entry = Entry {
+            username: value(username),
+            url: value(url),
+            notes: value(notes),
+            ..Entry::new(record.get(password).unwrap_or_default())
+        };
+        entries.push((name.join("/"), entry));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#""Group","Title","Username","Password","URL","Notes","TOTP","Icon","Last Modified","Created"
+"Root","email","me@example.com","hunter2","","","","0","2024-05-01T10:00:00Z","2024-05-01T10:00:00Z"
+"Root/Internet","github","octocat","p@ss, ""quoted""","https://github.com","first line
+second line","","1","2024-05-02T10:00:00Z","2024-05-02T10:00:00Z"
+"Root/Internet/Shops","store","","s3cret","https://store.example.com","","","0","2024-05-03T10:00:00Z","2024-05-03T10:00:00Z"
+"#;
+
+    #[test]
+    fn parse_csv_maps_a_keepass_export_to_entries() {
+        let entries = parse_csv(EXPORT).unwrap();
+
+        assert_eq!(
+            entries,
+            [
+                (
+                    "email".to_string(),
+                    Entry {
+                        username: Some("me@example.com".into()),
+                        ..Entry::new("hunter2")
+                    }
+                ),
+                (
+                    "Internet/github".to_string(),
+                    Entry {
+                        username: Some("octocat".into()),
+                        url: Some("https://github.com".into()),
+                        notes: Some("first line\nsecond line".into()),
+                        ..Entry::new(r#"p@ss, "quoted""#)
+                    }
+                ),
+                (
+                    "Internet/Shops/store".to_string(),
+                    Entry {
+                        url: Some("https://store.example.com".into()),
+                        ..Entry::new("s3cret")
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_csv_requires_a_title_and_password_column() {
+        assert!(matches!(
+            parse_csv("\"Group\",\"Title\",\"Username\"\n\"Root\",\"email\",\"me\"\n"),
+            Err(PassmateError::MissingColumn(column)) if column == "Password"
+        ));
+    }
+}
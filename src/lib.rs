@@ -4,248 +4,4931 @@ use aes_gcm::{
     aead::{self, Aead},
     AeadCore, Aes256Gcm, Key, KeyInit, Nonce,
 };
-use argon2::Argon2;
-use rand::{rngs::OsRng, Rng};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, CryptoRng, Rng, RngCore};
+use sha2::Sha256;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{ErrorKind, Read},
+    collections::{BTreeMap, HashMap},
+    io::{ErrorKind, Read, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
 
+pub mod config;
+pub mod doctor;
+pub mod editor;
+mod entry;
+pub mod format;
+pub mod generate;
+pub mod keepass;
+mod lines;
+pub mod passphrase;
+pub mod paths;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod select;
+#[cfg(feature = "age")]
+pub mod share;
+pub mod stats;
+pub mod style;
+pub mod time;
+
+use entry::StoredEntry;
+pub use entry::{Entry, EntryDetails, MASKED_SECRET};
+pub use generate::{PasswordPolicy, PolicyViolation};
+pub use stats::VaultStats;
+pub use zeroize::Zeroizing;
+
 #[derive(Debug, Error)]
 pub enum PassmateError {
-    #[error("An encryption error occurred: {0}")]
-    Encrypt(aead::Error),
-    #[error("A decryption error occurred: {0}")]
-    Decrypt(aead::Error),
-    #[error("Failed to make key: {0}")]
-    EncryptionKey(argon2::Error),
-    #[error("Failed to serialize vault: {0}")]
-    Json(serde_json::Error),
-    #[error("Error writing or reading vault: {0}")]
-    IO(std::io::Error),
+    #[error("An encryption error occurred")]
+    Encrypt(#[source] aead::Error),
+    #[error("A decryption error occurred")]
+    Decrypt(#[source] aead::Error),
+    #[error("Failed to make key")]
+    EncryptionKey(#[source] argon2::Error),
+    #[error("Failed to serialize vault")]
+    Json(#[source] serde_json::Error),
+    #[error("Failed to read or write YAML")]
+    Yaml(#[source] serde_yaml_ng::Error),
+    #[error("Failed to read CSV")]
+    Csv(#[source] csv::Error),
+    #[error("Missing column in CSV: {0}")]
+    MissingColumn(String),
+    #[error("Failed to use the keyring: {0}")]
+    Keyring(String),
+    #[error("Failed to read the credential: {0}")]
+    Credential(String),
+    #[error("age encryption failed: {0}")]
+    Age(String),
+    #[error("Failed to make a QR code: {0}")]
+    Qr(String),
+    #[error("Error writing or reading vault")]
+    IO(#[source] std::io::Error),
+    #[error("Invalid entry name: {0}")]
+    InvalidName(String),
+    #[error("Entry not found: {0}")]
+    EntryNotFound(String),
+    #[error("Tag can't be used as a file name: {0}")]
+    InvalidTag(String),
+    #[error("Invalid vault name: {0:?}")]
+    InvalidVaultName(String),
+    #[error("Invalid field name: {0}")]
+    InvalidFieldName(String),
+    #[error("Field not found: {0}")]
+    FieldNotFound(String),
+    #[error("Unknown fields in entry {name}: {}", .fields.join(", "))]
+    UnknownFields { name: String, fields: Vec<String> },
+    #[error("Entry appears more than once in the import: {0}")]
+    DuplicateName(String),
+    #[error("Name {name} matches several entries when ignoring case: {}", .matches.join(", "))]
+    AmbiguousName { name: String, matches: Vec<String> },
+    #[error("Invalid password policy: {0}")]
+    InvalidPolicy(String),
+    #[error("Invalid vault format: {0}")]
+    InvalidFormat(String),
+    #[error("Directory does not exist: {}", .0.display())]
+    MissingDirectory(PathBuf),
+    #[error("Vault already exists: {}", .0.display())]
+    VaultExists(PathBuf),
+    #[error("Permission denied: {}", .0.display())]
+    PermissionDenied(PathBuf),
+    #[error("{} is a directory, not a vault file", .0.display())]
+    InvalidPath(PathBuf),
+    #[error("Value is empty")]
+    EmptyValue,
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    #[error("Value is {size} bytes, which exceeds the limit of {limit} bytes")]
+    ValueTooLarge { size: usize, limit: usize },
+    #[error("Invalid passphrase{}", .hint.as_ref().map(|hint| format!(" (hint: {hint})")).unwrap_or_default())]
+    InvalidPassphrase { hint: Option<String> },
+    #[error("The entries didn't match after {attempts} attempts")]
+    SecretMismatch { attempts: u32 },
+    #[error("Key was derived from a different salt than the vault's")]
+    KeyMismatch,
+    #[error("The vault was opened with a key and has no passphrase to derive a new one from")]
+    NoPassphrase,
+    #[error("Invalid passphrase hint: {0}")]
+    InvalidHint(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Invalid time: {0}")]
+    InvalidTime(String),
+}
+
+/// The default limit on the size of a single value, in bytes.
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 1024 * 1024;
+
+/// A text format entries are imported from and exported to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    #[default]
+    Json,
+    Yaml,
+    /// Tab-separated `name<TAB>value` lines, which only hold secrets.
+    Lines,
+}
+
+impl DataFormat {
+    /// Detects the format from the extension of the path, if it has a known one.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    fn parse(
+        self,
+        text: &str,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, PassmateError> {
+        match self {
+            DataFormat::Json => serde_json::from_str(text).map_err(PassmateError::Json),
+            DataFormat::Yaml => serde_yaml_ng::from_str(text).map_err(PassmateError::Yaml),
+            DataFormat::Lines => lines::parse(text),
+        }
+    }
+
+    fn write(self, value: &impl serde::Serialize) -> Result<String, PassmateError> {
+        match self {
+            DataFormat::Json => serde_json::to_string_pretty(value).map_err(PassmateError::Json),
+            DataFormat::Yaml => serde_yaml_ng::to_string(value).map_err(PassmateError::Yaml),
+            DataFormat::Lines => match serde_json::to_value(value).map_err(PassmateError::Json)? {
+                serde_json::Value::Object(map) => lines::write(&map),
+                _ => Err(PassmateError::InvalidFormat(
+                    "lines can only hold a mapping of names to values".into(),
+                )),
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for DataFormat {
+    type Err = PassmateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(DataFormat::Json),
+            "yaml" | "yml" => Ok(DataFormat::Yaml),
+            "lines" => Ok(DataFormat::Lines),
+            _ => Err(PassmateError::InvalidFormat(format!(
+                "unknown format {s:?}, expected json, yaml or lines"
+            ))),
+        }
+    }
+}
+
+/// Controls how imported entries interact with existing ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// How to resolve existing entries that differ from the imported ones.
+    /// By default they're kept, skipping the imported ones.
+    pub strategy: MergeStrategy,
+    /// Report existing entries with an identical value as unchanged
+    /// instead of skipping or updating them.
+    pub dedupe: bool,
+    /// Reject structured entries with fields an entry doesn't have,
+    /// instead of dropping those fields.
+    pub strict: bool,
+}
+
+/// The outcome of importing a batch of entries into a vault.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Names of entries that didn't exist before the import.
+    pub added: Vec<String>,
+    /// Names of existing entries whose value was replaced.
+    pub updated: Vec<String>,
+    /// Names of existing entries kept by the strategy instead of the imported ones.
+    pub skipped: Vec<String>,
+    /// Names of existing entries that already had the imported value, when deduplicating.
+    pub unchanged: Vec<String>,
+    /// Entries that couldn't be imported, with the reason.
+    pub rejected: Vec<(String, PassmateError)>,
+}
+
+/// How to resolve entries that differ between two vaults being merged,
+/// or between a vault and an import.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep this vault's entry.
+    #[default]
+    KeepOurs,
+    /// Replace this vault's entry with the other vault's.
+    TakeTheirs,
+    /// Keep whichever entry was updated most recently, preferring ours on a tie.
+    Newest,
+}
+
+impl MergeStrategy {
+    /// Returns true if their entry should replace ours.
+    fn takes_theirs(self, ours: &Entry, theirs: &Entry) -> bool {
+        match self {
+            MergeStrategy::KeepOurs => false,
+            MergeStrategy::TakeTheirs => true,
+            MergeStrategy::Newest => theirs.updated_at > ours.updated_at,
+        }
+    }
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = PassmateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep-mine" | "keep-ours" => Ok(MergeStrategy::KeepOurs),
+            "keep-theirs" | "take-theirs" => Ok(MergeStrategy::TakeTheirs),
+            "keep-newest" | "newest" => Ok(MergeStrategy::Newest),
+            _ => Err(PassmateError::InvalidConfig(format!(
+                "unknown strategy {s:?}, expected keep-mine, keep-theirs or keep-newest"
+            ))),
+        }
+    }
+}
+
+/// How to resolve a single entry that differs between two vaults being merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep this vault's entry.
+    Mine,
+    /// Replace this vault's entry with the other vault's.
+    Theirs,
+    /// Keep this vault's entry but change its secret to the given value.
+    Value(String),
+}
+
+/// Why a vault file is out of date, from most to least important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeReason {
+    /// The file was written in an older format version.
+    OldFormat { version: u8 },
+    /// Entries without metadata are stored as bare strings.
+    LegacyEntries,
+    /// The key is derived with a variant or costs weaker than the defaults.
+    WeakKdf,
+}
+
+impl UpgradeReason {
+    /// Returns the most important reason a vault file with the given
+    /// header fields is out of date, or `None` if it's current.
+    #[must_use]
+    pub fn of(version: u8, kdf: &KdfParams, structured_entries: bool) -> Option<Self> {
+        let default = KdfParams::default();
+        if version < format::VERSION {
+            Some(Self::OldFormat { version })
+        } else if !structured_entries {
+            Some(Self::LegacyEntries)
+        } else if kdf.variant != default.variant
+            || kdf.m_cost < default.m_cost
+            || kdf.t_cost < default.t_cost
+        {
+            Some(Self::WeakKdf)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for UpgradeReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OldFormat { version } => write!(
+                f,
+                "it uses format version {version} rather than {}",
+                format::VERSION
+            ),
+            Self::LegacyEntries => f.write_str("its entries are stored in the legacy format"),
+            Self::WeakKdf => {
+                f.write_str("its key is derived with parameters weaker than the defaults")
+            }
+        }
+    }
+}
+
+/// The outcome of merging another vault into a vault.
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Names of entries that only existed in the other vault.
+    pub added: Vec<String>,
+    /// Names of entries replaced by the other vault's version, or given a new secret.
+    pub updated: Vec<String>,
+    /// Names of entries that differed, where this vault's version was kept.
+    pub conflicted: Vec<String>,
+    /// Entries resolved with an invalid new secret, with the reason.
+    /// They keep this vault's version.
+    pub rejected: Vec<(String, PassmateError)>,
+}
+
+/// The outcome of removing a batch of entries from a vault.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RemoveReport {
+    /// Names of the entries that were removed.
+    pub removed: Vec<String>,
+    /// Names that didn't match any entry.
+    pub not_found: Vec<String>,
+}
+
+/// A snapshot of a vault's entries taken by [`Vault::checkpoint`].
+#[derive(Clone)]
+pub struct Checkpoint {
+    data: HashMap<String, Entry>,
+}
+
+/// Parameters for deriving the encryption key from the passphrase with Argon2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KdfParams {
+    /// Which Argon2 variant to use, Argon2id unless another tool needs a different one.
+    pub variant: Argon2Variant,
+    /// Memory size in KiB.
+    pub m_cost: u32,
+    /// Number of iterations.
+    pub t_cost: u32,
+    /// Degree of parallelism.
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            variant: Argon2Variant::default(),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// A variant of the Argon2 key derivation function.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Argon2Variant {
+    Argon2d,
+    Argon2i,
+    #[default]
+    Argon2id,
+}
+
+impl Argon2Variant {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            Argon2Variant::Argon2d => Algorithm::Argon2d,
+            Argon2Variant::Argon2i => Algorithm::Argon2i,
+            Argon2Variant::Argon2id => Algorithm::Argon2id,
+        }
+    }
+}
+
+impl std::fmt::Display for Argon2Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Argon2Variant::Argon2d => "argon2d",
+            Argon2Variant::Argon2i => "argon2i",
+            Argon2Variant::Argon2id => "argon2id",
+        })
+    }
+}
+
+/// A container for passwords or other secrets.
+#[allow(clippy::struct_excessive_bools)]
+pub struct Vault {
+    path: PathBuf,
+    /// The passphrase new keys are derived from, or `None` for a vault
+    /// opened with a key, which can only be saved with that key.
+    passphrase: Option<Zeroizing<String>>,
+    kdf: KdfParams,
+    hint: Option<String>,
+    create_dirs: bool,
+    secure_delete: bool,
+    plaintext: bool,
+    reuse_salt: bool,
+    write_checksum: bool,
+    /// Whether the vault file didn't match its checksum file when opened.
+    checksum_mismatch: bool,
+    new: bool,
+    /// Whether entries without metadata are stored in structured form rather
+    /// than as bare strings. Vaults from before version 3 store bare strings
+    /// until they're upgraded.
+    structured_entries: bool,
+    /// The block size the plaintext is padded to a multiple of before
+    /// encrypting, or 0 to leave it unpadded.
+    padding: u32,
+    /// The format version of the encrypted file the vault was opened from.
+    format_version: Option<u8>,
+    /// The salt and key the vault was opened with, or first saved with
+    /// when reusing the salt, so they don't have to be derived again.
+    key: OnceLock<DerivedKey>,
+    /// The key fingerprints of secrets are computed with, generated
+    /// when the vault is created and stored along with its entries.
+    fingerprint_key: Zeroizing<[u8; 32]>,
+    max_value_size: Option<usize>,
+    allow_empty_values: bool,
+    allow_control_chars: bool,
+    data: HashMap<String, Entry>,
+    /// The entry names in alphabetical order, cleared whenever
+    /// an entry is added or removed.
+    sorted_names: OnceLock<Vec<String>>,
 }
 
-/// A container for passwords or other secrets.
-pub struct Vault {
-    path: PathBuf,
-    passphrase: String,
-    data: HashMap<String, String>,
-}
+impl Vault {
+    /// Opens the vault at the given path or returns
+    /// a empty vault if it doesn't already exist.
+    ///
+    /// # Errors
+    /// May return an error if opening, decrypting, or deserializing the vault data fails.
+    pub fn open(path: impl AsRef<Path>, passphrase: &str) -> Result<Self, PassmateError> {
+        match read_vault_file(path.as_ref())? {
+            Some(bytes) => Ok(Self {
+                path: PathBuf::from(path.as_ref()),
+                checksum_mismatch: checksum_mismatch(path.as_ref(), &bytes)?,
+                ..Self::open_bytes(&bytes, passphrase)?
+            }),
+            None => Ok(Self {
+                new: true,
+                ..Self::empty(path, passphrase)
+            }),
+        }
+    }
+
+    /// Opens the vault at the given path like [`Vault::open`], but only calls
+    /// `passphrase` to get the passphrase once the file turns out to be
+    /// missing or an encrypted vault, so callers don't prompt for it
+    /// needlessly when the file can't be opened anyway.
+    ///
+    /// A missing vault is returned empty and is encrypted with the
+    /// passphrase when it's saved.
+    ///
+    /// # Errors
+    /// Returns the error of the callback, or an error if opening,
+    /// decrypting, or deserializing the vault data fails. A plaintext
+    /// vault file is refused like [`Vault::open_bytes`] does, before
+    /// asking for the passphrase.
+    pub fn open_with<F>(path: impl AsRef<Path>, passphrase: F) -> Result<Self, PassmateError>
+    where
+        F: FnOnce() -> Result<Zeroizing<String>, PassmateError>,
+    {
+        let Some(bytes) = read_vault_file(path.as_ref())? else {
+            return Ok(Self {
+                new: true,
+                ..Self::empty(path, &passphrase()?)
+            });
+        };
+        refuse_plaintext(&bytes)?;
+        let vault = Self::open_bytes(&bytes, &passphrase()?)?;
+        Ok(Self {
+            path: PathBuf::from(path.as_ref()),
+            checksum_mismatch: checksum_mismatch(path.as_ref(), &bytes)?,
+            ..vault
+        })
+    }
+
+    /// Creates an encrypted vault at `vault_path` holding the secrets of a
+    /// plaintext JSON file mapping names to values, the format read by
+    /// [`Vault::import_json`], and saves it.
+    ///
+    /// With `delete_json`, the JSON file is overwritten and removed once the
+    /// vault is saved. Like [`Vault::set_secure_delete`], this is best-effort.
+    ///
+    /// # Errors
+    /// Returns an error if a file already exists at `vault_path`, reading or
+    /// parsing the JSON fails, an entry is invalid, saving the vault fails,
+    /// or deleting the JSON file fails.
+    pub fn from_json_file(
+        json_path: impl AsRef<Path>,
+        vault_path: impl AsRef<Path>,
+        passphrase: &str,
+        delete_json: bool,
+    ) -> Result<Self, PassmateError> {
+        let (json_path, vault_path) = (json_path.as_ref(), vault_path.as_ref());
+        if vault_path.exists() {
+            return Err(PassmateError::VaultExists(vault_path.into()));
+        }
+        let json = std::fs::read(json_path).map_err(PassmateError::IO)?;
+        let data = serde_json::from_slice(&json).map_err(PassmateError::Json)?;
+        let mut vault = Self::empty(vault_path, passphrase);
+        vault.replace_data(data)?;
+        vault.save()?;
+        if delete_json {
+            if let Some(file) = open_existing(json_path)? {
+                overwrite(file).map_err(PassmateError::IO)?;
+            }
+            std::fs::remove_file(json_path).map_err(PassmateError::IO)?;
+        }
+        Ok(vault)
+    }
+
+    /// Opens the existing vault at the given path with a key previously
+    /// returned by [`Vault::derived_key`], skipping the slow key derivation.
+    ///
+    /// The key is only used if the vault is still encrypted with the salt it
+    /// was derived from. Without the passphrase, saves reuse the salt and
+    /// key, and fail with [`PassmateError::NoPassphrase`] once a new key
+    /// would have to be derived, such as after changing the key derivation
+    /// parameters or turning off [`Vault::set_reuse_salt`], until a
+    /// passphrase is set with [`Vault::set_passphrase`].
+    ///
+    /// # Errors
+    /// Returns [`PassmateError::KeyMismatch`] if the vault's salt is not
+    /// `salt`, or an error if reading, decrypting, or deserializing the
+    /// vault data fails.
+    pub fn open_with_key(
+        path: impl AsRef<Path>,
+        key: [u8; 32],
+        salt: &[u8],
+    ) -> Result<Self, PassmateError> {
+        let bytes = read_existing_vault_file(path.as_ref())?;
+        let decoded = format::decode(&bytes)?;
+        if decoded.header.salt != salt {
+            return Err(PassmateError::KeyMismatch);
+        }
+        let data = decrypt_payload(&decoded, key)?;
+        Ok(Self {
+            checksum_mismatch: checksum_mismatch(path.as_ref(), &bytes)?,
+            structured_entries: decoded.header.structured_entries,
+            padding: decoded.header.padding,
+            format_version: Some(decoded.header.version),
+            kdf: decoded.header.kdf,
+            hint: decoded.header.hint,
+            reuse_salt: true,
+            key: OnceLock::from(DerivedKey {
+                salt: salt.to_vec(),
+                key,
+            }),
+            passphrase: None,
+            ..Self::empty(path, "").with_data(&data, decoded.header.version)?
+        })
+    }
+
+    /// Opens a vault from the encrypted contents of a vault file.
+    ///
+    /// The vault has no path to be saved to, use [`Vault::save_bytes`] instead.
+    ///
+    /// Plaintext vault files are refused, so that whoever can replace the
+    /// file can't get the next save to write the entries unencrypted.
+    /// Open them with [`Vault::open_plaintext_bytes`] instead.
+    ///
+    /// # Errors
+    /// Returns [`PassmateError::InvalidFormat`] for a plaintext vault file,
+    /// or an error if decrypting or deserializing the vault data fails.
+    pub fn open_bytes(bytes: &[u8], passphrase: &str) -> Result<Self, PassmateError> {
+        refuse_plaintext(bytes)?;
+        let (header, key, data) = unseal_with_header(bytes, passphrase)?;
+        Ok(Self {
+            structured_entries: header.structured_entries,
+            padding: header.padding,
+            format_version: Some(header.version),
+            kdf: header.kdf,
+            hint: header.hint,
+            key: OnceLock::from(key),
+            ..Self::empty(PathBuf::new(), passphrase).with_data(&data, header.version)?
+        })
+    }
+
+    /// Opens a vault by reading the encrypted contents of a vault file from the reader.
+    ///
+    /// The vault has no path to be saved to, use [`Vault::save_bytes`] instead.
+    ///
+    /// # Errors
+    /// May return an error if reading, decrypting, or deserializing the vault data fails.
+    pub fn open_reader(mut reader: impl Read, passphrase: &str) -> Result<Self, PassmateError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(PassmateError::IO)?;
+        Self::open_bytes(&bytes, passphrase)
+    }
+
+    /// Opens each of the existing vaults at the given paths with the same
+    /// passphrase, returning a result per path in the same order so that one
+    /// missing or unreadable vault doesn't stop the rest from opening.
+    ///
+    /// Unlike [`Vault::open`], a missing file is an error.
+    #[must_use]
+    pub fn open_many(paths: &[PathBuf], passphrase: &str) -> Vec<Result<Self, PassmateError>> {
+        paths
+            .iter()
+            .map(|path| {
+                let bytes = read_existing_vault_file(path)?;
+                Ok(Self {
+                    path: path.clone(),
+                    checksum_mismatch: checksum_mismatch(path, &bytes)?,
+                    ..Self::open_bytes(&bytes, passphrase)?
+                })
+            })
+            .collect()
+    }
+
+    /// Opens an unencrypted vault at the given path, or creates a new one
+    /// if there is no file at the path.
+    ///
+    /// Plaintext vaults are meant for non-secret data only.
+    ///
+    /// # Errors
+    /// Returns an error if reading the file fails, it holds an encrypted
+    /// vault, or deserializing the vault data fails.
+    pub fn open_plaintext(path: impl AsRef<Path>) -> Result<Self, PassmateError> {
+        match read_vault_file(path.as_ref())? {
+            Some(bytes) => Ok(Self {
+                path: PathBuf::from(path.as_ref()),
+                checksum_mismatch: checksum_mismatch(path.as_ref(), &bytes)?,
+                ..Self::open_plaintext_bytes(&bytes)?
+            }),
+            None => Ok(Self {
+                plaintext: true,
+                new: true,
+                ..Self::empty(path, "")
+            }),
+        }
+    }
+
+    /// Opens an unencrypted vault from the contents of a plaintext vault file.
+    ///
+    /// # Errors
+    /// Returns an error if the contents are not a plaintext vault,
+    /// or deserializing the vault data fails.
+    pub fn open_plaintext_bytes(bytes: &[u8]) -> Result<Self, PassmateError> {
+        let (version, data) = format::decode_plaintext(bytes)
+            .ok_or_else(|| PassmateError::InvalidFormat("the vault is encrypted".into()))?;
+        Ok(Self {
+            plaintext: true,
+            structured_entries: version > format::COMPACT_ENTRIES_VERSION,
+            ..Self::empty(PathBuf::new(), "").with_data(data, version)?
+        })
+    }
+
+    /// Fills an empty vault with the data of a vault file of the given
+    /// format version, keeping its fingerprint key if the version stores one.
+    fn with_data(mut self, data: &[u8], version: u8) -> Result<Self, PassmateError> {
+        if version >= format::FINGERPRINT_KEY_VERSION {
+            let stored: StoredData<[u8; 32], HashMap<String, StoredEntry<String, Entry>>> =
+                serde_json::from_slice(data).map_err(PassmateError::Json)?;
+            self.fingerprint_key = Zeroizing::new(stored.fingerprint_key);
+            self.data = from_stored_entries(stored.entries);
+        } else {
+            self.data = deserialize_entries(data)?;
+        }
+        Ok(self)
+    }
+
+    fn empty(path: impl AsRef<Path>, passphrase: &str) -> Self {
+        Self {
+            path: PathBuf::from(path.as_ref()),
+            passphrase: Some(Zeroizing::new(passphrase.into())),
+            kdf: KdfParams::default(),
+            hint: None,
+            create_dirs: true,
+            secure_delete: false,
+            plaintext: false,
+            reuse_salt: false,
+            write_checksum: false,
+            checksum_mismatch: false,
+            new: false,
+            structured_entries: true,
+            padding: 0,
+            format_version: None,
+            key: OnceLock::new(),
+            fingerprint_key: Zeroizing::new(OsRng.gen()),
+            max_value_size: Some(DEFAULT_MAX_VALUE_SIZE),
+            allow_empty_values: false,
+            allow_control_chars: false,
+            data: HashMap::new(),
+            sorted_names: OnceLock::new(),
+        }
+    }
+
+    /// Returns the salt and key the vault was opened with, if it was opened
+    /// from an encrypted file, to reopen it later with [`Vault::open_with_key`].
+    #[must_use]
+    pub fn derived_key(&self) -> Option<(Vec<u8>, [u8; 32])> {
+        self.key.get().map(|key| (key.salt.clone(), key.key))
+    }
+
+    /// Returns a list of entry names in alphabetical order.
+    #[must_use]
+    pub fn entries(&self) -> Vec<String> {
+        self.entries_matching(|_| true)
+    }
+
+    /// Returns the number of entries in the vault.
+    #[must_use]
+    pub fn entries_count(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the entry names in an arbitrary order that may change
+    /// between calls. Cheaper than [`Vault::entries`] when the order
+    /// doesn't matter, as the names aren't sorted.
+    #[must_use]
+    pub fn entry_names_unsorted(&self) -> Vec<String> {
+        self.data.keys().cloned().collect()
+    }
+
+    /// Iterates over the entry names in alphabetical order, like
+    /// [`Vault::entries`] but borrowing the names instead of cloning them.
+    pub fn entries_iter_sorted(&self) -> impl Iterator<Item = &str> + '_ {
+        self.sorted_names().iter().map(String::as_str)
+    }
+
+    /// Returns the names of entries for which the predicate
+    /// returns true, in alphabetical order.
+    #[must_use]
+    pub fn entries_matching(&self, pred: impl Fn(&str) -> bool) -> Vec<String> {
+        self.sorted_names()
+            .iter()
+            .filter(|name| pred(name))
+            .cloned()
+            .collect()
+    }
+
+    fn sorted_names(&self) -> &[String] {
+        self.sorted_names.get_or_init(|| {
+            let mut names: Vec<String> = self.data.keys().cloned().collect();
+            names.sort();
+            names
+        })
+    }
+
+    /// Switches the vault to storing every entry in structured form, the
+    /// current format version, the next time it's saved. Entries keep their
+    /// secrets and metadata. Returns false if the vault was already upgraded.
+    pub fn upgrade_entries(&mut self) -> bool {
+        !std::mem::replace(&mut self.structured_entries, true)
+    }
+
+    /// Returns why the file the vault was opened from is out of date, so
+    /// that saving it again or re-creating it would improve it, or `None` if
+    /// it's current. New vaults are always current.
+    #[must_use]
+    pub fn needs_upgrade(&self) -> Option<UpgradeReason> {
+        match self.format_version {
+            Some(version) => UpgradeReason::of(version, &self.kdf, self.structured_entries),
+            None if !self.structured_entries => Some(UpgradeReason::LegacyEntries),
+            None => None,
+        }
+    }
+
+    /// Trims the history of every entry to its `keep` most recent previous
+    /// secrets, dropping the oldest. A `keep` of 0 clears all history.
+    /// Returns the number of previous secrets removed.
+    pub fn prune_history(&mut self, keep: usize) -> usize {
+        self.data
+            .values_mut()
+            .map(|entry| {
+                let excess = entry.history.len().saturating_sub(keep);
+                entry.history.drain(..excess);
+                excess
+            })
+            .sum()
+    }
+
+    /// Returns the names of entries created or updated at or after the
+    /// given time, in alphabetical order. Entries without timestamps are left out.
+    #[must_use]
+    pub fn entries_modified_since(&self, since: SystemTime) -> Vec<String> {
+        let since = time::unix_secs(since);
+        self.entries_matching(|name| {
+            let entry = &self.data[name];
+            entry.created_at.max(entry.updated_at) >= Some(since)
+        })
+    }
+
+    /// Returns the names of entries starting with the given prefix,
+    /// in alphabetical order.
+    #[must_use]
+    pub fn entries_with_prefix(&self, prefix: &str) -> Vec<String> {
+        self.entries_matching(|name| name.starts_with(prefix))
+    }
+
+    /// Returns the names of entries with the given tag, in alphabetical order.
+    #[must_use]
+    pub fn entries_with_tag(&self, tag: &str) -> Vec<String> {
+        self.entries_matching(|name| self.data[name].tags.iter().any(|t| t == tag))
+    }
+
+    /// Returns every tag of the vault's entries once, in alphabetical order.
+    #[must_use]
+    pub fn tags(&self) -> Vec<String> {
+        let tags: std::collections::BTreeSet<&String> =
+            self.data.values().flat_map(|entry| &entry.tags).collect();
+        tags.into_iter().cloned().collect()
+    }
+
+    /// Saves the entries of each tag to a vault of their own in `dir`, named
+    /// after the tag like `work.vault` and encrypted with the passphrase.
+    /// Entries with several tags are in each of their vaults, while entries
+    /// without tags are left out. Returns the paths of the vaults written.
+    ///
+    /// The directory is created if it's missing.
+    ///
+    /// # Errors
+    /// Returns an error if a tag can't be used as a file name, one of the
+    /// vault files already exists, or saving a vault fails. Nothing is
+    /// written if a tag or path is rejected, and the vaults already written
+    /// are removed if saving a later one fails.
+    pub fn export_by_tag(
+        &self,
+        dir: impl AsRef<Path>,
+        passphrase: &str,
+    ) -> Result<Vec<PathBuf>, PassmateError> {
+        let dir = dir.as_ref();
+        let tags = self.tags();
+        let mut vault_paths = Vec::with_capacity(tags.len());
+        for tag in &tags {
+            if tag.is_empty() || tag == "." || tag == ".." || tag.contains(['/', '\\']) {
+                return Err(PassmateError::InvalidTag(tag.clone()));
+            }
+            let path = dir.join(format!("{tag}.{}", paths::VAULT_EXTENSION));
+            if path.exists() {
+                return Err(PassmateError::VaultExists(path));
+            }
+            vault_paths.push(path);
+        }
+        for (i, (tag, path)) in tags.iter().zip(&vault_paths).enumerate() {
+            let mut vault = Self::empty(path, passphrase);
+            vault.kdf = self.kdf;
+            vault.data = self
+                .entries_with_tag(tag)
+                .into_iter()
+                .map(|name| {
+                    let entry = self.data[&name].clone();
+                    (name, entry)
+                })
+                .collect();
+            if let Err(e) = vault.save() {
+                for written in &vault_paths[..i] {
+                    let _ = std::fs::remove_file(written);
+                }
+                return Err(e);
+            }
+        }
+        Ok(vault_paths)
+    }
+
+    /// Returns the names of entries whose value is empty, which are usually
+    /// left by a mistake such as a failed pipe, in alphabetical order.
+    #[must_use]
+    pub fn empty_value_entries(&self) -> Vec<String> {
+        self.entries_matching(|name| self.data[name].secret.is_empty())
+    }
+
+    /// Returns groups of entry names that are equal when ignoring ASCII case,
+    /// such as `GitHub` and `github`. Each group is sorted, as are the groups.
+    #[must_use]
+    pub fn case_collisions(&self) -> Vec<Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for name in self.entries() {
+            groups
+                .entry(name.to_ascii_lowercase())
+                .or_default()
+                .push(name);
+        }
+        groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect()
+    }
+
+    /// Returns the secrets of all entries, in the alphabetical order of their names.
+    #[must_use]
+    pub fn values(&self) -> Vec<&str> {
+        self.entries()
+            .iter()
+            .map(|name| self.data[name].secret.as_str())
+            .collect()
+    }
+
+    /// Checks every entry's secret against the policy, returning the entries
+    /// that break at least one rule along with the rules they break,
+    /// in alphabetical order.
+    #[must_use]
+    pub fn validate_all(&self, policy: &PasswordPolicy) -> Vec<(String, Vec<PolicyViolation>)> {
+        self.entries()
+            .into_iter()
+            .filter_map(|name| {
+                let violations = policy.violations(&self.data[&name].secret);
+                (!violations.is_empty()).then_some((name, violations))
+            })
+            .collect()
+    }
+
+    /// Returns statistics about the vault's secrets without revealing them.
+    #[must_use]
+    pub fn stats(&self) -> VaultStats {
+        VaultStats::from_values(self.values())
+    }
+
+    /// Looks up the secret of an entry by the given name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.data.get(name).map(|entry| &entry.secret)
+    }
+
+    /// Looks up the secret of an entry like [`Vault::get`], falling back to
+    /// the one entry whose name only differs by ASCII case, such as `GitHub`
+    /// for `github`. An exact match always wins.
+    ///
+    /// # Errors
+    /// Returns [`PassmateError::AmbiguousName`] if there's no exact match
+    /// and several names match when ignoring case.
+    pub fn get_ci(&self, name: &str) -> Result<Option<&String>, PassmateError> {
+        if let Some(secret) = self.get(name) {
+            return Ok(Some(secret));
+        }
+        let matches = self.entries_matching(|entry| entry.eq_ignore_ascii_case(name));
+        match matches.as_slice() {
+            [] => Ok(None),
+            [found] => Ok(self.get(found)),
+            _ => Err(PassmateError::AmbiguousName {
+                name: name.into(),
+                matches,
+            }),
+        }
+    }
+
+    /// Looks up the secrets of several entries at once, pairing each
+    /// requested name with its secret, or `None` if there's no such entry,
+    /// in the order requested.
+    #[must_use]
+    pub fn get_all(&self, names: &[&str]) -> Vec<(String, Option<&str>)> {
+        names
+            .iter()
+            .map(|&name| (name.to_string(), self.get(name).map(String::as_str)))
+            .collect()
+    }
+
+    /// Returns the length in bytes of an entry's secret without exposing it.
+    #[must_use]
+    pub fn value_len(&self, name: &str) -> Option<usize> {
+        self.get(name).map(String::len)
+    }
+
+    /// Returns a fingerprint of an entry's secret, to tell whether it
+    /// changed or matches another entry without exposing it.
+    ///
+    /// The fingerprint is an HMAC keyed by a random key the vault stores
+    /// along with its entries, so identical secrets share a fingerprint
+    /// within the vault, and across saves and copies of it, but not across
+    /// vaults. Vault files from before [`format::FINGERPRINT_KEY_VERSION`]
+    /// get a new key each time they're opened, until they're saved.
+    /// Returns `None` if there's no such entry.
+    #[must_use]
+    pub fn fingerprint(&self, name: &str) -> Option<String> {
+        let secret = self.get(name)?;
+        Some(format::hex(&hmac_sha256(
+            self.fingerprint_key.as_slice(),
+            secret.as_bytes(),
+        )))
+    }
+
+    /// Looks up an entry, including its metadata, by the given name.
+    #[must_use]
+    pub fn get_entry(&self, name: &str) -> Option<&Entry> {
+        self.data.get(name)
+    }
+
+    /// Adds or updates an entry with the given name.
+    ///
+    /// Updating an existing entry keeps its metadata and
+    /// pushes the previous value to its history.
+    ///
+    /// The name and value are validated before the vault is modified,
+    /// so a failed call leaves the vault unchanged.
+    ///
+    /// # Errors
+    /// Returns an error if the name or value is invalid.
+    pub fn set<S>(&mut self, name: S, value: S) -> Result<(), PassmateError>
+    where
+        S: Into<String>,
+    {
+        let (name, value) = (name.into(), value.into());
+        self.validate(&name, &value)?;
+        self.store_secret(name, value);
+        Ok(())
+    }
+
+    fn store_secret(&mut self, name: String, value: String) {
+        let now = now();
+        if let Some(entry) = self.data.get_mut(&name) {
+            entry.update_secret(value, now);
+        } else {
+            let entry = Entry {
+                created_at: Some(now),
+                updated_at: Some(now),
+                ..Entry::new(value)
+            };
+            self.data.insert(name, entry);
+            self.sorted_names.take();
+        }
+    }
+
+    /// Adds or replaces an entry, including its metadata, with the given name.
+    ///
+    /// Missing timestamps are filled in, keeping the creation
+    /// time of the entry being replaced.
+    ///
+    /// # Errors
+    /// Returns an error if the name or secret is invalid.
+    pub fn set_entry(
+        &mut self,
+        name: impl Into<String>,
+        entry: Entry,
+    ) -> Result<(), PassmateError> {
+        let name = name.into();
+        self.validate(&name, &entry.secret)?;
+        self.store_entry(name, entry);
+        Ok(())
+    }
+
+    fn store_entry(&mut self, name: String, mut entry: Entry) {
+        let now = now();
+        let created_at = self
+            .data
+            .get(&name)
+            .and_then(|existing| existing.created_at);
+        entry.created_at = entry.created_at.or(created_at).or(Some(now));
+        entry.updated_at = entry.updated_at.or(Some(now));
+        if self.data.insert(name, entry).is_none() {
+            self.sorted_names.take();
+        }
+    }
+
+    /// Imports entries from a JSON object mapping names to values, or to
+    /// entries with metadata as written by [`Vault::export_entries_json`].
+    ///
+    /// Existing entries that differ from the imported ones are resolved by
+    /// [`ImportOptions::strategy`], which keeps them by default; see
+    /// [`MergeStrategy`] for the alternatives.
+    ///
+    /// # Errors
+    /// Returns an error if the JSON isn't an object of values or entries,
+    /// or, in strict mode, an entry has fields an entry doesn't have.
+    pub fn import_json(
+        &mut self,
+        json: &str,
+        options: ImportOptions,
+    ) -> Result<ImportReport, PassmateError> {
+        self.import(json, DataFormat::Json, options)
+    }
+
+    /// Imports entries like [`Vault::import_json`] from text in the given format.
+    ///
+    /// # Errors
+    /// Returns an error if the text isn't a mapping of names to values or
+    /// entries, or, in strict mode, an entry has fields an entry doesn't have.
+    pub fn import(
+        &mut self,
+        text: &str,
+        format: DataFormat,
+        options: ImportOptions,
+    ) -> Result<ImportReport, PassmateError> {
+        let map = format.parse(text)?;
+        let mut entries = Vec::with_capacity(map.len());
+        for (name, value) in map {
+            if let (true, serde_json::Value::Object(fields)) = (options.strict, &value) {
+                let unknown: Vec<String> = fields
+                    .keys()
+                    .filter(|field| !entry::FIELDS.contains(&field.as_str()))
+                    .cloned()
+                    .collect();
+                if !unknown.is_empty() {
+                    return Err(PassmateError::UnknownFields {
+                        name,
+                        fields: unknown,
+                    });
+                }
+            }
+            let stored: StoredEntry<String, Entry> =
+                serde_json::from_value(value).map_err(PassmateError::Json)?;
+            entries.push((name, stored.into()));
+        }
+        Ok(self.import_entries(entries, options))
+    }
+
+    /// Imports the entries of a KeePass CSV export, read with
+    /// [`keepass::parse_csv`], like [`Vault::import`] does. Entries with the
+    /// same group and title are rejected after the first in the file.
+    ///
+    /// # Errors
+    /// Returns an error if the CSV is malformed or isn't a KeePass export.
+    pub fn import_keepass_csv(
+        &mut self,
+        csv: &str,
+        options: ImportOptions,
+    ) -> Result<ImportReport, PassmateError> {
+        Ok(self.import_entries(keepass::parse_csv(csv)?, options))
+    }
+
+    /// Returns the vault's entries as a JSON object mapping names to values,
+    /// the format read by [`Vault::import_json`].
+    ///
+    /// # Errors
+    /// Returns an error if serializing the entries fails.
+    pub fn export_json(&self) -> Result<String, PassmateError> {
+        self.export_secrets(DataFormat::Json)
+    }
+
+    /// Returns the vault's entries as a mapping of names to values in the
+    /// given format, the form read by [`Vault::import`].
+    ///
+    /// # Errors
+    /// Returns an error if serializing the entries fails.
+    pub fn export_secrets(&self, format: DataFormat) -> Result<String, PassmateError> {
+        let map: BTreeMap<&str, &str> = self
+            .data
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry.secret.as_str()))
+            .collect();
+        format.write(&map)
+    }
+
+    /// Returns the vault's entries as a JSON object mapping names to
+    /// entries with all of their metadata and history.
+    ///
+    /// # Errors
+    /// Returns an error if serializing the entries fails.
+    pub fn export_entries_json(&self) -> Result<String, PassmateError> {
+        self.export_entries(DataFormat::Json)
+    }
+
+    /// Returns the vault's entries with all of their metadata and history
+    /// as a mapping keyed by name in the given format.
+    ///
+    /// [`DataFormat::Lines`] only holds secrets, so it gets the output of
+    /// [`Vault::export_secrets`] instead.
+    ///
+    /// # Errors
+    /// Returns an error if serializing the entries fails.
+    pub fn export_entries(&self, format: DataFormat) -> Result<String, PassmateError> {
+        if format == DataFormat::Lines {
+            return self.export_secrets(format);
+        }
+        let map: BTreeMap<&str, &Entry> = self
+            .data
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+            .collect();
+        format.write(&map)
+    }
+
+    /// Merges the entries of another vault into this one, resolving
+    /// entries that exist in both but differ with the strategy.
+    ///
+    /// An entry of theirs that wins replaces ours along with its metadata,
+    /// including custom fields, while our secrets are kept in its history.
+    pub fn merge(&mut self, other: &Vault, strategy: MergeStrategy) -> MergeReport {
+        self.merge_by(other, |_, ours, theirs| {
+            if strategy.takes_theirs(ours, theirs) {
+                Resolution::Theirs
+            } else {
+                Resolution::Mine
+            }
+        })
+    }
+
+    /// Merges the entries of another vault into this one, calling the
+    /// resolver with the name, our secret and their secret of each entry
+    /// that exists in both but differs, in name order.
+    ///
+    /// Entries resolved with [`Resolution::Mine`] are reported as conflicted.
+    /// New secrets given with [`Resolution::Value`] are validated like
+    /// [`Vault::set`] does, and invalid ones are reported as rejected.
+    pub fn merge_with(
+        &mut self,
+        other: &Vault,
+        mut resolver: impl FnMut(&str, &str, &str) -> Resolution,
+    ) -> MergeReport {
+        self.merge_by(other, |name, ours, theirs| {
+            resolver(name, &ours.secret, &theirs.secret)
+        })
+    }
+
+    fn merge_by(
+        &mut self,
+        other: &Vault,
+        mut resolve: impl FnMut(&str, &Entry, &Entry) -> Resolution,
+    ) -> MergeReport {
+        let mut report = MergeReport::default();
+        for name in other.entries() {
+            let theirs = &other.data[&name];
+            let Some(ours) = self.data.get(&name) else {
+                self.data.insert(name.clone(), theirs.clone());
+                self.sorted_names.take();
+                report.added.push(name);
+                continue;
+            };
+            if ours == theirs {
+                continue;
+            }
+            match resolve(&name, ours, theirs) {
+                Resolution::Theirs => {
+                    let mut entry = theirs.clone();
+                    entry.keep_history_of(ours);
+                    self.data.insert(name.clone(), entry);
+                    report.updated.push(name);
+                }
+                Resolution::Value(value) if value != ours.secret => {
+                    match self.validate(&name, &value) {
+                        Ok(()) => {
+                            self.store_secret(name.clone(), value);
+                            report.updated.push(name);
+                        }
+                        Err(err) => report.rejected.push((name, err)),
+                    }
+                }
+                Resolution::Mine | Resolution::Value(_) => report.conflicted.push(name),
+            }
+        }
+        report
+    }
+
+    /// Opens the vault file at the given path and merges its entries into this vault.
+    ///
+    /// # Errors
+    /// Returns an error if the file doesn't exist or can't be opened with the passphrase.
+    pub fn merge_from_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        passphrase: &str,
+        strategy: MergeStrategy,
+    ) -> Result<MergeReport, PassmateError> {
+        let bytes = read_existing_vault_file(path.as_ref())?;
+        let other = Vault::open_bytes(&bytes, passphrase)?;
+        Ok(self.merge(&other, strategy))
+    }
+
+    /// Adds the given entries to the vault, in name order.
+    ///
+    /// Existing entries that differ from the imported ones are resolved by
+    /// [`ImportOptions::strategy`], which keeps them by default; see
+    /// [`MergeStrategy`] for the alternatives. Entries with an invalid name or value are reported as rejected
+    /// and don't stop the rest of the import. Values may contain control
+    /// characters, see [`Vault::set_allow_control_chars`].
+    pub fn set_many_from_map(
+        &mut self,
+        map: HashMap<String, String>,
+        options: ImportOptions,
+    ) -> ImportReport {
+        let entries = map
+            .into_iter()
+            .map(|(name, value)| (name, Entry::new(value)))
+            .collect();
+        self.import_entries(entries, options)
+    }
+
+    /// Adds the entries like [`Vault::set_many_from_map`]. Existing entries
+    /// are resolved with the options' strategy, like [`Vault::merge`] does.
+    /// Entries with metadata replace the metadata of existing ones, keeping
+    /// their secrets in the history, while bare secrets only update the
+    /// secret of an existing entry.
+    ///
+    /// Bare secrets have no update time, so [`MergeStrategy::Newest`]
+    /// never prefers them over an existing entry. When several entries have
+    /// the same name, the first is imported and the others are rejected.
+    fn import_entries(
+        &mut self,
+        mut entries: Vec<(String, Entry)>,
+        options: ImportOptions,
+    ) -> ImportReport {
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut report = ImportReport::default();
+        let mut previous: Option<String> = None;
+        for (name, entry) in entries {
+            if previous.as_ref() == Some(&name) {
+                report
+                    .rejected
+                    .push((name.clone(), PassmateError::DuplicateName(name)));
+                continue;
+            }
+            previous = Some(name.clone());
+            let existing = self.data.get(&name);
+            if options.dedupe && existing.is_some_and(|existing| existing.secret == entry.secret) {
+                report.unchanged.push(name);
+                continue;
+            }
+            let exists = existing.is_some();
+            if existing.is_some_and(|existing| !options.strategy.takes_theirs(existing, &entry)) {
+                report.skipped.push(name);
+                continue;
+            }
+            let result = self.validate_imported(&name, &entry.secret).map(|()| {
+                if entry.is_bare() {
+                    self.store_secret(name.clone(), entry.secret);
+                } else {
+                    let mut entry = entry;
+                    if let Some(existing) = self.data.get(&name) {
+                        entry.keep_history_of(existing);
+                    }
+                    self.store_entry(name.clone(), entry);
+                }
+            });
+            match result {
+                Ok(()) if exists => report.updated.push(name),
+                Ok(()) => report.added.push(name),
+                Err(err) => report.rejected.push((name, err)),
+            }
+        }
+        report
+    }
+
+    /// Replaces the secret of an existing entry with a newly generated
+    /// password, keeping its metadata and pushing the old value to its history.
+    /// Returns the new password.
+    ///
+    /// # Errors
+    /// Returns an error if the entry doesn't exist or the policy can't be satisfied.
+    pub fn rotate(&mut self, name: &str, policy: &PasswordPolicy) -> Result<String, PassmateError> {
+        let entry = self
+            .data
+            .get_mut(name)
+            .ok_or_else(|| PassmateError::EntryNotFound(name.into()))?;
+        let password = policy.generate(&mut OsRng)?;
+        entry.update_secret(password.clone(), now());
+        Ok(password)
+    }
+
+    /// Looks up a custom field of an entry.
+    #[must_use]
+    pub fn field(&self, name: &str, field: &str) -> Option<&str> {
+        self.data.get(name)?.fields.get(field).map(String::as_str)
+    }
+
+    /// Adds or updates a custom field of an existing entry. The value is
+    /// checked like a secret's.
+    ///
+    /// # Errors
+    /// Returns an error if the entry doesn't exist, or the field name or
+    /// value is invalid.
+    pub fn set_field(&mut self, name: &str, field: &str, value: &str) -> Result<(), PassmateError> {
+        validate_field_name(field)?;
+        self.validate_value(value)?;
+        self.entry_mut(name)?
+            .fields
+            .insert(field.into(), value.into());
+        Ok(())
+    }
+
+    /// Renames a custom field of an existing entry, keeping its value.
+    ///
+    /// # Errors
+    /// Returns an error if the entry or field doesn't exist, or the new
+    /// field name is invalid or already taken.
+    pub fn rename_field(&mut self, name: &str, old: &str, new: &str) -> Result<(), PassmateError> {
+        validate_field_name(new)?;
+        let fields = &mut self.entry_mut(name)?.fields;
+        if fields.contains_key(new) {
+            return Err(PassmateError::InvalidFieldName(format!(
+                "{new:?} already exists"
+            )));
+        }
+        let value = fields
+            .remove(old)
+            .ok_or_else(|| PassmateError::FieldNotFound(old.into()))?;
+        fields.insert(new.into(), value);
+        Ok(())
+    }
+
+    /// Removes a custom field of an existing entry.
+    ///
+    /// # Errors
+    /// Returns an error if the entry or field doesn't exist.
+    pub fn remove_field(&mut self, name: &str, field: &str) -> Result<(), PassmateError> {
+        self.entry_mut(name)?
+            .fields
+            .remove(field)
+            .map(drop)
+            .ok_or_else(|| PassmateError::FieldNotFound(field.into()))
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Result<&mut Entry, PassmateError> {
+        self.data
+            .get_mut(name)
+            .ok_or_else(|| PassmateError::EntryNotFound(name.into()))
+    }
+
+    /// Marks an existing entry as updated now, such as after reviewing it,
+    /// without changing its secret or history.
+    ///
+    /// # Errors
+    /// Returns an error if the entry doesn't exist.
+    pub fn touch(&mut self, name: &str) -> Result<(), PassmateError> {
+        self.entry_mut(name)?.updated_at = Some(now());
+        Ok(())
+    }
+
+    /// Removes the entry with the given name, returning false if there
+    /// was no such entry.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let removed = self.data.remove(name).is_some();
+        if removed {
+            self.sorted_names.take();
+        }
+        removed
+    }
+
+    /// Removes every entry with one of the given names, reporting
+    /// which names were removed and which weren't found.
+    pub fn remove_many<I, S>(&mut self, names: I) -> RemoveReport
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut report = RemoveReport::default();
+        for name in names {
+            let name = name.into();
+            if self.data.remove(&name).is_some() {
+                self.sorted_names.take();
+                report.removed.push(name);
+            } else {
+                report.not_found.push(name);
+            }
+        }
+        report
+    }
+
+    /// Replaces every entry in the vault with the given secrets at once,
+    /// such as when restoring a backup. The new entries have no metadata
+    /// or history.
+    ///
+    /// Every name and value is validated first, so a failed call leaves
+    /// the vault unchanged. Like imports, values may contain control
+    /// characters, see [`Vault::set_allow_control_chars`].
+    ///
+    /// # Errors
+    /// Returns the error of the first invalid entry in name order.
+    pub fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), PassmateError> {
+        let mut names: Vec<&String> = data.keys().collect();
+        names.sort();
+        for name in names {
+            self.validate_imported(name, &data[name])?;
+        }
+        let now = now();
+        self.sorted_names.take();
+        self.data = data
+            .into_iter()
+            .map(|(name, value)| {
+                let entry = Entry {
+                    created_at: Some(now),
+                    updated_at: Some(now),
+                    ..Entry::new(value)
+                };
+                (name, entry)
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Captures the vault's entries in memory, so that a multi-step change
+    /// that fails partway can be undone with [`Vault::rollback`].
+    #[must_use]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            data: self.data.clone(),
+        }
+    }
+
+    /// Restores the entries captured by the checkpoint, undoing every change
+    /// to them since. The vault's settings are left as they are, and
+    /// nothing is saved.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.sorted_names.take();
+        self.data = checkpoint.data;
+    }
+
+    /// Returns true if both vaults contain the same entries,
+    /// ignoring their paths and passphrases.
+    #[must_use]
+    pub fn data_eq(&self, other: &Vault) -> bool {
+        self.data == other.data
+    }
+
+    /// Returns the passphrase hint stored unencrypted in the vault file.
+    #[must_use]
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// Sets or removes the passphrase hint, which is stored unencrypted
+    /// in the vault file and shown when the vault fails to open.
+    ///
+    /// # Errors
+    /// Returns an error if the hint is the passphrase itself or is longer than 65535 bytes.
+    pub fn set_hint(&mut self, hint: Option<String>) -> Result<(), PassmateError> {
+        if let Some(hint) = &hint {
+            if self
+                .passphrase
+                .as_ref()
+                .is_some_and(|passphrase| passphrase.as_str() == hint)
+            {
+                return Err(PassmateError::InvalidHint(
+                    "the hint must not be the passphrase".into(),
+                ));
+            }
+            if hint.len() > usize::from(u16::MAX) {
+                return Err(PassmateError::InvalidHint("the hint is too long".into()));
+            }
+        }
+        self.hint = hint.filter(|hint| !hint.is_empty());
+        Ok(())
+    }
+
+    /// Sets the passphrase the vault is encrypted with the next time it's saved,
+    /// returning the previous one so it can be restored if saving fails, or
+    /// `None` if the vault was opened with a key.
+    pub fn set_passphrase(&mut self, new: &str) -> Option<Zeroizing<String>> {
+        self.key = OnceLock::new();
+        self.passphrase.replace(Zeroizing::new(new.into()))
+    }
+
+    /// Sets the key derivation parameters used the next time the vault is saved.
+    ///
+    /// # Errors
+    /// Returns [`PassmateError::InvalidConfig`] if the parameters are outside
+    /// the ranges a vault file can be opened with, such as a time cost above
+    /// [`format::MAX_T_COST`], leaving the current ones in place.
+    pub fn set_kdf_params(&mut self, kdf: KdfParams) -> Result<(), PassmateError> {
+        format::check_kdf(&kdf).map_err(PassmateError::InvalidConfig)?;
+        self.kdf = kdf;
+        self.key = OnceLock::new();
+        Ok(())
+    }
+
+    /// Pads the vault data to a multiple of the block size in bytes before
+    /// encrypting it the next time the vault is saved, so the file size only
+    /// reveals roughly how much it holds. A block size of 0 disables padding.
+    /// Plaintext vaults are never padded.
+    ///
+    /// # Errors
+    /// Returns [`PassmateError::InvalidConfig`] if the block size isn't a
+    /// power of two up to [`format::MAX_PADDING`].
+    pub fn set_padding(&mut self, block_size: u32) -> Result<(), PassmateError> {
+        format::check_padding(block_size).map_err(PassmateError::InvalidConfig)?;
+        self.padding = block_size;
+        Ok(())
+    }
+
+    /// Sets the limit on the size of a single value in bytes, or
+    /// removes it with `None`. Defaults to [`DEFAULT_MAX_VALUE_SIZE`].
+    pub fn set_max_value_size(&mut self, limit: Option<usize>) {
+        self.max_value_size = limit;
+    }
+
+    /// Sets whether entries may have an empty value, which is usually a
+    /// mistake such as an unset variable in a script. Disabled by default.
+    pub fn set_allow_empty_values(&mut self, allow: bool) {
+        self.allow_empty_values = allow;
+    }
+
+    /// Sets whether values may contain control characters such as newlines,
+    /// tabs or NUL, which garble printed values and usually come from a
+    /// mistake such as piping a whole file. Disabled by default.
+    ///
+    /// Imports and [`Vault::replace_data`] always allow them, as the formats
+    /// they read encode them deliberately, such as the lines of an SSH key.
+    pub fn set_allow_control_chars(&mut self, allow: bool) {
+        self.allow_control_chars = allow;
+    }
+
+    /// Sets whether `save` creates the vault's parent directory
+    /// if it's missing. Enabled by default.
+    pub fn set_create_dirs(&mut self, create_dirs: bool) {
+        self.create_dirs = create_dirs;
+    }
+
+    /// Returns true if the vault was created by [`Vault::open`] because
+    /// there was no file at its path.
+    #[must_use]
+    pub fn is_new(&self) -> bool {
+        self.new
+    }
+
+    /// Returns true if the vault is stored unencrypted.
+    #[must_use]
+    pub fn is_plaintext(&self) -> bool {
+        self.plaintext
+    }
+
+    /// Sets whether the vault is stored unencrypted. Disabled by default.
+    ///
+    /// Plaintext vaults are meant for non-secret data only: anyone who can
+    /// read the file can read every entry.
+    pub fn set_plaintext(&mut self, plaintext: bool) {
+        self.plaintext = plaintext;
+    }
+
+    /// Sets whether saves reuse the salt, and so the derived key, that the
+    /// vault was opened or first saved with. Disabled by default.
+    ///
+    /// This skips the expensive key derivation on every save after the first,
+    /// which suits long-running processes that save often. Each save still
+    /// uses a fresh random nonce, so reusing the key is safe with AES-GCM,
+    /// but successive files share a salt, showing they use the same key.
+    pub fn set_reuse_salt(&mut self, reuse_salt: bool) {
+        self.reuse_salt = reuse_salt;
+    }
+
+    /// Sets whether saves also write the SHA-256 digest of the vault file
+    /// to [`checksum_path`], so external tools can monitor its integrity.
+    /// Disabled by default. A checksum file left by an earlier save is
+    /// kept up to date either way, so it doesn't go stale.
+    ///
+    /// The file is in the format of `sha256sum`, which can check it.
+    pub fn set_write_checksum(&mut self, write_checksum: bool) {
+        self.write_checksum = write_checksum;
+    }
+
+    /// Returns true if the vault was opened from a file that doesn't match
+    /// the digest in its checksum file, meaning the file was modified by
+    /// something other than passmate. False if there's no checksum file.
+    #[must_use]
+    pub fn checksum_mismatch(&self) -> bool {
+        self.checksum_mismatch
+    }
+
+    /// Sets whether saving overwrites the bytes of the previous vault file
+    /// before it's unlinked. Disabled by default.
+    ///
+    /// This is best-effort: on SSDs, journaling or copy-on-write filesystems
+    /// and snapshots, the old ciphertext may survive elsewhere on disk.
+    pub fn set_secure_delete(&mut self, secure_delete: bool) {
+        self.secure_delete = secure_delete;
+    }
+
+    /// Saves the vault to disk, creating its parent directory
+    /// (readable only by the owner on Unix) if it's missing.
+    ///
+    /// The contents are written and flushed to a temporary file which then
+    /// replaces the vault file, so an interrupted save or a crash never
+    /// leaves a partial vault. A new vault file is only accessible by its
+    /// owner on Unix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if it fails to create and write
+    /// to a file at the given path.
+    pub fn save(&self) -> Result<(), PassmateError> {
+        self.save_with_path(&self.path)
+    }
+
+    /// Saves the vault like [`Vault::save`], trying up to `attempts` times
+    /// while it fails with a transient I/O error such as a busy or
+    /// temporarily locked file. The wait between attempts starts at
+    /// `backoff` and doubles after each one.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last attempt, or the first error
+    /// that isn't transient, such as permission being denied.
+    pub fn save_with_retry(&self, attempts: u32, backoff: Duration) -> Result<(), PassmateError> {
+        retry(attempts, backoff, || self.save())
+    }
+
+    /// Saves the vault to the given path the same way as [`Vault::save`],
+    /// without changing where the vault itself lives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if it fails to create and write
+    /// to a file at the given path.
+    pub fn save_with_path(&self, path: impl AsRef<Path>) -> Result<(), PassmateError> {
+        let path = path.as_ref();
+        self.save_to(path, self.write_checksum || checksum_path(path).exists())
+    }
+
+    /// Saves the vault to the path, along with a checksum file if asked to.
+    /// Both are written in full before either replaces its file.
+    fn save_to(&self, path: &Path, write_checksum: bool) -> Result<(), PassmateError> {
+        if path.is_dir() {
+            return Err(PassmateError::InvalidPath(path.into()));
+        }
+        let contents = self.save_bytes()?;
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            if !dir.exists() {
+                if !self.create_dirs {
+                    return Err(PassmateError::MissingDirectory(dir.into()));
+                }
+                create_private_dir(dir).map_err(PassmateError::IO)?;
+            }
+        }
+
+        // Keep a handle to the old file so it can still be overwritten
+        // once the rename has unlinked it.
+        let old_file = if self.secure_delete {
+            open_existing(path)?
+        } else {
+            None
+        };
+        if write_checksum {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let checksum = format!("{}  {file_name}\n", sha256_hex(&contents));
+            replace_files(&[
+                (path, &contents),
+                (&checksum_path(path), checksum.as_bytes()),
+            ])
+        } else {
+            replace_files(&[(path, &contents)])
+        }
+        .map_err(PassmateError::IO)?;
+        match old_file {
+            Some(file) => overwrite(file).map_err(PassmateError::IO),
+            None => Ok(()),
+        }
+    }
+
+    /// Saves the vault to a new path and removes the file at its old path,
+    /// moving its checksum file along with it.
+    ///
+    /// # Errors
+    /// Returns an error if a file already exists at the new path, or saving
+    /// the vault or removing the old file fails. If saving fails, the
+    /// vault keeps its old path.
+    pub fn move_to(&mut self, path: impl AsRef<Path>) -> Result<(), PassmateError> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(PassmateError::VaultExists(path.into()));
+        }
+        self.save_to(
+            path,
+            self.write_checksum || checksum_path(&self.path).exists(),
+        )?;
+        let old_path = std::mem::replace(&mut self.path, path.into());
+        if self.secure_delete {
+            if let Some(file) = open_existing(&old_path)? {
+                overwrite(file).map_err(PassmateError::IO)?;
+            }
+        }
+        for old_path in [checksum_path(&old_path), old_path] {
+            match std::fs::remove_file(&old_path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(PassmateError::IO(e)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self, name: &str, value: &str) -> Result<(), PassmateError> {
+        validate_name(name)?;
+        self.validate_value(value)
+    }
+
+    /// Checks the size of a value like [`Vault::validate_imported_value`],
+    /// and that it has no control characters unless they're allowed.
+    fn validate_value(&self, value: &str) -> Result<(), PassmateError> {
+        self.validate_imported_value(value)?;
+        if !self.allow_control_chars {
+            if let Some(c) = value.chars().find(|c| c.is_control()) {
+                return Err(PassmateError::InvalidValue(format!(
+                    "it contains the control character {c:?}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates an imported entry like [`Vault::validate`], except that
+    /// control characters are always allowed.
+    fn validate_imported(&self, name: &str, value: &str) -> Result<(), PassmateError> {
+        validate_name(name)?;
+        self.validate_imported_value(value)
+    }
+
+    fn validate_imported_value(&self, value: &str) -> Result<(), PassmateError> {
+        if value.is_empty() && !self.allow_empty_values {
+            return Err(PassmateError::EmptyValue);
+        }
+        if let Some(limit) = self.max_value_size.filter(|limit| value.len() > *limit) {
+            return Err(PassmateError::ValueTooLarge {
+                size: value.len(),
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the encrypted contents of the vault file without writing it.
+    ///
+    /// # Errors
+    /// Returns an error if serializing or encrypting the vault data fails.
+    pub fn save_bytes(&self) -> Result<Vec<u8>, PassmateError> {
+        self.save_bytes_with_rng(&mut OsRng)
+    }
+
+    /// Returns the encrypted contents of the vault file like
+    /// [`Vault::save_bytes`], drawing the salt and nonce from the given
+    /// random number generator. Outside of tests it should be [`OsRng`].
+    ///
+    /// # Errors
+    /// Returns an error if serializing or encrypting the vault data fails.
+    pub fn save_bytes_with_rng(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<u8>, PassmateError> {
+        if self.plaintext {
+            // Only structured plaintext vaults are written at a version
+            // that stores the fingerprint key.
+            let data = if self.structured_entries {
+                serialize_data(&self.data, true, &self.fingerprint_key)?
+            } else {
+                serialize_entries(&self.data, false)?
+            };
+            return Ok(format::encode_plaintext(self.structured_entries, &data));
+        }
+        let data = serialize_data(&self.data, self.structured_entries, &self.fingerprint_key)?;
+        let fresh;
+        let key = if !self.reuse_salt {
+            fresh = self.derive_key(rng)?;
+            &fresh
+        } else if let Some(key) = self.key.get() {
+            key
+        } else {
+            let key = self.derive_key(rng)?;
+            self.key.get_or_init(|| key)
+        };
+        let header = format::Header {
+            version: format::VERSION,
+            kdf: self.kdf,
+            hint: self.hint.clone(),
+            structured_entries: self.structured_entries,
+            padding: self.padding,
+            salt: key.salt.clone(),
+            cipher: format::Cipher::Aes256Gcm,
+        };
+        seal_with_key(&data, key, &header, rng)
+    }
+
+    /// Derives a new key from the passphrase with a fresh salt.
+    fn derive_key(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<DerivedKey, PassmateError> {
+        let passphrase = self
+            .passphrase
+            .as_ref()
+            .ok_or(PassmateError::NoPassphrase)?;
+        DerivedKey::new(passphrase, &self.kdf, rng)
+    }
+
+    /// Encrypts arbitrary data with the passphrase, producing
+    /// the same self-describing format as a vault file.
+    ///
+    /// # Errors
+    /// Returns an error if deriving the key or encrypting the data fails.
+    pub fn seal(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, PassmateError> {
+        let kdf = KdfParams::default();
+        let key = DerivedKey::new(passphrase, &kdf, &mut OsRng)?;
+        let header = format::Header {
+            version: format::VERSION,
+            kdf,
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: key.salt.clone(),
+            cipher: format::Cipher::Aes256Gcm,
+        };
+        seal_with_key(plaintext, &key, &header, &mut OsRng)
+    }
+
+    /// Decrypts data produced by [`Vault::seal`] with the passphrase.
+    ///
+    /// # Errors
+    /// Returns an error if the data is malformed or can't be decrypted with the passphrase.
+    pub fn unseal(ciphertext: &[u8], passphrase: &str) -> Result<Vec<u8>, PassmateError> {
+        Ok(unseal_with_header(ciphertext, passphrase)?.2)
+    }
+}
+
+/// A key together with the salt it was derived from.
+struct DerivedKey {
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+impl DerivedKey {
+    fn new(
+        passphrase: &str,
+        kdf: &KdfParams,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Self, PassmateError> {
+        let salt = generate_salt(rng);
+        let key = make_key(passphrase, &salt, kdf)?;
+        Ok(Self {
+            salt: salt.to_vec(),
+            key,
+        })
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Encrypts the plaintext with the key, padding it as the header describes.
+/// The header's salt must be the one the key was derived from.
+fn seal_with_key(
+    plaintext: &[u8],
+    key: &DerivedKey,
+    header: &format::Header,
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Result<Vec<u8>, PassmateError> {
+    let encrypted_data = if header.padding > 0 {
+        encrypt(key.key, &pad(plaintext, header.padding), rng)?
+    } else {
+        encrypt(key.key, plaintext, rng)?
+    };
+    Ok(format::encode(header, &encrypted_data))
+}
+
+/// Appends a 0x80 byte and then zeros up to a multiple of the block size.
+fn pad(data: &[u8], block_size: u32) -> Vec<u8> {
+    let block_size = block_size as usize;
+    let padded_len = (data.len() / block_size + 1) * block_size;
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(data);
+    padded.push(0x80);
+    padded.resize(padded_len, 0);
+    padded
+}
+
+/// Strips the padding added by [`pad`].
+fn unpad(mut data: Vec<u8>) -> Result<Vec<u8>, PassmateError> {
+    let end = data
+        .iter()
+        .rposition(|&b| b != 0)
+        .filter(|&i| data[i] == 0x80)
+        .ok_or_else(|| PassmateError::InvalidFormat("invalid padding".into()))?;
+    data.truncate(end);
+    Ok(data)
+}
+
+/// Returns an error if the bytes are a plaintext vault file, which only
+/// the explicit plaintext constructors open.
+fn refuse_plaintext(bytes: &[u8]) -> Result<(), PassmateError> {
+    if format::is_plaintext(bytes) {
+        return Err(PassmateError::InvalidFormat(
+            "the vault is not encrypted".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn unseal_with_header(
+    bytes: &[u8],
+    passphrase: &str,
+) -> Result<(format::Header, DerivedKey, Vec<u8>), PassmateError> {
+    let decoded = format::decode(bytes)?;
+    let key = make_key(passphrase, &decoded.header.salt, &decoded.header.kdf)?;
+    let data = decrypt_payload(&decoded, key)?;
+    let salt = decoded.header.salt.clone();
+    Ok((decoded.header, DerivedKey { salt, key }, data))
+}
+
+/// Returns the path of the checksum file of the vault file at the path,
+/// the path with `.sha256` appended.
+#[must_use]
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut checksum_path = path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    PathBuf::from(checksum_path)
+}
+
+/// Returns true if the vault file at the path has a checksum file whose
+/// digest isn't the one of its contents.
+fn checksum_mismatch(path: &Path, contents: &[u8]) -> Result<bool, PassmateError> {
+    match std::fs::read_to_string(checksum_path(path)) {
+        Ok(checksum) => Ok(checksum.split_whitespace().next() != Some(&sha256_hex(contents))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(PassmateError::IO(e)),
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format::hex(&<Sha256 as sha2::Digest>::digest(data))
+}
+
+/// Reads the vault file at the path, or returns `None` if there's none yet.
+fn read_vault_file(path: &Path) -> Result<Option<Vec<u8>>, PassmateError> {
+    if path.is_dir() {
+        return Err(PassmateError::InvalidPath(path.into()));
+    }
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            Err(PassmateError::PermissionDenied(path.into()))
+        }
+        Err(e) => Err(PassmateError::IO(e)),
+    }
+}
+
+/// Reads the vault file at the path like [`read_vault_file`], where a
+/// missing file is an error.
+fn read_existing_vault_file(path: &Path) -> Result<Vec<u8>, PassmateError> {
+    read_vault_file(path)?.ok_or_else(|| PassmateError::IO(ErrorKind::NotFound.into()))
+}
+
+/// Decrypts the payload of a decoded vault file with the key, removing any padding.
+fn decrypt_payload(decoded: &format::Decoded<'_>, key: [u8; 32]) -> Result<Vec<u8>, PassmateError> {
+    let data = decrypt(decoded.header.cipher, key, decoded.payload).map_err(|_| {
+        PassmateError::InvalidPassphrase {
+            hint: decoded.header.hint.clone(),
+        }
+    })?;
+    if decoded.header.padding > 0 {
+        unpad(data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Calls `f` until it succeeds, fails with an error that isn't transient,
+/// or has been called `attempts` times, sleeping between calls.
+fn retry<T>(
+    attempts: u32,
+    mut backoff: Duration,
+    mut f: impl FnMut() -> Result<T, PassmateError>,
+) -> Result<T, PassmateError> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < attempts && is_transient(&e) => {
+                std::thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns true for I/O errors that may go away if the operation is retried.
+fn is_transient(err: &PassmateError) -> bool {
+    matches!(
+        err,
+        PassmateError::IO(e) if matches!(
+            e.kind(),
+            ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::TimedOut
+                | ErrorKind::ResourceBusy
+                | ErrorKind::StaleNetworkFileHandle
+        )
+    )
+}
+
+/// Replaces each file with its contents through a temporary file in the
+/// same directory, flushing both to disk, so a crash leaves either the old
+/// or the new file. Every temporary file is written before any is renamed,
+/// so a failed write leaves all the files as they were. A replaced file
+/// keeps its owner, group and mode.
+fn replace_files(files: &[(&Path, &[u8])]) -> std::io::Result<()> {
+    let temp_paths: Vec<PathBuf> = files.iter().map(|(path, _)| temp_path(path)).collect();
+    let replaced = files
+        .iter()
+        .zip(&temp_paths)
+        .try_for_each(|((path, contents), temp_path)| {
+            write_private_file(temp_path, contents)?;
+            copy_ownership_and_mode(path, temp_path)
+        })
+        .and_then(|()| {
+            files
+                .iter()
+                .zip(&temp_paths)
+                .try_for_each(|((path, _), temp_path)| std::fs::rename(temp_path, path))
+        });
+    if let Err(e) = replaced {
+        for temp_path in &temp_paths {
+            let _ = std::fs::remove_file(temp_path);
+        }
+        return Err(e);
+    }
+    let mut dirs: Vec<&Path> = files
+        .iter()
+        .map(|(path, _)| {
+            path.parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+        })
+        .collect();
+    dirs.dedup();
+    dirs.into_iter().try_for_each(sync_dir)
+}
+
+/// Returns the hidden file next to the path that a save writes to first.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = std::ffi::OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Writes the contents to a new file that only the owner can access on
+/// Unix and flushes it to disk. A file left at the path by an interrupted
+/// save is removed first, as it may have other permissions.
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Flushes the entries of the directory to disk, so a file renamed into
+/// it survives a crash.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened to flush them outside of Unix.
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// Gives the replacement file the owner, group and mode of the file it
+/// replaces, if there is one, so saving as another user such as root
+/// doesn't take the vault away from its owner.
+#[cfg(unix)]
+fn copy_ownership_and_mode(original: &Path, replacement: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = match std::fs::metadata(original) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    // Only root may give a file away, and other users already own the
+    // files they write, so a failure here changes nothing.
+    let _ = std::os::unix::fs::chown(replacement, Some(metadata.uid()), Some(metadata.gid()));
+    std::fs::set_permissions(replacement, metadata.permissions())
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn copy_ownership_and_mode(_original: &Path, _replacement: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn open_existing(path: &Path) -> Result<Option<std::fs::File>, PassmateError> {
+    match std::fs::OpenOptions::new().write(true).open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(PassmateError::IO(e)),
+    }
+}
+
+/// Overwrites the whole file with zeros and flushes it to disk.
+fn overwrite(mut file: std::fs::File) -> std::io::Result<()> {
+    let len = file.metadata()?.len();
+    std::io::copy(&mut std::io::repeat(0).take(len), &mut file)?;
+    file.sync_all()
+}
+
+fn serialize_entries(
+    data: &HashMap<String, Entry>,
+    structured: bool,
+) -> Result<Vec<u8>, PassmateError> {
+    serde_json::to_vec(&to_stored_entries(data, structured)).map_err(PassmateError::Json)
+}
+
+/// Serializes the entries along with the fingerprint key, as vault files
+/// from [`format::FINGERPRINT_KEY_VERSION`] on store them.
+fn serialize_data(
+    data: &HashMap<String, Entry>,
+    structured: bool,
+    fingerprint_key: &[u8; 32],
+) -> Result<Vec<u8>, PassmateError> {
+    serde_json::to_vec(&StoredData {
+        entries: to_stored_entries(data, structured),
+        fingerprint_key,
+    })
+    .map_err(PassmateError::Json)
+}
+
+fn to_stored_entries(
+    data: &HashMap<String, Entry>,
+    structured: bool,
+) -> BTreeMap<&str, StoredEntry<&str, &Entry>> {
+    // Sorted by name so the same entries always serialize to the same bytes.
+    data.iter()
+        .map(|(name, entry)| {
+            let stored = if structured {
+                StoredEntry::Entry(entry)
+            } else {
+                entry.into()
+            };
+            (name.as_str(), stored)
+        })
+        .collect()
+}
+
+fn deserialize_entries(data: &[u8]) -> Result<HashMap<String, Entry>, PassmateError> {
+    let stored: HashMap<String, StoredEntry<String, Entry>> =
+        serde_json::from_slice(data).map_err(PassmateError::Json)?;
+    Ok(from_stored_entries(stored))
+}
+
+fn from_stored_entries(
+    stored: HashMap<String, StoredEntry<String, Entry>>,
+) -> HashMap<String, Entry> {
+    stored
+        .into_iter()
+        .map(|(name, entry)| (name, entry.into()))
+        .collect()
+}
+
+/// The data of a vault file from [`format::FINGERPRINT_KEY_VERSION`] on.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredData<K, E> {
+    entries: E,
+    fingerprint_key: K,
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+fn now() -> u64 {
+    time::unix_secs(SystemTime::now())
+}
+
+fn validate_field_name(field: &str) -> Result<(), PassmateError> {
+    validate_name(field).map_err(|e| match e {
+        PassmateError::InvalidName(reason) => PassmateError::InvalidFieldName(reason),
+        e => e,
+    })
+}
+
+fn validate_name(name: &str) -> Result<(), PassmateError> {
+    if name.is_empty() {
+        return Err(PassmateError::InvalidName("name cannot be empty".into()));
+    }
+    if name.trim() != name {
+        return Err(PassmateError::InvalidName(format!(
+            "{name:?} has leading or trailing whitespace"
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(PassmateError::InvalidName(format!(
+            "{name:?} contains control characters"
+        )));
+    }
+    Ok(())
+}
+
+#[mutants::skip]
+fn make_key(pwd: &str, salt: &[u8], kdf: &KdfParams) -> Result<[u8; 32], PassmateError> {
+    let mut key = [0u8; 32];
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(key.len()))
+        .map_err(PassmateError::EncryptionKey)?;
+    Argon2::new(kdf.variant.algorithm(), Version::V0x13, params)
+        .hash_password_into(pwd.as_bytes(), salt, &mut key)
+        .map_err(PassmateError::EncryptionKey)?;
+    Ok(key)
+}
+
+#[mutants::skip]
+fn generate_salt(rng: &mut (impl RngCore + CryptoRng)) -> [u8; format::SALT_LEN] {
+    let mut salt = [0u8; format::SALT_LEN];
+    rng.fill(&mut salt);
+    salt
+}
+
+/// Remembers the key and nonce of every encryption in debug builds and
+/// panics if a pair is ever reused, catching an RNG that repeats nonces.
+#[cfg(debug_assertions)]
+fn track_nonce(key: &Key<Aes256Gcm>, nonce: &[u8]) {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    static USED: OnceLock<std::sync::Mutex<std::collections::HashSet<u64>>> = OnceLock::new();
+    let mut hasher = DefaultHasher::new();
+    [key.as_slice(), nonce].hash(&mut hasher);
+    let unused = USED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(hasher.finish());
+    assert!(unused, "nonce reused with the same key");
+}
+
+fn encrypt(
+    key: [u8; 32],
+    data: &[u8],
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Result<Vec<u8>, PassmateError> {
+    let key = Key::<Aes256Gcm>::from_slice(&key);
+    let nonce = Aes256Gcm::generate_nonce(rng); // 96-bits; unique per message
+    #[cfg(debug_assertions)]
+    track_nonce(key, &nonce);
+
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(&nonce, data)
+        .map_err(PassmateError::Encrypt)?;
+
+    let mut encrypted_data = nonce.to_vec();
+    encrypted_data.extend_from_slice(&ciphertext);
+
+    Ok(encrypted_data)
+}
+
+/// Decrypts the nonce and ciphertext with the cipher, which determines
+/// the length of the nonce.
+fn decrypt(
+    cipher: format::Cipher,
+    key: [u8; 32],
+    encrypted_data: &[u8],
+) -> Result<Vec<u8>, PassmateError> {
+    let (nonce, ciphertext) = encrypted_data
+        .split_at_checked(cipher.nonce_len())
+        .ok_or(PassmateError::Decrypt(aead::Error))?;
+    match cipher {
+        format::Cipher::Aes256Gcm => Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+            .decrypt(Nonce::from_slice(nonce), ciphertext),
+        format::Cipher::XChaCha20Poly1305 => {
+            XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&key))
+                .decrypt(XNonce::from_slice(nonce), ciphertext)
+        }
+    }
+    .map_err(PassmateError::Decrypt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_none, assert_ok};
+    use proptest::prelude::*;
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+    };
+    use tempfile::TempDir;
+
+    /// Returns a lookup of the given environment variables, for the
+    /// modules that take a `var` function instead of reading the real ones.
+    pub(crate) fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| ((*name).into(), (*value).into()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn open_returns_an_empty_vault_for_file_that_doesnt_exist() {
+        let vault = Vault::open(PathBuf::from("doesnotexist"), "testpwd").unwrap();
+        assert!(vault.data.is_empty());
+        assert!(vault.is_new());
+    }
+
+    #[test]
+    fn open_opens_a_vault_with_existing_data() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        assert_ok!(tmp.vault.save());
+
+        let vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert!(vault.data_eq(&tmp.vault));
+        assert!(!vault.is_new());
+    }
+
+    #[test]
+    fn open_bytes_opens_the_contents_of_a_saved_vault() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+
+        let bytes = tmp.vault.save_bytes().unwrap();
+        let vault = Vault::open_bytes(&bytes, "testpwd").unwrap();
+
+        assert!(vault.data_eq(&tmp.vault));
+    }
+
+    #[test]
+    fn open_with_asks_for_the_passphrase_once_the_file_can_be_opened() {
+        let mut tmp = TempVault::new();
+        let mut asked = 0;
+
+        let mut missing = Vault::open_with(&tmp.vault.path, || {
+            asked += 1;
+            Ok(Zeroizing::new("newpwd".into()))
+        })
+        .unwrap();
+        assert_eq!(asked, 1);
+        assert!(missing.is_new());
+        missing.set("mypass", "test").unwrap();
+        let saved = missing.save_bytes().unwrap();
+        assert!(Vault::open_bytes(&saved, "newpwd")
+            .unwrap()
+            .data_eq(&missing));
+        assert!(Vault::open_bytes(&saved, "").is_err());
+
+        tmp.vault.set("mypass", "test").unwrap();
+        tmp.vault.save().unwrap();
+        let opened = Vault::open_with(&tmp.vault.path, || {
+            asked += 1;
+            Ok(Zeroizing::new("testpwd".into()))
+        })
+        .unwrap();
+        assert_eq!(asked, 2);
+        assert!(opened.data_eq(&tmp.vault));
+        assert_eq!(opened.path, tmp.vault.path);
+
+        let cancelled = Vault::open_with(&tmp.vault.path, || {
+            Err(PassmateError::IO(ErrorKind::Interrupted.into()))
+        });
+        assert!(matches!(cancelled, Err(PassmateError::IO(_))));
+    }
+
+    #[test]
+    fn open_many_returns_a_result_for_each_path_in_order() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        tmp.vault.save().unwrap();
+        let missing = tmp.vault.path.with_file_name("missing.vault");
+        let corrupt = tmp.vault.path.with_file_name("corrupt.vault");
+        std::fs::write(&corrupt, b"not a vault").unwrap();
+
+        let results = Vault::open_many(&[tmp.vault.path.clone(), missing, corrupt], "testpwd");
+
+        assert_eq!(results.len(), 3);
+        let opened = results[0].as_ref().unwrap();
+        assert_eq!(opened.path, tmp.vault.path);
+        assert!(opened.data_eq(&tmp.vault));
+        assert!(
+            matches!(&results[1], Err(PassmateError::IO(e)) if e.kind() == ErrorKind::NotFound)
+        );
+        assert!(matches!(results[2], Err(PassmateError::InvalidFormat(_))));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn open_bytes_never_panics_on_malformed_vaults(
+            hint_len in any::<u16>(),
+            rest in prop::collection::vec(any::<u8>(), 0..96),
+        ) {
+            let mut bytes = format::MAGIC.to_vec();
+            bytes.extend_from_slice(&[format::VERSION, 1]);
+            for cost in [8u32, 1, 1] {
+                bytes.extend_from_slice(&cost.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(hint_len % 64).to_le_bytes());
+            bytes.extend_from_slice(&rest);
+
+            prop_assert!(Vault::open_bytes(&bytes, "testpwd").is_err());
+        }
+    }
+
+    #[test]
+    fn open_bytes_tells_truncated_ciphertext_apart_from_a_wrong_passphrase() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        let bytes = tmp.vault.save_bytes().unwrap();
+        let header_len = bytes.len() - format::inspect(&bytes).unwrap().ciphertext_len;
+
+        assert!(matches!(
+            Vault::open_bytes(&bytes[..header_len + format::TAG_LEN - 1], "testpwd"),
+            Err(PassmateError::InvalidFormat(message)) if message.contains("truncated")
+        ));
+        assert!(matches!(
+            Vault::open_bytes(&bytes, "wrongpwd"),
+            Err(PassmateError::InvalidPassphrase { .. })
+        ));
+    }
+
+    #[test]
+    fn open_reader_opens_a_vault_from_a_reader() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        assert_ok!(tmp.vault.save());
+
+        let file = std::fs::File::open(&tmp.vault.path).unwrap();
+        let vault = Vault::open_reader(file, "testpwd").unwrap();
+
+        assert!(vault.data_eq(&tmp.vault));
+    }
+
+    #[test]
+    fn open_rejects_a_vault_with_corrupted_kdf_params_before_deriving_the_key() {
+        let tmp = TempVault::new();
+        tmp.vault.save().unwrap();
+        let mut bytes = std::fs::read(&tmp.vault.path).unwrap();
+        let t_cost = format::MAGIC.len() + 6;
+        bytes[t_cost..t_cost + 4].copy_from_slice(&0u32.to_le_bytes());
+        std::fs::write(&tmp.vault.path, bytes).unwrap();
+
+        assert!(matches!(
+            Vault::open(&tmp.vault.path, "testpwd"),
+            Err(PassmateError::InvalidFormat(message)) if message.contains("time cost of 0")
+        ));
+    }
+
+    #[test]
+    fn open_reads_vaults_written_without_a_header() {
+        let tmp = TempVault::new();
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpwd", &salt, &KdfParams::default()).unwrap();
+        let encrypted_data = encrypt(key, br#"{"mypass":"test"}"#, &mut OsRng).unwrap();
+        std::fs::write(&tmp.vault.path, [salt.as_slice(), &encrypted_data].concat()).unwrap();
+
+        let vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+
+        assert_eq!(vault.get("mypass").unwrap(), "test");
+    }
+
+    #[test]
+    fn prune_history_keeps_only_the_newest_previous_secrets() {
+        let mut tmp = TempVault::new();
+        for value in ["v1", "v2", "v3", "v4"] {
+            tmp.vault.set("mypass", value).unwrap();
+        }
+        tmp.vault.set("other", "only").unwrap();
+
+        assert_eq!(tmp.vault.prune_history(2), 1);
+        assert_eq!(tmp.vault.get_entry("mypass").unwrap().history, ["v2", "v3"]);
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "v4");
+        assert_eq!(tmp.vault.prune_history(2), 0);
+
+        assert_eq!(tmp.vault.prune_history(0), 2);
+        assert!(tmp.vault.get_entry("mypass").unwrap().history.is_empty());
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "v4");
+    }
+
+    #[test]
+    fn upgrade_entries_stores_bare_strings_of_a_v0_vault_in_structured_form() {
+        let tmp = TempVault::new();
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpwd", &salt, &KdfParams::default()).unwrap();
+        let encrypted_data = encrypt(key, br#"{"mypass":"test"}"#, &mut OsRng).unwrap();
+        std::fs::write(&tmp.vault.path, [salt.as_slice(), &encrypted_data].concat()).unwrap();
+        let mut vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        let legacy = vault.save_bytes().unwrap();
+        assert!(!format::inspect(&legacy).unwrap().structured_entries);
+
+        assert!(vault.upgrade_entries());
+        vault.save().unwrap();
+
+        let bytes = std::fs::read(&tmp.vault.path).unwrap();
+        assert!(format::inspect(&bytes).unwrap().structured_entries);
+        let (_, _, json) = unseal_with_header(&bytes, "testpwd").unwrap();
+        let stored: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            stored["entries"],
+            serde_json::json!({"mypass": {"secret": "test"}})
+        );
+
+        let mut upgraded = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert!(!upgraded.upgrade_entries());
+        assert!(upgraded.data_eq(&vault));
+        let (_, _, resaved) =
+            unseal_with_header(&upgraded.save_bytes().unwrap(), "testpwd").unwrap();
+        assert_eq!(resaved, json);
+    }
+
+    fn write_vault(path: &Path, header: &format::Header, data: &[u8]) {
+        let key = make_key("testpwd", &header.salt, &header.kdf).unwrap();
+        let key = DerivedKey {
+            salt: header.salt.clone(),
+            key,
+        };
+        std::fs::write(path, seal_with_key(data, &key, header, &mut OsRng).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn needs_upgrade_reports_why_an_older_vault_is_out_of_date() {
+        let tmp = TempVault::new();
+        let path = &tmp.vault.path;
+        let current = format::Header {
+            version: format::VERSION,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: generate_salt(&mut OsRng).to_vec(),
+            cipher: format::Cipher::Aes256Gcm,
+        };
+        let weak_kdf = KdfParams {
+            m_cost: 8,
+            t_cost: 1,
+            p_cost: 1,
+            ..KdfParams::default()
+        };
+        let cases = [
+            (
+                format::Header {
+                    version: 2,
+                    structured_entries: false,
+                    ..current.clone()
+                },
+                Some(UpgradeReason::OldFormat { version: 2 }),
+            ),
+            (
+                format::Header {
+                    version: 3,
+                    ..current.clone()
+                },
+                Some(UpgradeReason::OldFormat { version: 3 }),
+            ),
+            (
+                format::Header {
+                    structured_entries: false,
+                    ..current.clone()
+                },
+                Some(UpgradeReason::LegacyEntries),
+            ),
+            (
+                format::Header {
+                    kdf: weak_kdf,
+                    ..current.clone()
+                },
+                Some(UpgradeReason::WeakKdf),
+            ),
+            (
+                format::Header {
+                    kdf: KdfParams {
+                        variant: Argon2Variant::Argon2i,
+                        ..KdfParams::default()
+                    },
+                    ..current.clone()
+                },
+                Some(UpgradeReason::WeakKdf),
+            ),
+            (current, None),
+        ];
+
+        let data = HashMap::from([("mypass".to_string(), Entry::new("test"))]);
+        for (header, expected) in cases {
+            let data = if header.version >= format::FINGERPRINT_KEY_VERSION {
+                serialize_data(&data, false, &[1; 32]).unwrap()
+            } else {
+                serialize_entries(&data, false).unwrap()
+            };
+            write_vault(path, &header, &data);
+            let vault = Vault::open(path, "testpwd").unwrap();
+            assert_eq!(vault.needs_upgrade(), expected, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn needs_upgrade_reports_a_vault_written_without_a_header() {
+        let tmp = TempVault::new();
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpwd", &salt, &KdfParams::default()).unwrap();
+        let encrypted_data = encrypt(key, br#"{"mypass":"test"}"#, &mut OsRng).unwrap();
+        std::fs::write(&tmp.vault.path, [salt.as_slice(), &encrypted_data].concat()).unwrap();
+
+        let vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+
+        assert_eq!(
+            vault.needs_upgrade(),
+            Some(UpgradeReason::OldFormat { version: 0 })
+        );
+        assert_none!(tmp.vault.needs_upgrade());
+    }
+
+    #[test]
+    fn save_writes_a_header_with_the_kdf_params() {
+        let tmp = TempVault::new();
+        assert_ok!(tmp.vault.save());
+
+        let bytes = std::fs::read(&tmp.vault.path).unwrap();
+        let info = format::inspect(&bytes).unwrap();
+
+        assert!(bytes.starts_with(format::MAGIC));
+        assert_eq!(info.version, format::VERSION);
+        assert_eq!(info.kdf, KdfParams::default());
+    }
+
+    #[test]
+    fn set_kdf_params_rejects_params_the_vault_could_not_be_opened_with() {
+        let mut tmp = TempVault::new();
+        for kdf in [
+            KdfParams {
+                t_cost: format::MAX_T_COST + 1,
+                ..KdfParams::default()
+            },
+            KdfParams {
+                p_cost: format::MAX_P_COST + 1,
+                ..KdfParams::default()
+            },
+            KdfParams {
+                m_cost: format::MAX_M_COST + 1,
+                ..KdfParams::default()
+            },
+        ] {
+            assert!(matches!(
+                tmp.vault.set_kdf_params(kdf),
+                Err(PassmateError::InvalidConfig(_))
+            ));
+        }
+
+        assert_eq!(tmp.vault.kdf, KdfParams::default());
+        tmp.vault.set("mypass", "test").unwrap();
+        tmp.vault.save().unwrap();
+        assert_ok!(Vault::open(&tmp.vault.path, "testpwd"));
+    }
+
+    #[test]
+    fn vaults_saved_with_each_argon2_variant_reopen_and_record_it() {
+        for variant in [
+            Argon2Variant::Argon2d,
+            Argon2Variant::Argon2i,
+            Argon2Variant::Argon2id,
+        ] {
+            let mut tmp = TempVault::new();
+            let kdf = KdfParams {
+                variant,
+                m_cost: 64,
+                t_cost: 1,
+                p_cost: 1,
+            };
+            tmp.vault.set_kdf_params(kdf).unwrap();
+            tmp.vault.set("mypass", "test").unwrap();
+            tmp.vault.save().unwrap();
+
+            let bytes = std::fs::read(&tmp.vault.path).unwrap();
+            assert_eq!(format::inspect(&bytes).unwrap().kdf, kdf);
+            let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+            assert!(reopened.data_eq(&tmp.vault));
+            assert_eq!(reopened.kdf, kdf);
+        }
+    }
+
+    #[test]
+    fn padding_hides_the_number_of_entries_in_the_file_size() {
+        let mut small = TempVault::new();
+        let mut large = TempVault::new();
+        small.vault.set("mypass", "test").unwrap();
+        for i in 0..20 {
+            large
+                .vault
+                .set(format!("entry{i}"), "secret".into())
+                .unwrap();
+        }
+        small.vault.set_padding(4096).unwrap();
+        large.vault.set_padding(4096).unwrap();
+
+        let small_bytes = small.vault.save_bytes().unwrap();
+        let large_bytes = large.vault.save_bytes().unwrap();
+
+        assert_eq!(small_bytes.len(), large_bytes.len());
+        assert_eq!(format::inspect(&small_bytes).unwrap().padding, 4096);
+        let mut reopened = Vault::open_bytes(&large_bytes, "testpwd").unwrap();
+        assert!(reopened.data_eq(&large.vault));
+        reopened.set_padding(0).unwrap();
+        assert!(reopened.save_bytes().unwrap().len() < large_bytes.len());
+    }
+
+    #[test]
+    fn set_padding_rejects_block_sizes_that_are_not_small_powers_of_two() {
+        let mut tmp = TempVault::new();
+
+        for block_size in [1000, format::MAX_PADDING * 2, u32::MAX] {
+            assert!(matches!(
+                tmp.vault.set_padding(block_size),
+                Err(PassmateError::InvalidConfig(_))
+            ));
+        }
+        assert_eq!(tmp.vault.padding, 0);
+    }
+
+    #[test]
+    fn pad_and_unpad_round_trip_at_block_boundaries() {
+        for len in [0, 1, 15, 16, 17] {
+            let data = vec![0u8; len];
+            let padded = pad(&data, 16);
+            assert_eq!(padded.len() % 16, 0);
+            assert!(padded.len() > len);
+            assert_eq!(unpad(padded).unwrap(), data);
+        }
+        assert!(unpad(vec![1, 0, 0]).is_err());
+        assert!(unpad(vec![0, 0]).is_err());
+    }
+
+    #[test]
+    fn set_adds_a_new_secret_to_the_vault_with_the_given_name() {
+        let mut tmp = TempVault::new();
+
+        tmp.vault.set("mypass", "test").unwrap();
+
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "test");
+    }
+
+    #[test]
+    fn entries_returns_the_names_of_the_vault_entries_in_alphabetical_order() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("pass1", "test").unwrap();
+        tmp.vault.set("pass2", "test").unwrap();
+
+        let want: Vec<String> = vec!["pass1".into(), "pass2".into()];
+        let got = tmp.vault.entries();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn entry_names_unsorted_returns_every_name_and_entries_count_counts_them() {
+        let mut tmp = TempVault::new();
+        assert_eq!(tmp.vault.entries_count(), 0);
+        for name in ["zeta", "alpha", "mid"] {
+            tmp.vault.set(name, "test").unwrap();
+        }
+
+        let names: HashSet<String> = tmp.vault.entry_names_unsorted().into_iter().collect();
+
+        assert_eq!(
+            names,
+            HashSet::from(["alpha".into(), "mid".into(), "zeta".into()])
+        );
+        assert_eq!(tmp.vault.entry_names_unsorted().len(), 3);
+        assert_eq!(tmp.vault.entries_count(), 3);
+    }
+
+    #[test]
+    fn entries_matching_returns_the_sorted_names_accepted_by_the_predicate() {
+        let mut tmp = TempVault::new();
+        for name in ["work/github", "home/email", "work/aws", "bank"] {
+            tmp.vault.set(name, "test").unwrap();
+        }
+
+        assert_eq!(
+            tmp.vault.entries_matching(|name| name.contains('/')),
+            ["home/email", "work/aws", "work/github"]
+        );
+        assert_eq!(tmp.vault.entries_matching(|name| name.len() == 4), ["bank"]);
+        assert!(tmp.vault.entries_matching(|_| false).is_empty());
+    }
+
+    #[test]
+    fn entries_iter_sorted_yields_the_same_names_as_entries() {
+        let mut tmp = TempVault::new();
+        for name in ["work/github", "home/email", "bank"] {
+            tmp.vault.set(name, "test").unwrap();
+        }
+
+        let names: Vec<&str> = tmp.vault.entries_iter_sorted().collect();
+
+        assert_eq!(names, tmp.vault.entries());
+        assert_eq!(tmp.vault.entries_iter_sorted().nth(1), Some("home/email"));
+    }
+
+    #[test]
+    fn entries_reflects_every_mutation_after_being_listed() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "test").unwrap();
+        assert_eq!(tmp.vault.entries(), ["github"]);
+
+        tmp.vault.set("aws", "test").unwrap();
+        assert_eq!(tmp.vault.entries(), ["aws", "github"]);
+        tmp.vault.set_entry("bank", Entry::new("test")).unwrap();
+        assert_eq!(tmp.vault.entries(), ["aws", "bank", "github"]);
+        tmp.vault.remove("github");
+        assert_eq!(tmp.vault.entries(), ["aws", "bank"]);
+        tmp.vault.remove_many(["aws"]);
+        assert_eq!(tmp.vault.entries(), ["bank"]);
+        tmp.vault
+            .replace_data(HashMap::from([("email".to_string(), "test".to_string())]))
+            .unwrap();
+        assert_eq!(tmp.vault.entries(), ["email"]);
+    }
+
+    #[test]
+    fn entries_modified_since_includes_entries_changed_at_or_after_the_time() {
+        let mut tmp = TempVault::new();
+        let clock = time::from_unix_secs(10_000);
+        for (name, created_at, updated_at) in [
+            ("old", Some(100), Some(6_399)),
+            ("boundary", Some(100), Some(6_400)),
+            ("recent", Some(9_000), Some(9_000)),
+        ] {
+            let entry = Entry {
+                created_at,
+                updated_at,
+                ..Entry::new("test".to_string())
+            };
+            tmp.vault.set_entry(name, entry).unwrap();
+        }
+        tmp.vault.data.insert("untimed".into(), Entry::new("test"));
+
+        let since = time::parse_since("1h", clock).unwrap();
+
+        assert_eq!(
+            tmp.vault.entries_modified_since(since),
+            ["boundary", "recent"]
+        );
+    }
+
+    #[test]
+    fn export_by_tag_writes_a_vault_with_exactly_the_entries_of_each_tag() {
+        let mut tmp = TempVault::new();
+        for (name, tags) in [
+            ("github", vec!["work", "dev"]),
+            ("email", vec!["work"]),
+            ("bank", vec![]),
+            ("blog", vec!["dev"]),
+        ] {
+            let entry = Entry {
+                tags: tags.into_iter().map(String::from).collect(),
+                ..Entry::new("secret")
+            };
+            tmp.vault.set_entry(name, entry).unwrap();
+        }
+        let dir = tmp.vault.path.with_file_name("by-tag");
+
+        let paths = tmp.vault.export_by_tag(&dir, "exportpwd").unwrap();
+
+        assert_eq!(paths, [dir.join("dev.vault"), dir.join("work.vault")]);
+        let dev = Vault::open(dir.join("dev.vault"), "exportpwd").unwrap();
+        let work = Vault::open(dir.join("work.vault"), "exportpwd").unwrap();
+        assert_eq!(dev.entries(), ["blog", "github"]);
+        assert_eq!(work.entries(), ["email", "github"]);
+        assert_eq!(work.get_entry("github"), tmp.vault.get_entry("github"));
+        assert!(matches!(
+            tmp.vault.export_by_tag(&dir, "exportpwd"),
+            Err(PassmateError::VaultExists(_))
+        ));
+    }
+
+    #[test]
+    fn export_by_tag_rejects_tags_that_are_not_file_names() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            tags: vec!["../escape".into()],
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry).unwrap();
+        let dir = tmp.vault.path.with_file_name("by-tag");
+
+        assert!(matches!(
+            tmp.vault.export_by_tag(&dir, "exportpwd"),
+            Err(PassmateError::InvalidTag(tag)) if tag == "../escape"
+        ));
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn export_by_tag_removes_the_vaults_written_when_a_later_save_fails() {
+        let mut tmp = TempVault::new();
+        for (name, tag) in [("github", "dev"), ("email", "work")] {
+            let entry = Entry {
+                tags: vec![tag.into()],
+                ..Entry::new("secret")
+            };
+            tmp.vault.set_entry(name, entry).unwrap();
+        }
+        let dir = tmp.vault.path.with_file_name("by-tag");
+        // A directory in the way of its temporary file fails saving work.vault.
+        std::fs::create_dir_all(temp_path(&dir.join("work.vault"))).unwrap();
+
+        assert!(matches!(
+            tmp.vault.export_by_tag(&dir, "exportpwd"),
+            Err(PassmateError::IO(_))
+        ));
+        assert!(!dir.join("dev.vault").exists());
+        assert!(!dir.join("work.vault").exists());
+    }
+
+    #[test]
+    fn entries_with_prefix_returns_the_sorted_names_with_the_prefix() {
+        let mut tmp = TempVault::new();
+        for name in ["work/github", "home/email", "work/aws"] {
+            tmp.vault.set(name, "test").unwrap();
+        }
+
+        assert_eq!(
+            tmp.vault.entries_with_prefix("work/"),
+            ["work/aws", "work/github"]
+        );
+        assert!(tmp.vault.entries_with_prefix("play/").is_empty());
+    }
+
+    #[test]
+    fn case_collisions_groups_names_that_differ_only_by_case() {
+        let mut tmp = TempVault::new();
+        for name in ["foo", "Foo", "bar", "FOO", "Baz", "baz"] {
+            tmp.vault.set(name, "test").unwrap();
+        }
+
+        assert_eq!(
+            tmp.vault.case_collisions(),
+            [vec!["Baz", "baz"], vec!["FOO", "Foo", "foo"]]
+        );
+    }
+
+    #[test]
+    fn case_collisions_is_empty_when_all_names_are_distinct() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("foo", "test").unwrap();
+        tmp.vault.set("bar", "test").unwrap();
+
+        assert!(tmp.vault.case_collisions().is_empty());
+    }
+
+    #[test]
+    fn empty_value_entries_returns_the_sorted_names_with_empty_values() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_allow_empty_values(true);
+        for (name, value) in [
+            ("zed", ""),
+            ("github", "secret"),
+            ("aws", ""),
+            ("blank", " "),
+        ] {
+            tmp.vault.set(name, value).unwrap();
+        }
+
+        assert_eq!(tmp.vault.empty_value_entries(), ["aws", "zed"]);
+        tmp.vault.remove_many(tmp.vault.empty_value_entries());
+        assert_eq!(tmp.vault.entries(), ["blank", "github"]);
+        assert!(tmp.vault.empty_value_entries().is_empty());
+    }
+
+    #[test]
+    fn validate_all_reports_the_entries_breaking_the_policy() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("weak", "abc").unwrap();
+        tmp.vault.set("strong", "Abcdefgh1234!xyz9876").unwrap();
+
+        let report = tmp.vault.validate_all(&PasswordPolicy::default());
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, "weak");
+        assert_eq!(
+            report[0].1,
+            [
+                PolicyViolation::TooShort {
+                    length: 3,
+                    minimum: 20
+                },
+                PolicyViolation::MissingClass(generate::CharClass::Uppercase),
+                PolicyViolation::MissingClass(generate::CharClass::Digits),
+                PolicyViolation::MissingClass(generate::CharClass::Symbols),
+            ]
+        );
+    }
+
+    #[test]
+    fn values_returns_the_secrets_in_name_order() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("b", "second").unwrap();
+        tmp.vault.set("a", "first").unwrap();
+
+        assert_eq!(tmp.vault.values(), ["first", "second"]);
+    }
+
+    #[test]
+    fn stats_summarizes_the_vaults_secrets() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("short", "abc").unwrap();
+        tmp.vault.set("long", "abcdefg").unwrap();
+
+        let stats = tmp.vault.stats();
+
+        assert_eq!(
+            (stats.entries, stats.min_length, stats.max_length),
+            (2, 3, 7)
+        );
+        assert_eq!(stats.strength[&generate::Strength::Weak], 2);
+    }
+
+    #[test]
+    fn get_retrieves_a_secret_from_the_vault_with_the_given_name() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+
+        assert_eq!(tmp.vault.get("mypass"), Some(&"test".to_string()));
+    }
+
+    #[test]
+    fn value_len_returns_the_length_of_the_secret_in_bytes() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("ascii", "password").unwrap();
+        tmp.vault.set("unicode", "pässword").unwrap();
+
+        assert_eq!(tmp.vault.value_len("ascii"), Some(8));
+        assert_eq!(tmp.vault.value_len("unicode"), Some(9));
+        assert_none!(tmp.vault.value_len("missing"));
+    }
+
+    #[test]
+    fn get_returns_none_if_a_secret_does_not_exist_by_the_given_name() {
+        let tmp = TempVault::new();
+
+        assert_none!(tmp.vault.get("mypass"));
+    }
+
+    #[test]
+    fn get_ci_prefers_an_exact_match_and_falls_back_to_a_unique_one_ignoring_case() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("Foo", "upper").unwrap();
+        tmp.vault.set("foo", "lower").unwrap();
+        tmp.vault.set("GitHub", "secret").unwrap();
+
+        assert_eq!(tmp.vault.get_ci("foo").unwrap().unwrap(), "lower");
+        assert_eq!(tmp.vault.get_ci("Foo").unwrap().unwrap(), "upper");
+        assert_eq!(tmp.vault.get_ci("github").unwrap().unwrap(), "secret");
+        assert_eq!(tmp.vault.get_ci("gitlab").unwrap(), None);
+        assert!(matches!(
+            tmp.vault.get_ci("FOO"),
+            Err(PassmateError::AmbiguousName { matches, .. }) if matches == ["Foo", "foo"]
+        ));
+    }
+
+    #[test]
+    fn get_all_pairs_each_requested_name_with_its_secret_in_order() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "octocat").unwrap();
+        tmp.vault.set("email", "hunter2").unwrap();
+
+        assert_eq!(
+            tmp.vault.get_all(&["email", "missing", "github", "email"]),
+            [
+                ("email".to_string(), Some("hunter2")),
+                ("missing".to_string(), None),
+                ("github".to_string(), Some("octocat")),
+                ("email".to_string(), Some("hunter2")),
+            ]
+        );
+        assert!(tmp.vault.get_all(&[]).is_empty());
+    }
+
+    #[test]
+    fn set_updates_an_existing_secret_if_it_already_exists_by_the_given_name() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        tmp.vault.set("mypass", "newtest").unwrap();
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "newtest");
+    }
+
+    #[test]
+    fn set_rejects_invalid_names() {
+        let mut tmp = TempVault::new();
+
+        for name in ["", " mypass", "mypass\n", "my\0pass"] {
+            assert!(matches!(
+                tmp.vault.set(name, "test"),
+                Err(PassmateError::InvalidName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn set_with_an_invalid_name_leaves_the_vault_and_file_untouched() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        assert_ok!(tmp.vault.save());
+        let contents = std::fs::read(&tmp.vault.path).unwrap();
+
+        assert_err!(tmp.vault.set("", "newtest").and_then(|()| tmp.vault.save()));
+
+        assert_eq!(tmp.vault.entries(), vec!["mypass".to_string()]);
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "test");
+        assert_eq!(std::fs::read(&tmp.vault.path).unwrap(), contents);
+    }
+
+    #[test]
+    fn set_accepts_values_up_to_the_size_limit() {
+        let mut tmp = TempVault::new();
+
+        assert_ok!(tmp
+            .vault
+            .set("below", &"a".repeat(DEFAULT_MAX_VALUE_SIZE - 1)));
+        assert_ok!(tmp.vault.set("at", &"a".repeat(DEFAULT_MAX_VALUE_SIZE)));
+    }
+
+    #[test]
+    fn set_rejects_values_above_the_size_limit() {
+        let mut tmp = TempVault::new();
+
+        let result = tmp
+            .vault
+            .set("above", &"a".repeat(DEFAULT_MAX_VALUE_SIZE + 1));
+
+        assert!(matches!(
+            result,
+            Err(PassmateError::ValueTooLarge { size, limit })
+                if size == DEFAULT_MAX_VALUE_SIZE + 1 && limit == DEFAULT_MAX_VALUE_SIZE
+        ));
+        assert_none!(tmp.vault.get("above"));
+    }
+
+    #[test]
+    fn set_max_value_size_changes_or_removes_the_limit() {
+        let mut tmp = TempVault::new();
+
+        tmp.vault.set_max_value_size(Some(4));
+        assert_ok!(tmp.vault.set("mypass", "test"));
+        assert_err!(tmp.vault.set("mypass", "tests"));
+
+        tmp.vault.set_max_value_size(None);
+        assert_ok!(tmp
+            .vault
+            .set("mypass", &"a".repeat(DEFAULT_MAX_VALUE_SIZE + 1)));
+    }
+
+    #[test]
+    fn set_rejects_empty_values_unless_allowed() {
+        let mut tmp = TempVault::new();
+
+        assert!(matches!(
+            tmp.vault.set("mypass", ""),
+            Err(PassmateError::EmptyValue)
+        ));
+        assert_none!(tmp.vault.get("mypass"));
+
+        tmp.vault.set_allow_empty_values(true);
+        assert_ok!(tmp.vault.set("mypass", ""));
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "");
+    }
+
+    #[test]
+    fn set_rejects_values_with_control_characters_unless_allowed() {
+        let mut tmp = TempVault::new();
+
+        for value in ["line\nbreak", "nul\0byte", "tab\there"] {
+            assert!(matches!(
+                tmp.vault.set("mypass", value),
+                Err(PassmateError::InvalidValue(_))
+            ));
+        }
+        assert_none!(tmp.vault.get("mypass"));
+
+        tmp.vault.set_allow_control_chars(true);
+        assert_ok!(tmp.vault.set("mypass", "line\nbreak\0"));
+        assert_eq!(tmp.vault.get("mypass").unwrap(), "line\nbreak\0");
+    }
+
+    #[test]
+    fn set_many_from_map_skips_existing_entries_unless_taking_theirs() {
+        let mut temp = TempVault::new();
+        temp.vault.set("github", "old").unwrap();
+        let map = HashMap::from([
+            ("github".to_string(), "new".to_string()),
+            ("gitlab".to_string(), "secret".to_string()),
+        ]);
+
+        let report = temp
+            .vault
+            .set_many_from_map(map.clone(), ImportOptions::default());
+
+        assert_eq!(report.added, ["gitlab"]);
+        assert_eq!(report.skipped, ["github"]);
+        assert!(report.updated.is_empty());
+        assert_eq!(temp.vault.get("github").unwrap(), "old");
+
+        let take_theirs = ImportOptions {
+            strategy: MergeStrategy::TakeTheirs,
+            ..ImportOptions::default()
+        };
+        let report = temp.vault.set_many_from_map(map, take_theirs);
+
+        assert_eq!(report.updated, ["github", "gitlab"]);
+        assert!(report.added.is_empty() && report.skipped.is_empty());
+        assert_eq!(temp.vault.get("github").unwrap(), "new");
+    }
+
+    #[test]
+    fn set_many_from_map_with_dedupe_reports_identical_entries_as_unchanged() {
+        let mut temp = TempVault::new();
+        temp.vault.set("same", "secret").unwrap();
+        temp.vault.set("changed", "old").unwrap();
+        let map = HashMap::from([
+            ("same".to_string(), "secret".to_string()),
+            ("changed".to_string(), "new".to_string()),
+            ("added".to_string(), "secret".to_string()),
+        ]);
+        let options = ImportOptions {
+            strategy: MergeStrategy::TakeTheirs,
+            dedupe: true,
+            ..ImportOptions::default()
+        };
+
+        let report = temp.vault.set_many_from_map(map, options);
+
+        assert_eq!(report.added, ["added"]);
+        assert_eq!(report.updated, ["changed"]);
+        assert_eq!(report.unchanged, ["same"]);
+        assert!(report.skipped.is_empty());
+        assert!(temp.vault.get_entry("same").unwrap().history.is_empty());
+        assert_eq!(temp.vault.get_entry("changed").unwrap().history, ["old"]);
+    }
+
+    #[test]
+    fn set_many_from_map_reports_invalid_entries_and_imports_the_rest() {
+        let mut temp = TempVault::new();
+        let map = HashMap::from([
+            (" padded".to_string(), "secret".to_string()),
+            ("github".to_string(), "secret".to_string()),
+        ]);
+
+        let report = temp.vault.set_many_from_map(map, ImportOptions::default());
+
+        assert_eq!(report.added, ["github"]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].0, " padded");
+        assert!(matches!(
+            report.rejected[0].1,
+            PassmateError::InvalidName(_)
+        ));
+    }
+
+    #[test]
+    fn rollback_restores_the_entries_at_the_checkpoint() {
+        let mut temp = TempVault::new();
+        temp.vault.set("kept", "secret").unwrap();
+        temp.vault.set("changed", "before").unwrap();
+        temp.vault.set("removed", "secret").unwrap();
+        let before = temp.vault.get_entry("changed").cloned();
+
+        let checkpoint = temp.vault.checkpoint();
+        temp.vault.set("changed", "after").unwrap();
+        temp.vault.remove("removed");
+        temp.vault.set("added", "secret").unwrap();
+        assert_eq!(temp.vault.entries(), ["added", "changed", "kept"]);
+        temp.vault.rollback(checkpoint);
+
+        assert_eq!(temp.vault.entries(), ["changed", "kept", "removed"]);
+        assert_eq!(temp.vault.get_entry("changed").cloned(), before);
+        assert_eq!(temp.vault.get("removed").unwrap(), "secret");
+    }
+
+    #[test]
+    fn replace_data_swaps_out_every_entry() {
+        let mut temp = TempVault::new();
+        temp.vault.set("old", "secret").unwrap();
+        temp.vault.set("shared", "before").unwrap();
+
+        temp.vault
+            .replace_data(HashMap::from([
+                ("new".to_string(), "fresh\nlines".to_string()),
+                ("shared".to_string(), "after".to_string()),
+            ]))
+            .unwrap();
+
+        assert_eq!(temp.vault.entries(), ["new", "shared"]);
+        assert_eq!(temp.vault.get("new").unwrap(), "fresh\nlines");
+        assert_eq!(temp.vault.get("shared").unwrap(), "after");
+        assert!(temp.vault.get_entry("shared").unwrap().history.is_empty());
+    }
+
+    #[test]
+    fn replace_data_leaves_the_vault_unchanged_if_an_entry_is_invalid() {
+        let mut temp = TempVault::new();
+        temp.vault.set("old", "secret").unwrap();
+
+        let result = temp.vault.replace_data(HashMap::from([
+            ("new".to_string(), "fresh".to_string()),
+            (" padded".to_string(), "secret".to_string()),
+        ]));
+
+        assert!(matches!(result, Err(PassmateError::InvalidName(_))));
+        assert_eq!(temp.vault.entries(), ["old"]);
+    }
+
+    #[test]
+    fn import_json_imports_an_object_of_values() {
+        let mut temp = TempVault::new();
+
+        let report = temp
+            .vault
+            .import_json(
+                r#"{"github": "secret", "gitlab": "other"}"#,
+                ImportOptions::default(),
+            )
+            .unwrap();
+
+        assert_eq!(report.added, ["github", "gitlab"]);
+        assert_eq!(temp.vault.get("gitlab").unwrap(), "other");
+        assert_err!(temp
+            .vault
+            .import_json(r#"["github"]"#, ImportOptions::default()));
+    }
+
+    #[test]
+    fn import_with_the_newest_strategy_only_updates_entries_updated_later() {
+        let mut tmp = TempVault::new();
+        for (name, updated_at) in [("older", 100), ("newer", 300), ("same", 200)] {
+            let entry = Entry {
+                updated_at: Some(updated_at),
+                ..Entry::new("mine")
+            };
+            tmp.vault.set_entry(name, entry).unwrap();
+        }
+        let options = ImportOptions {
+            strategy: MergeStrategy::Newest,
+            ..ImportOptions::default()
+        };
+
+        let report = tmp
+            .vault
+            .import_json(
+                r#"{
+                    "older": {"secret": "theirs", "updated_at": 200},
+                    "newer": {"secret": "theirs", "updated_at": 200},
+                    "same": {"secret": "theirs", "updated_at": 200},
+                    "bare": "theirs"
+                }"#,
+                options,
+            )
+            .unwrap();
+
+        assert_eq!(report.added, ["bare"]);
+        assert_eq!(report.updated, ["older"]);
+        assert_eq!(report.skipped, ["newer", "same"]);
+        assert_eq!(tmp.vault.get("older").unwrap(), "theirs");
+        assert_eq!(tmp.vault.get_entry("older").unwrap().updated_at, Some(200));
+        assert_eq!(tmp.vault.get("newer").unwrap(), "mine");
+        assert_eq!(tmp.vault.get("same").unwrap(), "mine");
+    }
+
+    #[test]
+    fn import_json_imports_entries_with_metadata() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "older").unwrap();
+        tmp.vault.set("github", "old").unwrap();
+        let options = ImportOptions {
+            strategy: MergeStrategy::TakeTheirs,
+            ..ImportOptions::default()
+        };
+
+        let report = tmp
+            .vault
+            .import_json(
+                r#"{"github": {"secret": "new", "username": "me"}, "bank": "other"}"#,
+                options,
+            )
+            .unwrap();
+
+        assert_eq!(report.added, ["bank"]);
+        assert_eq!(report.updated, ["github"]);
+        let github = tmp.vault.get_entry("github").unwrap();
+        assert_eq!(github.secret, "new");
+        assert_eq!(github.username.as_deref(), Some("me"));
+        assert_eq!(github.history, ["older", "old"]);
+        assert_eq!(tmp.vault.get("bank").unwrap(), "other");
+    }
+
+    #[test]
+    fn import_keepass_csv_rejects_entries_with_the_same_group_and_title() {
+        let mut tmp = TempVault::new();
+        let csv = "\"Group\",\"Title\",\"Password\"\n\
+                   \"Root/Internet\",\"github\",\"first\"\n\
+                   \"Root/Internet\",\"github\",\"second\"\n";
+
+        let report = tmp
+            .vault
+            .import_keepass_csv(csv, ImportOptions::default())
+            .unwrap();
+
+        assert_eq!(report.added, ["Internet/github"]);
+        assert!(matches!(
+            report.rejected.as_slice(),
+            [(name, PassmateError::DuplicateName(_))] if name == "Internet/github"
+        ));
+        assert_eq!(tmp.vault.get("Internet/github").unwrap(), "first");
+    }
+
+    #[test]
+    fn import_json_rejects_unknown_fields_only_in_strict_mode() {
+        let json = r#"{"github": {"secret": "test", "usrname": "me", "tag": ["x"]}}"#;
+        let strict = ImportOptions {
+            strict: true,
+            ..ImportOptions::default()
+        };
+        let mut tmp = TempVault::new();
+
+        let err = tmp.vault.import_json(json, strict).unwrap_err();
+        assert!(matches!(
+            &err,
+            PassmateError::UnknownFields { name, fields }
+                if name == "github" && fields == &["tag", "usrname"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unknown fields in entry github: tag, usrname"
+        );
+        assert!(tmp.vault.entries().is_empty());
+
+        let report = tmp
+            .vault
+            .import_json(json, ImportOptions::default())
+            .unwrap();
+        assert_eq!(report.added, ["github"]);
+        assert_eq!(tmp.vault.get_entry("github").unwrap().username, None);
+    }
+
+    #[test]
+    fn serialize_entries_is_byte_identical_for_the_same_data() {
+        let entries = || -> HashMap<String, Entry> {
+            (0..50)
+                .map(|i| {
+                    let entry = Entry {
+                        fields: (0..10)
+                            .map(|f| (format!("field{f}"), i.to_string()))
+                            .collect(),
+                        ..Entry::new(format!("secret{i}"))
+                    };
+                    (format!("entry{i}"), entry)
+                })
+                .collect()
+        };
+        let (first, second) = (entries(), entries());
+
+        let serialized = serialize_entries(&first, true).unwrap();
+
+        assert_eq!(serialized, serialize_entries(&second, true).unwrap());
+        assert_eq!(
+            serialize_entries(&first, false).unwrap(),
+            serialize_entries(&second, false).unwrap()
+        );
+        let json = String::from_utf8(serialized).unwrap();
+        assert!(json
+            .starts_with(r#"{"entry0":{"secret":"secret0","fields":{"field0":"0","field1":"0","#));
+        assert!(json.find(r#""entry1":"#) < json.find(r#""entry10":"#));
+    }
+
+    #[test]
+    fn export_entries_json_round_trips_through_strict_import_json() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            username: Some("me".into()),
+            tags: vec!["work".into()],
+            fields: HashMap::from([("pin".into(), "1234".into())]),
+            history: vec!["old".into()],
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry).unwrap();
+        let strict = ImportOptions {
+            strict: true,
+            ..ImportOptions::default()
+        };
+
+        let mut other = TempVault::new();
+        other
+            .vault
+            .import_json(&tmp.vault.export_entries_json().unwrap(), strict)
+            .unwrap();
+
+        assert!(other.vault.data_eq(&tmp.vault));
+    }
+
+    #[test]
+    fn export_entries_round_trips_through_import_as_yaml() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            username: Some("me".into()),
+            tags: vec!["work".into()],
+            fields: HashMap::from([("pin".into(), "1234".into())]),
+            history: vec!["old".into()],
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry).unwrap();
+        tmp.vault.set("bank", "other").unwrap();
+        let strict = ImportOptions {
+            strict: true,
+            ..ImportOptions::default()
+        };
+
+        let yaml = tmp.vault.export_entries(DataFormat::Yaml).unwrap();
+        let mut other = TempVault::new();
+        other.vault.import(&yaml, DataFormat::Yaml, strict).unwrap();
+
+        assert!(yaml.starts_with("bank:\n  secret: other\n"));
+        assert!(other.vault.data_eq(&tmp.vault));
+    }
+
+    #[test]
+    fn export_entries_as_lines_round_trips_secrets_with_tabs_and_newlines() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_allow_control_chars(true);
+        tmp.vault
+            .set_entry(
+                "github",
+                Entry {
+                    username: Some("me".into()),
+                    ..Entry::new("multi\nline\twith \\n")
+                },
+            )
+            .unwrap();
+        tmp.vault.set("bank", "other").unwrap();
+
+        let lines = tmp.vault.export_entries(DataFormat::Lines).unwrap();
+        let mut other = TempVault::new();
+        other
+            .vault
+            .import(&lines, DataFormat::Lines, ImportOptions::default())
+            .unwrap();
+
+        assert_eq!(lines, "bank\tother\ngithub\tmulti\\nline\\twith \\\\n\n");
+        assert_eq!(other.vault.get("github").unwrap(), "multi\nline\twith \\n");
+        assert_eq!(other.vault.entries(), ["bank", "github"]);
+    }
+
+    #[test]
+    fn import_reads_a_yaml_mapping_of_names_to_values() {
+        let mut tmp = TempVault::new();
+
+        let report = tmp
+            .vault
+            .import(
+                "github: secret\nbank: other\n",
+                DataFormat::Yaml,
+                ImportOptions::default(),
+            )
+            .unwrap();
+
+        assert_eq!(report.added, ["bank", "github"]);
+        assert_eq!(
+            tmp.vault.export_secrets(DataFormat::Yaml).unwrap(),
+            "bank: other\ngithub: secret\n"
+        );
+        assert!(matches!(
+            tmp.vault
+                .import("- github", DataFormat::Yaml, ImportOptions::default()),
+            Err(PassmateError::Yaml(_))
+        ));
+    }
+
+    #[test]
+    fn data_format_is_detected_from_the_file_extension() {
+        assert_eq!(
+            DataFormat::from_path(Path::new("secrets.yml")),
+            Some(DataFormat::Yaml)
+        );
+        assert_eq!(
+            DataFormat::from_path(Path::new("secrets.YAML")),
+            Some(DataFormat::Yaml)
+        );
+        assert_eq!(
+            DataFormat::from_path(Path::new("secrets.json")),
+            Some(DataFormat::Json)
+        );
+        assert_eq!(DataFormat::from_path(Path::new("secrets.txt")), None);
+        assert_eq!(DataFormat::from_path(Path::new("-")), None);
+    }
+
+    #[test]
+    fn export_json_round_trips_through_import_json() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.set("bank", "other").unwrap();
+
+        let json = tmp.vault.export_json().unwrap();
+        let mut other = TempVault::new();
+        other
+            .vault
+            .import_json(&json, ImportOptions::default())
+            .unwrap();
+
+        assert!(json.find("bank") < json.find("github"));
+        assert_eq!(other.vault.values(), ["other", "secret"]);
+    }
+
+    #[test]
+    fn export_entries_json_keeps_the_metadata_that_export_json_drops() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            username: Some("me".into()),
+            tags: vec!["work".into()],
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry).unwrap();
+
+        let secrets: serde_json::Value =
+            serde_json::from_str(&tmp.vault.export_json().unwrap()).unwrap();
+        let entries: serde_json::Value =
+            serde_json::from_str(&tmp.vault.export_entries_json().unwrap()).unwrap();
+
+        assert_eq!(secrets, serde_json::json!({"github": "secret"}));
+        assert_eq!(entries["github"]["secret"], "secret");
+        assert_eq!(entries["github"]["username"], "me");
+        assert_eq!(entries["github"]["tags"], serde_json::json!(["work"]));
+    }
+
+    fn merge_fixture() -> (TempVault, TempVault) {
+        let mut ours = TempVault::new();
+        ours.vault
+            .set_entry("same", Entry::new("same".to_string()))
+            .unwrap();
+        ours.vault
+            .set_entry(
+                "older",
+                Entry {
+                    updated_at: Some(100),
+                    ..Entry::new("ours".to_string())
+                },
+            )
+            .unwrap();
+        ours.vault
+            .set_entry(
+                "newer",
+                Entry {
+                    updated_at: Some(300),
+                    ..Entry::new("ours".to_string())
+                },
+            )
+            .unwrap();
+
+        let mut theirs = TempVault::new();
+        theirs.vault.data = ours.vault.data.clone();
+        theirs
+            .vault
+            .set_entry("added", Entry::new("theirs".to_string()))
+            .unwrap();
+        for (name, updated_at) in [("older", 200), ("newer", 200)] {
+            theirs.vault.data.insert(
+                name.into(),
+                Entry {
+                    updated_at: Some(updated_at),
+                    ..Entry::new("theirs".to_string())
+                },
+            );
+        }
+        theirs.vault.save().unwrap();
+        (ours, theirs)
+    }
+
+    #[test]
+    fn merge_from_file_resolves_differing_entries_with_each_strategy() {
+        let cases = [
+            (
+                MergeStrategy::KeepOurs,
+                vec![],
+                vec!["newer", "older"],
+                "ours",
+                "ours",
+            ),
+            (
+                MergeStrategy::TakeTheirs,
+                vec!["newer", "older"],
+                vec![],
+                "theirs",
+                "theirs",
+            ),
+            (
+                MergeStrategy::Newest,
+                vec!["older"],
+                vec!["newer"],
+                "ours",
+                "theirs",
+            ),
+        ];
+        for (strategy, updated, conflicted, newer, older) in cases {
+            let (mut ours, theirs) = merge_fixture();
+
+            let report = ours
+                .vault
+                .merge_from_file(&theirs.vault.path, "testpwd", strategy)
+                .unwrap();
+
+            assert_eq!(report.added, ["added"], "{strategy:?}");
+            assert_eq!(report.updated, updated, "{strategy:?}");
+            assert_eq!(report.conflicted, conflicted, "{strategy:?}");
+            assert_eq!(ours.vault.get("newer").unwrap(), newer, "{strategy:?}");
+            assert_eq!(ours.vault.get("older").unwrap(), older, "{strategy:?}");
+            assert_eq!(ours.vault.get("added").unwrap(), "theirs");
+            assert_eq!(ours.vault.get("same").unwrap(), "same");
+        }
+    }
+
+    #[test]
+    fn merge_with_applies_the_resolution_of_each_conflicting_entry() {
+        let (mut ours, mut theirs) = merge_fixture();
+        theirs
+            .vault
+            .set_entry("custom", Entry::new("theirs"))
+            .unwrap();
+        ours.vault.set_entry("custom", Entry::new("ours")).unwrap();
+        let mut calls = Vec::new();
+
+        let report = ours.vault.merge_with(&theirs.vault, |name, mine, other| {
+            calls.push((name.to_string(), mine.to_string(), other.to_string()));
+            match calls.len() {
+                1 => Resolution::Value("merged".into()),
+                2 => Resolution::Mine,
+                _ => Resolution::Theirs,
+            }
+        });
+
+        assert_eq!(
+            calls,
+            [
+                ("custom".into(), "ours".into(), "theirs".into()),
+                ("newer".into(), "ours".into(), "theirs".into()),
+                ("older".into(), "ours".into(), "theirs".into()),
+            ]
+        );
+        assert_eq!(report.added, ["added"]);
+        assert_eq!(report.updated, ["custom", "older"]);
+        assert_eq!(report.conflicted, ["newer"]);
+        assert_eq!(ours.vault.get("custom").unwrap(), "merged");
+        assert_eq!(ours.vault.get_entry("custom").unwrap().history, ["ours"]);
+        assert_eq!(ours.vault.get("newer").unwrap(), "ours");
+        assert_eq!(ours.vault.get("older").unwrap(), "theirs");
+        assert_eq!(ours.vault.get_entry("older").unwrap().history, ["ours"]);
+    }
+
+    #[test]
+    fn merge_with_rejects_invalid_values_and_keeps_our_entry() {
+        let (mut ours, theirs) = merge_fixture();
+        let before = ours.vault.data.clone();
+
+        let report = ours
+            .vault
+            .merge_with(&theirs.vault, |name, _, _| match name {
+                "newer" => Resolution::Value(String::new()),
+                _ => Resolution::Value("two\nlines".into()),
+            });
+
+        let rejected: Vec<&str> = report
+            .rejected
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(rejected, ["newer", "older"]);
+        assert!(matches!(report.rejected[0].1, PassmateError::EmptyValue));
+        assert!(matches!(
+            report.rejected[1].1,
+            PassmateError::InvalidValue(_)
+        ));
+        assert!(report.updated.is_empty());
+        assert_eq!(report.added, ["added"]);
+        ours.vault.remove("added");
+        assert_eq!(ours.vault.data, before);
+    }
+
+    #[test]
+    fn merge_from_file_returns_an_error_for_a_missing_file_or_wrong_passphrase() {
+        let (mut ours, theirs) = merge_fixture();
+        let missing = theirs.vault.path.with_file_name("missing.vault");
+
+        assert_err!(ours
+            .vault
+            .merge_from_file(missing, "testpwd", MergeStrategy::KeepOurs));
+        assert!(matches!(
+            ours.vault
+                .merge_from_file(&theirs.vault.path, "wrongpwd", MergeStrategy::KeepOurs),
+            Err(PassmateError::InvalidPassphrase { .. })
+        ));
+    }
+
+    #[test]
+    fn remove_deletes_the_secret_with_the_given_name_from_the_vault() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+
+        assert!(tmp.vault.remove("mypass"));
+
+        assert_none!(tmp.vault.get("mypass"));
+        assert!(!tmp.vault.remove("mypass"));
+    }
+
+    #[test]
+    fn remove_many_reports_removed_and_missing_names() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "test").unwrap();
+        tmp.vault.set("gitlab", "test").unwrap();
+        tmp.vault.set("bank", "test").unwrap();
+
+        let report = tmp
+            .vault
+            .remove_many(["github", "missing", "bank", "github"]);
+
+        assert_eq!(
+            report,
+            RemoveReport {
+                removed: vec!["github".into(), "bank".into()],
+                not_found: vec!["missing".into(), "github".into()],
+            }
+        );
+        assert_eq!(tmp.vault.entries(), ["gitlab"]);
+    }
+
+    #[test]
+    fn data_eq_returns_true_for_vaults_with_identical_entries() {
+        let mut tmp1 = TempVault::new();
+        let mut tmp2 = TempVault::new();
+        tmp1.vault.set("mypass", "test").unwrap();
+        tmp2.vault.set("mypass", "test").unwrap();
+
+        assert!(tmp1.vault.data_eq(&tmp2.vault));
+    }
+
+    #[test]
+    fn data_eq_returns_false_for_vaults_with_differing_entries() {
+        let mut tmp1 = TempVault::new();
+        let mut tmp2 = TempVault::new();
+        tmp1.vault.set("mypass", "test").unwrap();
+        tmp2.vault.set("mypass", "different").unwrap();
+
+        assert!(!tmp1.vault.data_eq(&tmp2.vault));
+
+        tmp2.vault.set("mypass", "test").unwrap();
+        tmp2.vault.set("otherpass", "test").unwrap();
+
+        assert!(!tmp1.vault.data_eq(&tmp2.vault));
+    }
+
+    #[test]
+    fn save_replaces_the_vault_file_leaving_other_links_to_it_intact() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+        let original = std::fs::read(&tmp.vault.path).unwrap();
+        let link = tmp.vault.path.with_extension("link");
+        std::fs::hard_link(&tmp.vault.path, &link).unwrap();
+
+        tmp.vault.set("github", "changed").unwrap();
+        tmp.vault.save().unwrap();
+
+        assert_eq!(std::fs::read(&link).unwrap(), original);
+        assert_ne!(std::fs::read(&tmp.vault.path).unwrap(), original);
+    }
+
+    #[test]
+    fn save_with_secure_delete_overwrites_the_previous_vault_file() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+        let original = std::fs::read(&tmp.vault.path).unwrap();
+        let link = tmp.vault.path.with_extension("link");
+        std::fs::hard_link(&tmp.vault.path, &link).unwrap();
+
+        tmp.vault.set_secure_delete(true);
+        tmp.vault.set("github", "changed").unwrap();
+        tmp.vault.save().unwrap();
+
+        let overwritten = std::fs::read(&link).unwrap();
+        assert_eq!(overwritten.len(), original.len());
+        assert!(overwritten.iter().all(|&byte| byte == 0));
+        let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert_eq!(reopened.get("github").unwrap(), "changed");
+    }
+
+    #[test]
+    fn save_with_write_checksum_writes_a_checksum_file_that_detects_tampering() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_write_checksum(true);
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+        let checksum = std::fs::read_to_string(checksum_path(&tmp.vault.path)).unwrap();
+
+        let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        let mut swapped = Vault::open_bytes(&tmp.vault.save_bytes().unwrap(), "testpwd").unwrap();
+        swapped.set("github", "attacker").unwrap();
+        std::fs::write(&tmp.vault.path, swapped.save_bytes().unwrap()).unwrap();
+        let tampered = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        let (salt, key) = tampered.derived_key().unwrap();
+        let with_key = Vault::open_with_key(&tmp.vault.path, key, &salt).unwrap();
+        let many = Vault::open_many(std::slice::from_ref(&tmp.vault.path), "testpwd");
+
+        assert!(checksum.ends_with("  test.vault\n"));
+        assert_eq!(checksum.split_whitespace().next().unwrap().len(), 64);
+        assert!(!reopened.checksum_mismatch());
+        assert!(tampered.checksum_mismatch());
+        assert!(with_key.checksum_mismatch());
+        assert!(many[0].as_ref().unwrap().checksum_mismatch());
+    }
+
+    #[test]
+    fn save_keeps_an_existing_checksum_file_up_to_date() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+        assert!(!checksum_path(&tmp.vault.path).exists());
+        tmp.vault.set_write_checksum(true);
+        tmp.vault.save().unwrap();
+
+        tmp.vault.set_write_checksum(false);
+        tmp.vault.set("github", "changed").unwrap();
+        tmp.vault.save().unwrap();
+
+        let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert!(!reopened.checksum_mismatch());
+    }
+
+    #[test]
+    fn save_leaves_the_vault_as_it_was_if_the_checksum_cannot_be_written() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_write_checksum(true);
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+        let saved = std::fs::read(&tmp.vault.path).unwrap();
+        // A directory in the way of the checksum's temporary file fails its write.
+        let blocked = temp_path(&checksum_path(&tmp.vault.path));
+        std::fs::create_dir(&blocked).unwrap();
+
+        tmp.vault.set("github", "changed").unwrap();
+        assert!(matches!(tmp.vault.save(), Err(PassmateError::IO(_))));
+
+        assert_eq!(std::fs::read(&tmp.vault.path).unwrap(), saved);
+        assert!(!temp_path(&tmp.vault.path).exists());
+        let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert!(!reopened.checksum_mismatch());
+        assert_eq!(reopened.get("github").unwrap(), "secret");
+    }
+
+    #[test]
+    fn save_with_path_writes_an_openable_vault_without_changing_the_path() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        let original_path = tmp.vault.path.clone();
+        let backup = original_path.with_file_name("backup").join("copy.vault");
+
+        assert_ok!(tmp.vault.save_with_path(&backup));
+
+        assert_eq!(tmp.vault.path, original_path);
+        assert!(!original_path.exists());
+        let copy = Vault::open(&backup, "testpwd").unwrap();
+        assert!(copy.data_eq(&tmp.vault));
+    }
+
+    fn flaky(failures: u32, kind: ErrorKind) -> impl FnMut() -> Result<u32, PassmateError> {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            if calls > failures {
+                Ok(calls)
+            } else {
+                Err(PassmateError::IO(std::io::Error::from(kind)))
+            }
+        }
+    }
+
+    #[test]
+    fn retry_succeeds_after_transient_failures() {
+        assert_eq!(
+            retry(3, Duration::ZERO, flaky(2, ErrorKind::WouldBlock)).unwrap(),
+            3
+        );
+        assert_eq!(
+            retry(5, Duration::ZERO, flaky(1, ErrorKind::ResourceBusy)).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn retry_gives_up_after_the_last_attempt() {
+        assert!(matches!(
+            retry(2, Duration::ZERO, flaky(2, ErrorKind::TimedOut)),
+            Err(PassmateError::IO(e)) if e.kind() == ErrorKind::TimedOut
+        ));
+    }
+
+    #[test]
+    fn retry_does_not_retry_errors_that_are_not_transient() {
+        let mut calls = 0;
+        let result = retry(5, Duration::ZERO, || {
+            calls += 1;
+            flaky(1, ErrorKind::PermissionDenied)()
+        });
+
+        assert!(matches!(
+            result,
+            Err(PassmateError::IO(e)) if e.kind() == ErrorKind::PermissionDenied
+        ));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn save_with_retry_saves_the_vault() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+
+        assert_ok!(tmp.vault.save_with_retry(3, Duration::from_millis(1)));
+
+        let reopened = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert!(reopened.data_eq(&tmp.vault));
+    }
+
+    #[test]
+    fn plaintext_vaults_round_trip_without_a_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.plain");
+        let mut vault = Vault::open_plaintext(&path).unwrap();
+        vault.set("editor", "vim").unwrap();
+        vault.save().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(format::is_plaintext(&bytes));
+        assert!(String::from_utf8_lossy(&bytes).contains("vim"));
+        let reopened = Vault::open_plaintext(&path).unwrap();
+        assert!(reopened.is_plaintext());
+        assert_eq!(reopened.get("editor").unwrap(), "vim");
+    }
+
+    #[test]
+    fn encrypted_opens_refuse_a_plaintext_vault_without_asking_for_the_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("default.vault");
+        let mut vault = Vault::open_plaintext(&path).unwrap();
+        vault.set("editor", "vim").unwrap();
+        vault.save().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        assert!(matches!(
+            Vault::open_bytes(&bytes, "testpwd"),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Vault::open(&path, "testpwd"),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Vault::open_with(&path, || panic!("asked for the passphrase")),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn open_plaintext_refuses_an_encrypted_vault() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        tmp.vault.save().unwrap();
+
+        assert!(matches!(
+            Vault::open_plaintext(&tmp.vault.path),
+            Err(PassmateError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn save_bytes_with_reuse_salt_keeps_the_salt_across_saves() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_reuse_salt(true);
+        tmp.vault.set("github", "first").unwrap();
+        let first = tmp.vault.save_bytes().unwrap();
+        tmp.vault.set("github", "second").unwrap();
+        let second = tmp.vault.save_bytes().unwrap();
+
+        let (first_info, second_info) = (
+            format::inspect(&first).unwrap(),
+            format::inspect(&second).unwrap(),
+        );
+        assert_eq!(first_info.salt, second_info.salt);
+        assert_ne!(first_info.nonce, second_info.nonce);
+        let reopened = Vault::open_bytes(&second, "testpwd").unwrap();
+        assert_eq!(reopened.get("github").unwrap(), "second");
+    }
+
+    #[test]
+    fn save_bytes_with_reuse_salt_reuses_the_salt_the_vault_was_opened_with() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+        let saved = tmp.vault.save_bytes().unwrap();
+        let mut vault = Vault::open_bytes(&saved, "testpwd").unwrap();
+        vault.set_reuse_salt(true);
+
+        vault.set("github", "changed").unwrap();
+        let resaved = vault.save_bytes().unwrap();
+
+        assert_eq!(
+            format::inspect(&saved).unwrap().salt,
+            format::inspect(&resaved).unwrap().salt
+        );
+        let reopened = Vault::open_bytes(&resaved, "testpwd").unwrap();
+        assert_eq!(reopened.get("github").unwrap(), "changed");
+    }
+
+    #[test]
+    fn save_bytes_uses_a_fresh_salt_by_default() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+
+        assert_ne!(
+            format::inspect(&tmp.vault.save_bytes().unwrap())
+                .unwrap()
+                .salt,
+            format::inspect(&tmp.vault.save_bytes().unwrap())
+                .unwrap()
+                .salt
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_returns_permission_denied_for_an_unreadable_vault() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.save().unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        if std::fs::read(&path).is_ok() {
+            // Running as root, which ignores the permissions.
+            return;
+        }
+
+        let denied = |result: Result<Vault, PassmateError>| matches!(result, Err(PassmateError::PermissionDenied(denied)) if denied == path);
+        assert!(denied(Vault::open(&path, "testpwd")));
+        assert!(denied(
+            Vault::open_many(std::slice::from_ref(&path), "testpwd").remove(0)
+        ));
+        assert!(denied(Vault::open_with_key(&path, [0; 32], &[])));
+        assert!(matches!(
+            Vault::open(temp_dir.path().join("other.vault"), "testpwd")
+                .unwrap()
+                .merge_from_file(&path, "testpwd", MergeStrategy::KeepOurs),
+            Err(PassmateError::PermissionDenied(denied)) if denied == path
+        ));
+    }
+
+    #[test]
+    fn open_and_save_reject_a_directory_as_the_vault_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let is_dir = |result| matches!(result, Err(PassmateError::InvalidPath(path)) if path == temp_dir.path());
+
+        assert!(is_dir(Vault::open(temp_dir.path(), "testpwd").map(drop)));
+        assert!(is_dir(
+            Vault::open_with(temp_dir.path(), || unreachable!()).map(drop)
+        ));
+
+        let mut vault = Vault::open(temp_dir.path().join("test.vault"), "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        assert!(is_dir(vault.save_with_path(temp_dir.path())));
+    }
+
+    #[test]
+    fn set_field_adds_and_updates_a_custom_field() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("bank", "secret").unwrap();
+
+        tmp.vault
+            .set_field("bank", "security question", "Rex")
+            .unwrap();
+        assert_eq!(tmp.vault.field("bank", "security question"), Some("Rex"));
+        tmp.vault
+            .set_field("bank", "security question", "Max")
+            .unwrap();
+        assert_eq!(tmp.vault.field("bank", "security question"), Some("Max"));
+        assert_eq!(tmp.vault.get("bank").unwrap(), "secret");
+        assert!(matches!(
+            tmp.vault.set_field("missing", "pin", "1234"),
+            Err(PassmateError::EntryNotFound(_))
+        ));
+        assert!(matches!(
+            tmp.vault.set_field("bank", "", "1234"),
+            Err(PassmateError::InvalidFieldName(_))
+        ));
+    }
 
-impl Vault {
-    /// Opens the vault at the given path or returns
-    /// a empty vault if it doesn't already exist.
-    ///
-    /// # Errors
-    /// May return an error if opening, decrypting, or deserializing the vault data fails.
-    pub fn open(path: impl AsRef<Path>, passphrase: &str) -> Result<Self, PassmateError> {
-        match File::open(&path) {
-            Ok(mut file) => {
-                let mut encrypted_data = Vec::new();
-                file.read_to_end(&mut encrypted_data)
-                    .map_err(PassmateError::IO)?;
-                let (salt, encrypted_data) = encrypted_data.split_at(16);
-                let key = make_key(passphrase, salt)?;
-                let data = decrypt(key, encrypted_data)?;
-                let data = serde_json::from_slice(&data).map_err(PassmateError::Json)?;
-                Ok(Self {
-                    path: PathBuf::from(path.as_ref()),
-                    passphrase: passphrase.into(),
-                    data,
-                })
+    #[test]
+    fn set_field_checks_the_value_like_a_secret() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("bank", "secret").unwrap();
+        tmp.vault.set_max_value_size(Some(4));
+
+        assert!(matches!(
+            tmp.vault.set_field("bank", "pin", "12345"),
+            Err(PassmateError::ValueTooLarge { size: 5, limit: 4 })
+        ));
+        assert!(matches!(
+            tmp.vault.set_field("bank", "pin", "1\n2"),
+            Err(PassmateError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            tmp.vault.set_field("bank", "pin", ""),
+            Err(PassmateError::EmptyValue)
+        ));
+        assert_eq!(tmp.vault.field("bank", "pin"), None);
+    }
+
+    #[test]
+    fn rename_field_moves_the_value_to_the_new_name() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("bank", "secret").unwrap();
+        tmp.vault.set_field("bank", "pin", "1234").unwrap();
+        tmp.vault.set_field("bank", "account", "42").unwrap();
+
+        tmp.vault.rename_field("bank", "pin", "card pin").unwrap();
+
+        assert_eq!(tmp.vault.field("bank", "pin"), None);
+        assert_eq!(tmp.vault.field("bank", "card pin"), Some("1234"));
+        assert!(matches!(
+            tmp.vault.rename_field("bank", "pin", "other"),
+            Err(PassmateError::FieldNotFound(_))
+        ));
+        assert!(matches!(
+            tmp.vault.rename_field("bank", "card pin", "account"),
+            Err(PassmateError::InvalidFieldName(_))
+        ));
+        assert_eq!(tmp.vault.field("bank", "card pin"), Some("1234"));
+    }
+
+    #[test]
+    fn remove_field_removes_only_that_field() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("bank", "secret").unwrap();
+        tmp.vault.set_field("bank", "pin", "1234").unwrap();
+        tmp.vault.set_field("bank", "account", "42").unwrap();
+
+        tmp.vault.remove_field("bank", "pin").unwrap();
+
+        assert_eq!(tmp.vault.field("bank", "pin"), None);
+        assert_eq!(tmp.vault.field("bank", "account"), Some("42"));
+        assert!(matches!(
+            tmp.vault.remove_field("bank", "pin"),
+            Err(PassmateError::FieldNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn custom_fields_are_kept_across_saves() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("bank", "secret").unwrap();
+        vault.set_field("bank", "pin", "1234").unwrap();
+        vault.save().unwrap();
+
+        let reopened = Vault::open(&path, "testpwd").unwrap();
+
+        assert_eq!(reopened.field("bank", "pin"), Some("1234"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_creates_a_new_vault_file_only_the_owner_can_access() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let stale_temp = temp_dir.path().join(".test.vault.tmp");
+        std::fs::write(&stale_temp, "left by a crash").unwrap();
+        std::fs::set_permissions(&stale_temp, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+
+        vault.save().unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!stale_temp.exists());
+        assert!(Vault::open(&path, "testpwd").unwrap().data_eq(&vault));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_the_mode_and_owner_of_the_replaced_file() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.save().unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let before = std::fs::metadata(&path).unwrap();
+
+        vault.set("github", "changed").unwrap();
+        vault.save().unwrap();
+
+        let after = std::fs::metadata(&path).unwrap();
+        assert_eq!(after.permissions().mode() & 0o777, 0o640);
+        assert_eq!((after.uid(), after.gid()), (before.uid(), before.gid()));
+    }
+
+    #[test]
+    fn from_json_file_creates_a_vault_with_the_entries_of_the_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("secrets.json");
+        let path = temp_dir.path().join("test.vault");
+        std::fs::write(&json_path, r#"{"github": "secret", "bank": "other"}"#).unwrap();
+
+        let vault = Vault::from_json_file(&json_path, &path, "testpwd", false).unwrap();
+
+        let reopened = Vault::open(&path, "testpwd").unwrap();
+        assert_eq!(reopened.entries(), ["bank", "github"]);
+        assert_eq!(reopened.get("github").unwrap(), "secret");
+        assert!(reopened.data_eq(&vault));
+        assert!(json_path.exists());
+        assert!(matches!(
+            Vault::from_json_file(&json_path, &path, "testpwd", false),
+            Err(PassmateError::VaultExists(_))
+        ));
+    }
+
+    #[test]
+    fn from_json_file_deletes_the_file_when_asked() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("secrets.json");
+        let path = temp_dir.path().join("test.vault");
+        std::fs::write(&json_path, r#"{"github": "secret"}"#).unwrap();
+
+        Vault::from_json_file(&json_path, &path, "testpwd", true).unwrap();
+
+        assert!(!json_path.exists());
+        assert_eq!(
+            Vault::open(&path, "testpwd")
+                .unwrap()
+                .get("github")
+                .unwrap(),
+            "secret"
+        );
+    }
+
+    #[test]
+    fn from_json_file_creates_nothing_if_an_entry_is_invalid() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("secrets.json");
+        let path = temp_dir.path().join("test.vault");
+        std::fs::write(&json_path, r#"{" padded": "secret"}"#).unwrap();
+
+        assert!(matches!(
+            Vault::from_json_file(&json_path, &path, "testpwd", true),
+            Err(PassmateError::InvalidName(_))
+        ));
+        assert!(!path.exists());
+        assert!(json_path.exists());
+    }
+
+    #[test]
+    fn touch_only_changes_the_update_time() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            created_at: Some(1),
+            updated_at: Some(2),
+            history: vec!["old".into()],
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry.clone()).unwrap();
+
+        tmp.vault.touch("github").unwrap();
+
+        let touched = tmp.vault.get_entry("github").unwrap();
+        assert!(touched.updated_at > Some(2));
+        assert_eq!(
+            touched,
+            &Entry {
+                updated_at: touched.updated_at,
+                ..entry
             }
-            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self {
-                path: PathBuf::from(path.as_ref()),
-                passphrase: passphrase.into(),
-                data: HashMap::new(),
-            }),
-            Err(e) => Err(PassmateError::IO(e)),
+        );
+        assert!(matches!(
+            tmp.vault.touch("missing"),
+            Err(PassmateError::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn open_with_key_opens_a_vault_with_the_key_it_was_opened_with() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.save().unwrap();
+        let (salt, key) = Vault::open(&path, "testpwd")
+            .unwrap()
+            .derived_key()
+            .unwrap();
+
+        let mut reopened = Vault::open_with_key(&path, key, &salt).unwrap();
+        assert_eq!(reopened.get("github").unwrap(), "secret");
+        reopened.set("github", "changed").unwrap();
+        reopened.save().unwrap();
+
+        let resaved = Vault::open(&path, "testpwd").unwrap();
+        assert_eq!(resaved.get("github").unwrap(), "changed");
+        assert_eq!(resaved.derived_key(), Some((salt, key)));
+    }
+
+    #[test]
+    fn open_with_key_refuses_to_save_under_a_new_key_without_a_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.save().unwrap();
+        let (salt, key) = Vault::open(&path, "testpwd")
+            .unwrap()
+            .derived_key()
+            .unwrap();
+        let saved = std::fs::read(&path).unwrap();
+
+        let mut reopened = Vault::open_with_key(&path, key, &salt).unwrap();
+        reopened.set_reuse_salt(false);
+        assert!(matches!(reopened.save(), Err(PassmateError::NoPassphrase)));
+        let mut reopened = Vault::open_with_key(&path, key, &salt).unwrap();
+        reopened.set_kdf_params(KdfParams::default()).unwrap();
+        assert!(matches!(reopened.save(), Err(PassmateError::NoPassphrase)));
+        assert_eq!(std::fs::read(&path).unwrap(), saved);
+        assert!(Vault::open(&path, "").is_err());
+
+        assert_none!(reopened.set_passphrase("newpwd"));
+        reopened.save().unwrap();
+        assert_eq!(
+            Vault::open(&path, "newpwd").unwrap().get("github").unwrap(),
+            "secret"
+        );
+    }
+
+    #[test]
+    fn open_with_key_fails_when_the_salt_does_not_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.save().unwrap();
+        let (salt, key) = Vault::open(&path, "testpwd")
+            .unwrap()
+            .derived_key()
+            .unwrap();
+        vault.save().unwrap();
+
+        assert!(matches!(
+            Vault::open_with_key(&path, key, &salt),
+            Err(PassmateError::KeyMismatch)
+        ));
+        let (salt, _) = Vault::open(&path, "testpwd")
+            .unwrap()
+            .derived_key()
+            .unwrap();
+        assert!(matches!(
+            Vault::open_with_key(&path, [0; 32], &salt),
+            Err(PassmateError::InvalidPassphrase { .. })
+        ));
+    }
+
+    #[test]
+    fn save_bytes_with_rng_is_deterministic_for_a_seeded_rng() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+        let mut tmp = TempVault::new();
+        tmp.vault
+            .set_kdf_params(KdfParams {
+                m_cost: 8,
+                t_cost: 1,
+                p_cost: 1,
+                ..KdfParams::default()
+            })
+            .unwrap();
+        let entry = Entry {
+            created_at: Some(1_700_000_000),
+            updated_at: Some(1_700_000_000),
+            ..Entry::new("secret")
+        };
+        tmp.vault.set_entry("github", entry).unwrap();
+
+        // Saving under another passphrase derives another key from the same
+        // salt, so the seeded RNG never reuses a nonce with the same key.
+        let first = tmp
+            .vault
+            .save_bytes_with_rng(&mut ChaCha20Rng::seed_from_u64(7))
+            .unwrap();
+        tmp.vault.set_passphrase("otherpwd");
+        let second = tmp
+            .vault
+            .save_bytes_with_rng(&mut ChaCha20Rng::seed_from_u64(7))
+            .unwrap();
+
+        let mut expected = ChaCha20Rng::seed_from_u64(7);
+        let (mut salt, mut nonce) = ([0u8; format::SALT_LEN], [0u8; 12]);
+        rand::RngCore::fill_bytes(&mut expected, &mut salt);
+        rand::RngCore::fill_bytes(&mut expected, &mut nonce);
+        for (bytes, passphrase) in [(first, "testpwd"), (second, "otherpwd")] {
+            let info = format::inspect(&bytes).unwrap();
+            assert_eq!(info.salt, salt);
+            assert_eq!(info.nonce, nonce);
+            let reopened = Vault::open_bytes(&bytes, passphrase).unwrap();
+            assert_eq!(reopened.get("github").unwrap(), "secret");
         }
     }
 
-    /// Returns a list of entry names in alphabetical order.
-    #[must_use]
-    pub fn entries(&self) -> Vec<String> {
-        let mut entries: Vec<String> = self.data.keys().cloned().collect();
-        entries.sort();
-        entries
+    #[test]
+    fn set_passphrase_changes_the_passphrase_used_by_the_next_save() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("github", "secret").unwrap();
+
+        let old = tmp.vault.set_passphrase("newpwd").unwrap();
+        let rekeyed = tmp.vault.save_bytes().unwrap();
+        assert_eq!(old.as_str(), "testpwd");
+        assert_ok!(Vault::open_bytes(&rekeyed, "newpwd"));
+        assert!(Vault::open_bytes(&rekeyed, "testpwd").is_err());
+
+        assert_eq!(tmp.vault.set_passphrase(&old).unwrap().as_str(), "newpwd");
+        let restored = tmp.vault.save_bytes().unwrap();
+        assert_ok!(Vault::open_bytes(&restored, "testpwd"));
     }
 
-    /// Looks up an entry by the given name.
-    #[must_use]
-    pub fn get(&self, name: &str) -> Option<&String> {
-        self.data.get(name)
+    #[test]
+    fn set_passphrase_drops_the_key_cached_for_reuse() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_reuse_salt(true);
+        tmp.vault.save_bytes().unwrap();
+
+        tmp.vault.set_passphrase("newpwd");
+
+        assert_ok!(Vault::open_bytes(
+            &tmp.vault.save_bytes().unwrap(),
+            "newpwd"
+        ));
     }
 
-    /// Adds or updates an entry with the given name.
-    pub fn set<S>(&mut self, name: S, value: S)
-    where
-        S: Into<String>,
-    {
-        self.data.insert(name.into(), value.into());
+    #[test]
+    fn save_persists_the_vaults_data_to_disk_as_json() {
+        let mut temp_vault = TempVault::new();
+        temp_vault.vault.set("mypass", "test").unwrap();
+        assert_ok!(temp_vault.vault.save());
+
+        let got = Vault::open(&temp_vault.vault.path, "testpwd").unwrap();
+        let entry = temp_vault.vault.get_entry("mypass").unwrap().clone();
+        let want = HashMap::from([("mypass".into(), entry)]);
+        assert_eq!(got.data, want);
     }
 
-    /// Removes the entry with the given name.
-    pub fn remove(&mut self, name: &str) {
-        self.data.remove(name);
+    #[test]
+    fn save_stores_entries_without_metadata_as_plain_strings() {
+        let mut data = HashMap::new();
+        data.insert("mypass".to_string(), Entry::new("test"));
+
+        let json = serialize_entries(&data, false).unwrap();
+
+        assert_eq!(json, br#"{"mypass":"test"}"#);
+        assert_eq!(deserialize_entries(&json).unwrap(), data);
     }
 
-    /// Saves the vault to disk.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if it fails to create and write
-    /// to a file at the given path.
-    pub fn save(&self) -> Result<(), PassmateError> {
-        let salt = generate_salt();
-        let key = make_key(&self.passphrase, &salt)?;
+    #[test]
+    fn save_persists_entry_metadata() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            username: Some("me".into()),
+            url: Some("https://example.com".into()),
+            ..Entry::new("test")
+        };
+        tmp.vault.set_entry("mypass", entry).unwrap();
+        assert_ok!(tmp.vault.save());
 
-        let data = serde_json::to_vec(&self.data).map_err(PassmateError::Json)?;
-        let encrypted_data = encrypt(key, &data)?;
+        let vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert_eq!(vault.get_entry("mypass"), tmp.vault.get_entry("mypass"));
+        assert_eq!(
+            vault.get_entry("mypass").unwrap().username.as_deref(),
+            Some("me")
+        );
+    }
 
-        let mut contents = salt.to_vec();
-        contents.extend_from_slice(&encrypted_data);
+    #[test]
+    fn set_pushes_the_previous_value_to_the_history() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set("mypass", "test").unwrap();
+        tmp.vault.set("mypass", "newtest").unwrap();
 
-        std::fs::write(&self.path, &contents).map_err(PassmateError::IO)
+        let entry = tmp.vault.get_entry("mypass").unwrap();
+        assert_eq!(entry.secret, "newtest");
+        assert_eq!(entry.history, vec!["test".to_string()]);
     }
-}
 
-#[mutants::skip]
-fn make_key(pwd: &str, salt: &[u8]) -> Result<[u8; 32], PassmateError> {
-    let mut key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(pwd.as_bytes(), salt, &mut key)
-        .map_err(PassmateError::EncryptionKey)?;
-    Ok(key)
-}
+    #[test]
+    fn set_records_when_the_entry_was_created_and_updated() {
+        let mut tmp = TempVault::new();
+        let before = now();
+        tmp.vault.set("mypass", "test").unwrap();
 
-#[mutants::skip]
-fn generate_salt() -> [u8; 16] {
-    let mut salt = [0u8; 16];
-    rand::thread_rng().fill(&mut salt);
-    salt
-}
+        let entry = tmp.vault.get_entry("mypass").unwrap().clone();
+        assert!(entry.created_at.unwrap() >= before);
+        assert_eq!(entry.created_at, entry.updated_at);
 
-fn encrypt(key: [u8; 32], data: &[u8]) -> Result<Vec<u8>, PassmateError> {
-    let key = Key::<Aes256Gcm>::from_slice(&key);
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96-bits; unique per message
+        tmp.vault
+            .set_entry("mypass", Entry::new("newtest"))
+            .unwrap();
+        assert_eq!(
+            tmp.vault.get_entry("mypass").unwrap().created_at,
+            entry.created_at
+        );
+    }
 
-    let ciphertext = Aes256Gcm::new(key)
-        .encrypt(&nonce, data)
-        .map_err(PassmateError::Encrypt)?;
+    #[test]
+    fn rotate_replaces_the_value_preserving_metadata_and_history() {
+        let mut tmp = TempVault::new();
+        let entry = Entry {
+            username: Some("me".into()),
+            url: Some("https://example.com".into()),
+            history: vec!["oldest".into()],
+            ..Entry::new("test")
+        };
+        tmp.vault.set_entry("mypass", entry).unwrap();
 
-    let mut encrypted_data = nonce.to_vec();
-    encrypted_data.extend_from_slice(&ciphertext);
+        let password = tmp
+            .vault
+            .rotate("mypass", &PasswordPolicy::with_length(24))
+            .unwrap();
 
-    Ok(encrypted_data)
-}
+        let rotated = tmp.vault.get_entry("mypass").unwrap();
+        assert_ne!(rotated.secret, "test");
+        assert_eq!(rotated.secret, password);
+        assert_eq!(password.len(), 24);
+        assert_eq!(rotated.username.as_deref(), Some("me"));
+        assert_eq!(rotated.url.as_deref(), Some("https://example.com"));
+        assert_eq!(rotated.history, vec!["oldest".to_string(), "test".into()]);
+    }
 
-fn decrypt(key: [u8; 32], encrypted_data: &[u8]) -> Result<Vec<u8>, PassmateError> {
-    let key = Key::<Aes256Gcm>::from_slice(&key);
-    let (nonce, ciphertext) = encrypted_data.split_at(12);
-    Aes256Gcm::new(key)
-        .decrypt(Nonce::from_slice(nonce), ciphertext)
-        .map_err(PassmateError::Decrypt)
-}
+    #[test]
+    fn rotate_returns_an_error_if_the_entry_does_not_exist() {
+        let mut tmp = TempVault::new();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use claims::{assert_err, assert_none, assert_ok};
-    use std::{collections::HashMap, path::PathBuf};
-    use tempfile::TempDir;
+        assert!(matches!(
+            tmp.vault.rotate("mypass", &PasswordPolicy::default()),
+            Err(PassmateError::EntryNotFound(_))
+        ));
+        assert_none!(tmp.vault.get("mypass"));
+    }
 
     #[test]
-    fn open_returns_an_empty_vault_for_file_that_doesnt_exist() {
-        let vault = Vault::open(PathBuf::from("doesnotexist"), "testpwd").unwrap();
-        assert!(vault.data.is_empty());
+    fn save_creates_missing_parent_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested/dir/test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("mypass", "test").unwrap();
+
+        assert_ok!(vault.save());
+
+        let saved = Vault::open(&path, "testpwd").unwrap();
+        assert!(saved.data_eq(&vault));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path.parent().unwrap())
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
     }
 
     #[test]
-    fn open_opens_a_vault_with_existing_data() {
+    fn save_returns_an_error_for_a_missing_directory_if_creating_is_disabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested/test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set_create_dirs(false);
+
+        assert!(matches!(
+            vault.save(),
+            Err(PassmateError::MissingDirectory(dir)) if dir == temp_dir.path().join("nested")
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn move_to_saves_the_vault_at_the_new_path_and_removes_the_old_file() {
         let mut tmp = TempVault::new();
-        tmp.vault.set("mypass", "test");
+        tmp.vault.set("mypass", "test").unwrap();
         assert_ok!(tmp.vault.save());
+        let old_path = tmp.vault.path.clone();
+        let new_path = old_path.with_file_name("renamed.vault");
 
-        let vault = Vault::open(tmp.vault.path, "testpwd").unwrap();
-        assert_eq!(vault.data, tmp.vault.data);
+        assert_ok!(tmp.vault.move_to(&new_path));
+
+        assert!(!old_path.exists());
+        assert_eq!(tmp.vault.path, new_path);
+        let vault = Vault::open(&new_path, "testpwd").unwrap();
+        assert!(vault.data_eq(&tmp.vault));
     }
 
     #[test]
-    fn set_adds_a_new_secret_to_the_vault_with_the_given_name() {
+    fn move_to_moves_the_checksum_file_with_the_vault() {
         let mut tmp = TempVault::new();
+        tmp.vault.set_write_checksum(true);
+        tmp.vault.set("mypass", "test").unwrap();
+        assert_ok!(tmp.vault.save());
+        tmp.vault.set_write_checksum(false);
+        let old_path = tmp.vault.path.clone();
+        let new_path = old_path.with_file_name("renamed.vault");
 
-        tmp.vault.set("mypass", "test");
+        assert_ok!(tmp.vault.move_to(&new_path));
 
-        assert_eq!(tmp.vault.data.get("mypass").unwrap(), "test");
+        assert!(!checksum_path(&old_path).exists());
+        let checksum = std::fs::read_to_string(checksum_path(&new_path)).unwrap();
+        assert!(checksum.ends_with("  renamed.vault\n"));
+        assert!(!Vault::open(&new_path, "testpwd")
+            .unwrap()
+            .checksum_mismatch());
     }
 
     #[test]
-    fn entries_returns_the_names_of_the_vault_entries_in_alphabetical_order() {
+    fn move_to_does_not_overwrite_an_existing_vault() {
         let mut tmp = TempVault::new();
-        tmp.vault.set("pass1", "test");
-        tmp.vault.set("pass2", "test");
+        assert_ok!(tmp.vault.save());
+        let old_path = tmp.vault.path.clone();
+        let new_path = old_path.with_file_name("existing.vault");
+        std::fs::write(&new_path, "existing").unwrap();
 
-        let want: Vec<String> = vec!["pass1".into(), "pass2".into()];
-        let got = tmp.vault.entries();
-        assert_eq!(want, got);
+        assert!(matches!(
+            tmp.vault.move_to(&new_path),
+            Err(PassmateError::VaultExists(_))
+        ));
+
+        assert!(old_path.exists());
+        assert_eq!(tmp.vault.path, old_path);
+        assert_eq!(std::fs::read_to_string(new_path).unwrap(), "existing");
     }
 
     #[test]
-    fn get_retrieves_a_secret_from_the_vault_with_the_given_name() {
+    fn set_hint_rejects_a_hint_matching_the_passphrase() {
         let mut tmp = TempVault::new();
-        tmp.vault.set("mypass", "test");
 
-        assert_eq!(tmp.vault.get("mypass"), Some(&"test".to_string()));
+        assert!(matches!(
+            tmp.vault.set_hint(Some("testpwd".into())),
+            Err(PassmateError::InvalidHint(_))
+        ));
+        assert_none!(tmp.vault.hint());
     }
 
     #[test]
-    fn get_returns_none_if_a_secret_does_not_exist_by_the_given_name() {
-        let tmp = TempVault::new();
+    fn save_stores_the_hint_in_the_header() {
+        let mut tmp = TempVault::new();
+        tmp.vault.set_hint(Some("first pet".into())).unwrap();
+        assert_ok!(tmp.vault.save());
 
-        assert_none!(tmp.vault.get("mypass"));
+        let bytes = std::fs::read(&tmp.vault.path).unwrap();
+        assert_eq!(
+            format::inspect(&bytes).unwrap().hint.as_deref(),
+            Some("first pet")
+        );
+        let vault = Vault::open(&tmp.vault.path, "testpwd").unwrap();
+        assert_eq!(vault.hint(), Some("first pet"));
     }
 
     #[test]
-    fn set_updates_an_existing_secret_if_it_already_exists_by_the_given_name() {
+    fn open_returns_the_hint_with_an_invalid_passphrase_error() {
         let mut tmp = TempVault::new();
-        tmp.vault.set("mypass", "test");
-        tmp.vault.set("mypass", "newtest");
-        assert_eq!(tmp.vault.get("mypass").unwrap(), "newtest");
+        tmp.vault.set_hint(Some("first pet".into())).unwrap();
+        assert_ok!(tmp.vault.save());
+
+        let result = Vault::open(&tmp.vault.path, "wrongpwd");
+
+        assert!(matches!(
+            result,
+            Err(PassmateError::InvalidPassphrase { hint: Some(hint) }) if hint == "first pet"
+        ));
     }
 
     #[test]
-    fn remove_deletes_the_secret_with_the_given_name_from_the_vault() {
-        let mut tmp = TempVault::new();
-        tmp.vault.set("mypass", "test");
+    fn seal_and_unseal_round_trip_arbitrary_bytes() {
+        for plaintext in [&b""[..], b"secret", &[0, 159, 146, 150, 255]] {
+            let sealed = Vault::seal(plaintext, "testpwd").unwrap();
+
+            assert!(sealed.starts_with(format::MAGIC));
+            assert_eq!(Vault::unseal(&sealed, "testpwd").unwrap(), plaintext);
+        }
+    }
 
-        tmp.vault.remove("mypass");
+    #[test]
+    fn unseal_returns_an_error_for_the_wrong_passphrase() {
+        let sealed = Vault::seal(b"secret", "testpwd").unwrap();
 
-        assert_none!(tmp.vault.get("mypass"));
+        assert!(matches!(
+            Vault::unseal(&sealed, "wrongpwd"),
+            Err(PassmateError::InvalidPassphrase { hint: None })
+        ));
     }
 
     #[test]
-    fn save_persists_the_vaults_data_to_disk_as_json() {
-        let mut temp_vault = TempVault::new();
-        temp_vault.vault.set("mypass", "test");
-        assert_ok!(temp_vault.vault.save());
+    fn errors_wrapping_another_error_expose_it_as_the_source() {
+        use std::error::Error as _;
 
-        let got = Vault::open(&temp_vault.vault.path, &temp_vault.vault.passphrase).unwrap();
-        let want = HashMap::from([("mypass".into(), "test".into())]);
-        assert_eq!(got.data, want);
+        let io = PassmateError::IO(std::io::Error::other("disk on fire"));
+        let source = io.source().unwrap();
+        assert_eq!(source.to_string(), "disk on fire");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+        // The source is reported after the message, not repeated in it.
+        assert_eq!(io.to_string(), "Error writing or reading vault");
+
+        let json = Vault::open_bytes(&Vault::seal(b"not json", "testpwd").unwrap(), "testpwd")
+            .err()
+            .unwrap();
+        assert!(matches!(json, PassmateError::Json(_)));
+        assert!(json
+            .source()
+            .unwrap()
+            .downcast_ref::<serde_json::Error>()
+            .is_some());
+
+        let key = make_key("testpwd", &[0; 4], &KdfParams::default()).unwrap_err();
+        assert!(key.source().is_some());
+        assert!(PassmateError::InvalidName("name".into()).source().is_none());
     }
 
     #[test]
     fn data_can_be_encrypted_and_decrypted() {
-        let salt = generate_salt();
-        let key = make_key("testpass", &salt).expect("failed to make key");
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpass", &salt, &KdfParams::default()).expect("failed to make key");
         let original_plaintext = "this is a test";
-        let ciphertext =
-            encrypt(key, original_plaintext.as_bytes()).expect("failed to encrypt data");
-        let decrypted_plaintext = decrypt(key, &ciphertext).expect("failed to decrypted data");
+        let ciphertext = encrypt(key, original_plaintext.as_bytes(), &mut OsRng)
+            .expect("failed to encrypt data");
+        let decrypted_plaintext =
+            decrypt(format::Cipher::Aes256Gcm, key, &ciphertext).expect("failed to decrypted data");
         assert_eq!(
             original_plaintext,
             String::from_utf8_lossy(&decrypted_plaintext)
@@ -254,22 +4937,148 @@ mod tests {
 
     #[test]
     fn encrypting_a_value_should_produce_different_results_each_time() {
-        let salt = generate_salt();
-        let key = make_key("testpass", &salt).expect("failed to make key");
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpass", &salt, &KdfParams::default()).expect("failed to make key");
         let plaintext = "this is a test";
-        let ciphertext1 = encrypt(key, plaintext.as_bytes()).expect("failed to encrypt data");
-        let ciphertext2 = encrypt(key, plaintext.as_bytes()).expect("failed to encrypt data");
+        let ciphertext1 =
+            encrypt(key, plaintext.as_bytes(), &mut OsRng).expect("failed to encrypt data");
+        let ciphertext2 =
+            encrypt(key, plaintext.as_bytes(), &mut OsRng).expect("failed to encrypt data");
         assert_ne!(ciphertext1, ciphertext2);
     }
 
     #[test]
     fn decrypting_a_tampered_with_ciphertext_should_return_an_error() {
-        let salt = generate_salt();
-        let key = make_key("testpass", &salt).expect("failed to make key");
+        let salt = generate_salt(&mut OsRng);
+        let key = make_key("testpass", &salt, &KdfParams::default()).expect("failed to make key");
         let plaintext = "this is a test";
-        let mut ciphertext = encrypt(key, plaintext.as_bytes()).expect("failed to encrypt data");
+        let mut ciphertext =
+            encrypt(key, plaintext.as_bytes(), &mut OsRng).expect("failed to encrypt data");
         ciphertext[0] = 0;
-        assert_err!(decrypt(key, &ciphertext));
+        assert_err!(decrypt(format::Cipher::Aes256Gcm, key, &ciphertext));
+    }
+
+    #[test]
+    fn decrypt_splits_off_the_nonce_length_of_the_cipher() {
+        let key = [3; 32];
+        let gcm = encrypt(key, b"secret", &mut OsRng).unwrap();
+        let nonce = XNonce::from([5; format::XNONCE_LEN]);
+        let mut xchacha = nonce.to_vec();
+        xchacha.extend(
+            XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&key))
+                .encrypt(&nonce, b"secret".as_slice())
+                .unwrap(),
+        );
+
+        assert_eq!(
+            decrypt(format::Cipher::Aes256Gcm, key, &gcm).unwrap(),
+            b"secret"
+        );
+        assert_eq!(
+            decrypt(format::Cipher::XChaCha20Poly1305, key, &xchacha).unwrap(),
+            b"secret"
+        );
+        assert_err!(decrypt(format::Cipher::XChaCha20Poly1305, key, &gcm));
+        assert_err!(decrypt(format::Cipher::XChaCha20Poly1305, key, &[0; 10]));
+    }
+
+    #[test]
+    fn open_bytes_reads_vaults_with_other_salt_and_nonce_lengths() {
+        let data = HashMap::from([("github".to_string(), Entry::new("secret"))]);
+        let header = format::Header {
+            version: format::VERSION,
+            kdf: KdfParams::default(),
+            hint: None,
+            structured_entries: true,
+            padding: 0,
+            salt: vec![6; 32],
+            cipher: format::Cipher::XChaCha20Poly1305,
+        };
+        let key = make_key("testpwd", &header.salt, &header.kdf).unwrap();
+        let nonce = XNonce::from([5; format::XNONCE_LEN]);
+        let mut payload = nonce.to_vec();
+        payload.extend(
+            XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&key))
+                .encrypt(
+                    &nonce,
+                    serialize_data(&data, true, &[3; 32]).unwrap().as_slice(),
+                )
+                .unwrap(),
+        );
+
+        let opened = Vault::open_bytes(&format::encode(&header, &payload), "testpwd").unwrap();
+
+        assert_eq!(opened.get("github").unwrap(), "secret");
+        assert_eq!(*opened.fingerprint_key, [3; 32]);
+        assert_eq!(opened.derived_key(), Some((vec![6; 32], key)));
+    }
+
+    #[test]
+    fn encrypt_uses_a_distinct_nonce_every_time() {
+        let key = [7; 32];
+
+        let nonces: HashSet<Vec<u8>> = (0..1000)
+            .map(|_| encrypt(key, b"secret", &mut OsRng).unwrap()[..format::NONCE_LEN].to_vec())
+            .collect();
+
+        assert_eq!(nonces.len(), 1000);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "nonce reused with the same key")]
+    fn encrypt_panics_in_debug_builds_when_a_nonce_is_reused() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+        let key = [9; 32];
+
+        encrypt(key, b"same", &mut ChaCha20Rng::seed_from_u64(1)).unwrap();
+        encrypt(key, b"same", &mut ChaCha20Rng::seed_from_u64(1)).unwrap();
+    }
+
+    #[test]
+    fn fingerprint_matches_only_identical_secrets_in_the_same_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        vault.set("gitlab", "secret").unwrap();
+        vault.set("email", "other").unwrap();
+        let github = vault.fingerprint("github").unwrap();
+
+        assert_eq!(github.len(), 64);
+        assert_eq!(vault.fingerprint("gitlab").unwrap(), github);
+        assert_ne!(vault.fingerprint("email").unwrap(), github);
+        assert_eq!(vault.fingerprint("missing"), None);
+
+        let mut other = Vault::open(temp_dir.path().join("other.vault"), "testpwd").unwrap();
+        other.set("github", "secret").unwrap();
+        assert_ne!(other.fingerprint("github").unwrap(), github);
+    }
+
+    #[test]
+    fn fingerprint_survives_saving_and_reopening_the_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.vault");
+        let mut vault = Vault::open(&path, "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        let github = vault.fingerprint("github").unwrap();
+        vault.save().unwrap();
+
+        let reopened = Vault::open(&path, "testpwd").unwrap();
+        assert_eq!(reopened.fingerprint("github").unwrap(), github);
+
+        // Every save draws a new salt, which mustn't change the fingerprint.
+        reopened.save().unwrap();
+        let copy = temp_dir.path().join("copy.vault");
+        reopened.save_with_path(&copy).unwrap();
+        assert_eq!(
+            Vault::open(&path, "testpwd").unwrap().fingerprint("github"),
+            Some(github.clone())
+        );
+        assert_eq!(
+            Vault::open(&copy, "testpwd").unwrap().fingerprint("github"),
+            Some(github)
+        );
     }
 
     struct TempVault {
@@ -0,0 +1,104 @@
+//! Tab-separated `name<TAB>value` lines, one entry per line.
+//!
+//! Tabs, newlines, carriage returns and backslashes in names and values
+//! are escaped as `\t`, `\n`, `\r` and `\\`, so every entry stays on one
+//! line and reads back unchanged.
+use crate::PassmateError;
+use serde_json::{Map, Value};
+
+/// Writes the mapping of names to values as lines, in the mapping's order.
+pub(crate) fn write(map: &Map<String, Value>) -> Result<String, PassmateError> {
+    let mut output = String::new();
+    for (name, value) in map {
+        let Value::String(value) = value else {
+            return Err(PassmateError::InvalidFormat(format!(
+                "{name} isn't a plain value, which is all lines can hold"
+            )));
+        };
+        escape(name, &mut output);
+        output.push('\t');
+        escape(value, &mut output);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Parses lines into a mapping of names to values, skipping blank lines.
+pub(crate) fn parse(text: &str) -> Result<Map<String, Value>, PassmateError> {
+    let mut map = Map::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let invalid =
+            |reason: &str| PassmateError::InvalidFormat(format!("line {}: {reason}", number + 1));
+        let (name, value) = line
+            .split_once('\t')
+            .ok_or_else(|| invalid("expected a tab between the name and value"))?;
+        let name = unescape(name).ok_or_else(|| invalid("invalid escape in the name"))?;
+        let value = unescape(value).ok_or_else(|| invalid("invalid escape in the value"))?;
+        map.insert(name, Value::String(value));
+    }
+    Ok(map)
+}
+
+fn escape(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        output.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_escapes_values_so_parse_reads_them_back_unchanged() {
+        let mut map = Map::new();
+        map.insert("plain".into(), "secret".into());
+        map.insert("tab\tname".into(), "a\tb\nc\r\nd\\e\\t".into());
+
+        let written = write(&map).unwrap();
+
+        assert_eq!(
+            written,
+            "plain\tsecret\ntab\\tname\ta\\tb\\nc\\r\\nd\\\\e\\\\t\n"
+        );
+        assert_eq!(parse(&written).unwrap(), map);
+    }
+
+    #[test]
+    fn parse_rejects_lines_without_a_tab_or_with_unknown_escapes() {
+        assert!(matches!(
+            parse("github\tsecret\n\nemail"),
+            Err(PassmateError::InvalidFormat(reason)) if reason.starts_with("line 3:")
+        ));
+        assert!(parse("github\tsec\\xret").is_err());
+        assert!(parse("github\tsecret\\").is_err());
+    }
+}
@@ -1,69 +1,1355 @@
 use clap::{Parser, Subcommand};
-use passmate::{PassmateError, Vault};
-use std::path::PathBuf;
+use passmate::{
+    config::{Config, CONFIG_FILE},
+    doctor, format, generate,
+    passphrase::PassphraseSource,
+    paths::{VaultDirs, PLAINTEXT_EXTENSION, VAULT_EXTENSION},
+    style::{self, paint},
+    DataFormat, Entry, ImportOptions, MergeStrategy, PassmateError, PasswordPolicy, UpgradeReason,
+    Vault, Zeroizing, MASKED_SECRET,
+};
+use std::{
+    io::{ErrorKind, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// The `--vault` value that reads the vault from stdin and writes it to stdout.
+const STDIO_VAULT: &str = "-";
+/// How many times saving is tried when the file is temporarily unavailable.
+const SAVE_ATTEMPTS: u32 = 3;
+/// The wait before retrying a failed save, doubled after each attempt.
+const SAVE_BACKOFF: Duration = Duration::from_millis(100);
 
 #[derive(Parser)]
 #[command(version, about)]
+#[allow(clippy::struct_excessive_bools)]
 struct Args {
     #[command(subcommand)]
     command: Commands,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Name of the vault to use, or - to read it from stdin and write it to stdout")]
+    #[arg(
+        long_help = "Name of the vault to use, or - to read it from stdin and write it to stdout. \
+        Defaults to $PASSMATE_VAULT, then the vault set in config.toml, then \"default\"."
+    )]
+    vault: Option<String>,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Directory to store the vault in instead of the XDG data directory")]
+    config_dir: Option<PathBuf>,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Fail instead of creating the vault's directory if it's missing")]
+    no_create_dirs: bool,
+
+    #[arg(short, long, global = true)]
+    #[arg(help = "Don't print progress messages")]
+    quiet: bool,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Overwrite the old vault file before removing it when saving (best-effort)")]
+    secure_delete: bool,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Also write the SHA-256 digest of the vault file to <vault>.sha256 when saving")]
+    checksum: bool,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Store the vault UNENCRYPTED, without a passphrase; never use it for secrets")]
+    plaintext: bool,
+
+    #[arg(long, global = true, value_name = "TEXT")]
+    #[arg(help = "Name the vault in the passphrase prompt, as in \"Enter TEXT password:\"")]
+    prompt_label: Option<String>,
+
+    #[arg(long, global = true, value_name = "FD")]
+    #[arg(help = "Read the passphrase from the first line of an open file descriptor (Unix only)")]
+    passphrase_fd: Option<i32>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with = "passphrase_fd"
+    )]
+    #[arg(
+        help = "Read the passphrase from a systemd credential in $CREDENTIALS_DIRECTORY (Unix only)"
+    )]
+    credential: Option<String>,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Cache the passphrase in the OS keyring, prompting only when it isn't there yet")]
+    use_keyring: bool,
+
+    #[arg(long, global = true, conflicts_with = "mask_stdout")]
+    #[arg(help = "Print secrets instead of a placeholder")]
+    reveal: bool,
+
+    #[arg(long, global = true)]
+    #[arg(help = "Print a placeholder instead of secrets, even if the config says otherwise")]
+    mask_stdout: bool,
+
+    #[arg(long, global = true)]
+    #[arg(
+        help = "Never color the output; colors are also off when it isn't a terminal or NO_COLOR is set"
+    )]
+    no_color: bool,
+
+    /// Settings resolved from the flags, environment and config file.
+    #[arg(skip)]
+    config: Config,
+}
+
+impl Args {
+    /// Returns where to read the passphrase from, or `None` to prompt for it.
+    fn passphrase_source(&self) -> Option<PassphraseSource> {
+        match (self.passphrase_fd, &self.credential) {
+            (Some(fd), _) => Some(PassphraseSource::Fd(fd)),
+            (None, Some(name)) => Some(PassphraseSource::Credential(name.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Metadata to store with an entry, leaving fields that aren't given as they are.
+#[derive(clap::Args)]
+struct Metadata {
+    #[arg(long, help = "Username stored with the entry")]
+    username: Option<String>,
+    #[arg(long, help = "URL stored with the entry")]
+    url: Option<String>,
+    #[arg(
+        long = "tag",
+        value_name = "TAG",
+        help = "Tag the entry, may be repeated"
+    )]
+    tags: Vec<String>,
+}
+
+impl Metadata {
+    fn is_empty(&self) -> bool {
+        self.username.is_none() && self.url.is_none() && self.tags.is_empty()
+    }
+
+    /// Merges the metadata into the entry: the username and URL replace
+    /// the entry's, while tags are added to the ones it already has.
+    fn apply(&self, entry: &mut Entry) {
+        if let Some(username) = &self.username {
+            entry.username = Some(username.clone());
+        }
+        if let Some(url) = &self.url {
+            entry.url = Some(url.clone());
+        }
+        for tag in &self.tags {
+            if !entry.tags.contains(tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
+/// How imported entries interact with existing ones.
+#[derive(clap::Args)]
+struct ImportFlags {
+    #[arg(
+        long,
+        conflicts_with = "strategy",
+        help = "Replace existing entries instead of skipping them, like --strategy keep-theirs"
+    )]
+    overwrite: bool,
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How to resolve existing entries: keep-mine (the default), keep-theirs, or keep-newest by update time"
+    )]
+    strategy: Option<MergeStrategy>,
+    #[arg(
+        long,
+        help = "Count entries whose value is already identical as unchanged"
+    )]
+    dedupe: bool,
+    #[arg(
+        long,
+        help = "Fail on entry fields passmate doesn't know instead of dropping them"
+    )]
+    strict_json: bool,
+}
+
+/// What `import` reads and how to make sense of it.
+#[derive(clap::Args)]
+struct ImportSource {
+    #[arg(help = "File to read, or - for stdin")]
+    file: PathBuf,
+    #[arg(
+        long,
+        help = "json, yaml or lines; detected from the file extension if omitted, else json"
+    )]
+    format: Option<DataFormat>,
+    #[arg(
+        long,
+        conflicts_with = "format",
+        help = "Read a CSV file exported by KeePass or KeePassXC"
+    )]
+    from_keepass: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Decrypt a file encrypted with age using the identity in FILE"
+    )]
+    identity: Option<PathBuf>,
+}
+
+impl ImportSource {
+    /// Reads the file, decrypting it if an identity was given.
+    fn read(&self, location: &Location) -> anyhow::Result<Zeroizing<String>> {
+        let input = Zeroizing::new(read_input(&self.file, location)?);
+        match &self.identity {
+            Some(identity) => {
+                decrypt_with(&Zeroizing::new(std::fs::read_to_string(identity)?), &input)
+            }
+            None => Ok(input),
+        }
+    }
+}
+
+#[cfg(feature = "age")]
+fn decrypt_with(identity: &str, input: &str) -> anyhow::Result<Zeroizing<String>> {
+    let data = passmate::share::decrypt_with(identity, input.as_bytes())?;
+    Ok(Zeroizing::new(String::from_utf8(data.to_vec())?))
+}
+
+#[cfg(feature = "age")]
+fn encrypt_for(recipient: &str, text: &str) -> anyhow::Result<String> {
+    Ok(passmate::share::encrypt_for(recipient, text.as_bytes())?)
+}
+
+#[cfg(not(feature = "age"))]
+fn decrypt_with(_identity: &str, _input: &str) -> anyhow::Result<Zeroizing<String>> {
+    anyhow::bail!("Can't decrypt; passmate was built without the age feature")
+}
+
+#[cfg(not(feature = "age"))]
+fn encrypt_for(_recipient: &str, _text: &str) -> anyhow::Result<String> {
+    anyhow::bail!("Can't encrypt; passmate was built without the age feature")
+}
+
+#[cfg(feature = "qr")]
+fn render_qr(secret: &str) -> anyhow::Result<String> {
+    Ok(passmate::qr::render(secret)?)
+}
+
+#[cfg(not(feature = "qr"))]
+fn render_qr(_secret: &str) -> anyhow::Result<String> {
+    anyhow::bail!("Can't show a QR code; passmate was built without the qr feature")
+}
+
+impl ImportFlags {
+    fn options(&self) -> ImportOptions {
+        let strategy = match self.strategy {
+            Some(strategy) => strategy,
+            None if self.overwrite => MergeStrategy::TakeTheirs,
+            None => MergeStrategy::KeepOurs,
+        };
+        ImportOptions {
+            strategy,
+            dedupe: self.dedupe,
+            strict: self.strict_json,
+        }
+    }
+}
+
+/// How `gen` generates the password.
+#[derive(clap::Args)]
+struct GenFlags {
+    #[arg(long, default_value_t = PasswordPolicy::default().length)]
+    length: usize,
+    #[arg(
+        long,
+        default_value = "",
+        help = "Characters the password must not contain"
+    )]
+    exclude: String,
+    #[arg(
+        long,
+        conflicts_with = "exclude",
+        help = "Alternate consonants and vowels, for a password that's easier to say and type"
+    )]
+    pronounceable: bool,
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["length", "pronounceable", "no_store"],
+        help = "Match the length and character classes of an existing entry's value"
+    )]
+    like: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "no_store",
+        help = "Show the password and ask to accept or regenerate it before storing it (terminal only)"
+    )]
+    confirm: bool,
+}
+
+impl GenFlags {
+    /// Generates a password, printing the entropy of a pronounceable one
+    /// to stderr unless `quiet`, as it's lower than its length suggests.
+    /// The vault is only needed to generate one like an existing entry's.
+    fn generate(&self, vault: Option<&Vault>, quiet: bool) -> Result<String, PassmateError> {
+        if let Some(name) = &self.like {
+            let secret = vault
+                .and_then(|vault| vault.get(name))
+                .ok_or_else(|| PassmateError::EntryNotFound(name.clone()))?;
+            let policy = PasswordPolicy {
+                exclude: self.exclude.clone(),
+                ..PasswordPolicy::matching(secret)
+            };
+            return policy.generate(&mut rand::rngs::OsRng);
+        }
+        if !self.pronounceable {
+            return policy(self.length, &self.exclude).generate(&mut rand::rngs::OsRng);
+        }
+        let password = generate::generate_pronounceable(self.length, &mut rand::rngs::OsRng)?;
+        if !quiet {
+            eprintln!(
+                "About {:.0} bits of entropy",
+                generate::pronounceable_entropy(self.length)
+            );
+        }
+        Ok(password)
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     #[command(visible_alias = "ls")]
     #[command(about = "List the entries stored in the vault")]
-    List,
+    List {
+        #[arg(long, value_name = "WHEN")]
+        #[arg(
+            help = "Only list entries changed within a duration like 7d, or since a date like 2024-05-01"
+        )]
+        since: Option<String>,
+        #[arg(long, help = "Group entries under their top-level prefix, like aws/")]
+        group: bool,
+    },
 
     #[command(about = "Get the value of an entry by name")]
-    Get { name: String },
+    Get {
+        #[arg(help = "Name of the entry, chosen interactively on a terminal if omitted")]
+        name: Option<String>,
+        #[arg(long, help = "Fail instead of prompting when no name is given")]
+        no_interactive: bool,
+        #[arg(long, help = "Print all fields of the entry")]
+        all: bool,
+        #[arg(
+            long,
+            conflicts_with = "all",
+            help = "Show the secret as a QR code, visible to anyone who can see the screen"
+        )]
+        qr: bool,
+    },
+
+    #[command(about = "Write the raw value of an entry to stdout, without a trailing newline")]
+    Cat { name: String },
 
     #[command(about = "Add or update an entry")]
-    Set { name: String, value: String },
+    Set {
+        name: String,
+        value: String,
+        #[arg(long, help = "Allow values larger than 1 MiB")]
+        allow_large: bool,
+        #[arg(long, help = "Allow storing an empty value")]
+        allow_empty_value: bool,
+        #[arg(long, help = "Allow control characters such as newlines in the value")]
+        allow_control_chars: bool,
+        #[command(flatten)]
+        metadata: Metadata,
+    },
 
     #[command(about = "Remove an entry")]
-    Remove { name: String },
+    Remove {
+        #[arg(required_unless_present = "entries_from")]
+        name: Option<String>,
+        #[arg(long, value_name = "FILE", conflicts_with = "name")]
+        #[arg(help = "Remove every entry named in the file, one per line, or - for stdin")]
+        entries_from: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Mark an entry as updated now without changing it, such as after reviewing it"
+    )]
+    Touch { name: String },
+
+    #[command(about = "Replace the value of an entry with a generated password")]
+    Rotate {
+        name: String,
+        #[arg(long, default_value_t = PasswordPolicy::default().length)]
+        length: usize,
+    },
+
+    #[command(about = "Add entries from a JSON or YAML mapping of names to values or to entries")]
+    Import {
+        #[command(flatten)]
+        source: ImportSource,
+        #[command(flatten)]
+        flags: ImportFlags,
+    },
+
+    #[command(
+        about = "Print every entry with its metadata as a JSON or YAML mapping keyed by name"
+    )]
+    Export {
+        #[arg(
+            long,
+            default_value = "json",
+            help = "json, yaml, or lines of name<TAB>secret with only the secrets"
+        )]
+        format: DataFormat,
+        #[arg(
+            long,
+            help = "Print the encrypted vault bytes as they are, without decrypting"
+        )]
+        raw: bool,
+        #[arg(
+            long,
+            conflicts_with = "raw",
+            help = "Print only the secrets, as the object of names to values read by import"
+        )]
+        secrets_only: bool,
+        #[arg(
+            long,
+            value_name = "RECIPIENT",
+            conflicts_with = "raw",
+            help = "Encrypt the export with age for a public key like age1..., to import with --identity"
+        )]
+        encrypted_for: Option<String>,
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["raw", "format", "secrets_only", "encrypted_for"],
+            help = "Write the entries of each tag to an encrypted vault of their own in DIR, like DIR/work.vault"
+        )]
+        split_by_tag: Option<PathBuf>,
+    },
+
+    #[command(about = "Show statistics about the vault's secrets without revealing them")]
+    Stats,
+
+    #[command(
+        about = "Rewrite the vault in the current format, storing every entry in structured form"
+    )]
+    UpgradeEntries,
+
+    #[command(about = "Drop old versions of secrets from every entry's history")]
+    Prune {
+        #[arg(long, value_name = "N")]
+        #[arg(help = "Number of previous versions to keep per entry; 0 clears all history")]
+        keep: usize,
+    },
+
+    #[command(about = "Check the vault for problems such as names differing only by case")]
+    Audit {
+        #[arg(
+            long,
+            help = "Also check every secret against the default password policy"
+        )]
+        check_policy: bool,
+        #[arg(
+            long,
+            help = "Remove the entries with an empty value instead of reporting them"
+        )]
+        remove_empty: bool,
+    },
+
+    #[command(about = "Generate a password and store it as an entry")]
+    Gen {
+        #[arg(required_unless_present = "no_store", conflicts_with = "no_store")]
+        name: Option<String>,
+        #[arg(
+            long,
+            help = "Only print the password, unmasked, without opening the vault or storing it"
+        )]
+        no_store: bool,
+        #[command(flatten)]
+        flags: GenFlags,
+    },
+
+    #[command(about = "Set, get, rename, or remove custom fields of an entry")]
+    Field {
+        #[command(subcommand)]
+        command: FieldCommand,
+    },
+
+    #[command(about = "Show, set, or clear the vault's passphrase hint")]
+    Hint {
+        #[arg(help = "The new hint, which is stored unencrypted")]
+        text: Option<String>,
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+
+    #[command(about = "List the names of the available vaults")]
+    Vaults,
+
+    #[command(about = "Print the path of the vault that would be opened")]
+    Which,
+
+    #[command(about = "Check the setup for common problems without changing anything")]
+    Doctor,
+
+    #[command(about = "Rename a vault file")]
+    RenameVault { old: String, new: String },
+
+    #[command(hide = true)]
+    #[command(about = "Print the header fields of the vault file without decrypting it")]
+    DebugFormat,
+}
+
+#[derive(Subcommand)]
+enum FieldCommand {
+    #[command(about = "Add or update a custom field")]
+    Set {
+        entry: String,
+        field: String,
+        value: String,
+    },
+
+    #[command(about = "Print the value of a custom field")]
+    Get { entry: String, field: String },
+
+    #[command(about = "Rename a custom field, keeping its value")]
+    Rename {
+        entry: String,
+        old: String,
+        new: String,
+    },
+
+    #[command(about = "Remove a custom field")]
+    Remove { entry: String, field: String },
 }
 
 #[cfg(feature = "integration-tests")]
-fn open_vault(path: PathBuf) -> Result<Vault, PassmateError> {
-    Vault::open(path, "testpwd")
+#[allow(clippy::unnecessary_wraps)]
+fn read_passphrase(_prompt: &str) -> Result<String, PassmateError> {
+    Ok("testpwd".into())
 }
 
 #[mutants::skip]
 #[cfg(not(feature = "integration-tests"))]
-fn open_vault(path: PathBuf) -> Result<Vault, PassmateError> {
-    let passphrase = rpassword::prompt_password("Enter password: ").map_err(PassmateError::IO)?;
+fn read_passphrase(prompt: &str) -> Result<String, PassmateError> {
+    rpassword::prompt_password(prompt).map_err(PassmateError::IO)
+}
+
+/// Where the vault is read from and written to.
+enum Location {
+    File(PathBuf),
+    Stdio,
+}
+
+fn open_vault(location: &Location, name: &str, args: &Args) -> Result<Vault, PassmateError> {
+    let bytes = match location {
+        Location::File(path) if !path.exists() => None,
+        _ => Some(read_vault_bytes(location)?),
+    };
+    if !args.plaintext && bytes.as_deref().is_some_and(format::is_plaintext) {
+        // Refused before prompting; opening it would drop the encryption on save.
+        return Err(PassmateError::InvalidFormat(
+            "the vault is not encrypted; only --plaintext opens plaintext vaults".into(),
+        ));
+    }
+    if args.plaintext {
+        style::eprintln(paint(
+            style::WARNING,
+            "WARNING: this vault is NOT encrypted; anyone who can read it can read every entry",
+        ));
+        return match location {
+            Location::File(path) => Vault::open_plaintext(path),
+            Location::Stdio => Vault::open_plaintext_bytes(&bytes.unwrap_or_default()),
+        }
+        .map(warn_on_checksum_mismatch);
+    }
+    let prompt = || match args.passphrase_source() {
+        Some(source) => source.read(),
+        None => read_passphrase(&args.config.password_prompt()).map(Zeroizing::new),
+    };
+    let quiet = args.quiet;
+    let open = |passphrase: &str| match location {
+        Location::File(path) if bytes.is_none() => Vault::open(path, passphrase),
+        Location::File(path) => {
+            with_progress("Deriving key...", quiet, || Vault::open(path, passphrase))
+        }
+        Location::Stdio => with_progress("Deriving key...", quiet, || {
+            Vault::open_bytes(bytes.as_deref().unwrap_or_default(), passphrase)
+        }),
+    };
+    match location {
+        Location::File(_) if args.use_keyring => open_with_keyring(name, prompt, open),
+        Location::Stdio if args.use_keyring => Err(PassmateError::Keyring(
+            "a vault read from stdin has no name to cache its passphrase under".into(),
+        )),
+        _ => open(&prompt()?),
+    }
+    .map(warn_on_checksum_mismatch)
+}
+
+fn warn_on_checksum_mismatch(vault: Vault) -> Vault {
+    if vault.checksum_mismatch() {
+        style::eprintln(paint(
+            style::WARNING,
+            "WARNING: the vault file doesn't match its .sha256 checksum; it was modified outside passmate",
+        ));
+    }
+    vault
+}
+
+#[cfg(feature = "keyring")]
+#[mutants::skip]
+fn open_with_keyring(
+    name: &str,
+    prompt: impl FnOnce() -> Result<Zeroizing<String>, PassmateError>,
+    open: impl FnMut(&str) -> Result<Vault, PassmateError>,
+) -> Result<Vault, PassmateError> {
+    passmate::passphrase::open_with_store(&passmate::passphrase::Keyring, name, prompt, open)
+}
+
+#[cfg(not(feature = "keyring"))]
+fn open_with_keyring(
+    _name: &str,
+    _prompt: impl FnOnce() -> Result<Zeroizing<String>, PassmateError>,
+    _open: impl FnMut(&str) -> Result<Vault, PassmateError>,
+) -> Result<Vault, PassmateError> {
+    Err(PassmateError::Keyring(
+        "passmate was built without the keyring feature".into(),
+    ))
+}
+
+fn read_vault_bytes(location: &Location) -> Result<Vec<u8>, PassmateError> {
+    match location {
+        Location::File(path) if path.is_dir() => Err(PassmateError::InvalidPath(path.clone())),
+        Location::File(path) => std::fs::read(path).map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => PassmateError::PermissionDenied(path.clone()),
+            _ => PassmateError::IO(e),
+        }),
+        Location::Stdio => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(PassmateError::IO)?;
+            Ok(bytes)
+        }
+    }
+}
+
+fn save_vault(vault: &Vault, location: &Location) -> Result<(), PassmateError> {
+    match location {
+        Location::File(_) => vault.save_with_retry(SAVE_ATTEMPTS, SAVE_BACKOFF),
+        Location::Stdio => std::io::stdout()
+            .write_all(&vault.save_bytes()?)
+            .map_err(PassmateError::IO),
+    }
+}
+
+/// Runs `f` after printing the message, animating a spinner
+/// on a separate thread while it runs if stderr is a terminal.
+fn with_progress<T, F>(message: &str, quiet: bool, f: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    if quiet {
+        return f();
+    }
+    if !std::io::stderr().is_terminal() {
+        eprintln!("{message}");
+        return f();
+    }
+    thread::scope(|scope| {
+        let handle = scope.spawn(f);
+        for frame in ['|', '/', '-', '\\'].iter().cycle() {
+            if handle.is_finished() {
+                break;
+            }
+            eprint!("\r{message} {frame}");
+            thread::sleep(Duration::from_millis(100));
+        }
+        eprintln!("\r{message} done");
+        handle.join().expect("task panicked")
+    })
+}
 
-    Vault::open(path, &passphrase)
+fn load_config(args: &Args) -> anyhow::Result<Config> {
+    let file = Config::load(vault_dirs(args)?.legacy_dir.join(CONFIG_FILE))?;
+    Ok(flag_config(args).or(Config::from_env()).or(file))
 }
 
-fn main() -> anyhow::Result<()> {
+fn flag_config(args: &Args) -> Config {
+    Config {
+        vault: args.vault.clone(),
+        prompt_label: args.prompt_label.clone(),
+        mask_secrets: match (args.reveal, args.mask_stdout) {
+            (true, _) => Some(false),
+            (_, true) => Some(true),
+            _ => None,
+        },
+        ..Config::default()
+    }
+}
+
+fn vault_dirs(args: &Args) -> anyhow::Result<VaultDirs> {
+    if let Some(dir) = &args.config_dir {
+        return Ok(VaultDirs {
+            data_dir: dir.clone(),
+            legacy_dir: dir.clone(),
+        });
+    }
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("passmate")?;
+    Ok(VaultDirs {
+        data_dir: xdg_dirs.get_data_home(),
+        legacy_dir: xdg_dirs.get_config_home(),
+    })
+}
+
+fn vault_location(args: &Args, name: &str) -> anyhow::Result<Location> {
+    if name == STDIO_VAULT {
+        if args.passphrase_fd == Some(0) {
+            anyhow::bail!("Can't read the passphrase from stdin when the vault is read from stdin");
+        }
+        return Ok(Location::Stdio);
+    }
+    let dirs = vault_dirs(args)?;
+    let file_name = vault_file_name(args, name)?;
+    match dirs.migrate_legacy_vault(&file_name) {
+        Ok(Some(path)) => eprintln!("Moved vault to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!(
+            "Failed to move vault to {}: {:#}",
+            dirs.data_dir.display(),
+            anyhow::Error::from(e)
+        ),
+    }
+    Ok(Location::File(resolve_vault_path(args, name)?))
+}
+
+/// Returns the absolute path of the named vault file from the flags,
+/// environment and config, without creating or moving anything.
+fn resolve_vault_path(args: &Args, name: &str) -> anyhow::Result<PathBuf> {
+    let path = vault_dirs(args)?.vault_path(&vault_file_name(args, name)?);
+    Ok(std::path::absolute(path)?)
+}
+
+fn vault_file_name(args: &Args, name: &str) -> Result<String, PassmateError> {
+    let extension = if args.plaintext {
+        PLAINTEXT_EXTENSION
+    } else {
+        VAULT_EXTENSION
+    };
+    passmate::paths::vault_file_name(name, extension)
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    let dirs = xdg::BaseDirectories::with_prefix("passmate")?;
-    let path = dirs.place_config_file("default.vault")?;
-    let mut vault = open_vault(path)?;
-    match args.command {
-        Commands::List => {
-            for entry in vault.entries() {
-                println!("{entry}");
+    if args.no_color {
+        style::disable();
+    }
+    match try_main(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            style::eprintln(format_args!("{} {e:?}", paint(style::ERROR, "Error:")));
+            if let Some(PassmateError::PermissionDenied(path)) = e.downcast_ref() {
+                eprintln!(
+                    "Check the file's owner, or run: chmod 600 {}",
+                    path.display()
+                );
             }
+            ExitCode::FAILURE
         }
-        Commands::Get { name } => {
-            let Some(value) = vault.get(&name) else {
+    }
+}
+
+fn try_main(mut args: Args) -> anyhow::Result<()> {
+    args.config = match load_config(&args) {
+        // The doctor reports the invalid config instead of failing on it.
+        Err(_) if matches!(args.command, Commands::Doctor) => flag_config(&args),
+        config => config?,
+    };
+    match &args.command {
+        Commands::Vaults => list_vaults(&args),
+        Commands::Which => which(&args),
+        Commands::Doctor => doctor(&args),
+        Commands::RenameVault { old, new } => rename_vault(&args, old, new),
+        Commands::DebugFormat => debug_format(&args),
+        Commands::Export { raw: true, .. } => export_raw(&args),
+        Commands::Gen {
+            name: None, flags, ..
+        } => {
+            // Printed unmasked, as the password isn't stored anywhere else.
+            println!("{}", flags.generate(None, args.quiet)?);
+            Ok(())
+        }
+        Commands::Hint {
+            text: None,
+            clear: false,
+        } => show_hint(&args),
+        command => run(&args, command),
+    }
+}
+
+fn run(args: &Args, command: &Commands) -> anyhow::Result<()> {
+    let location = vault_location(args, args.config.vault())?;
+    let mut vault = open_vault(&location, args.config.vault(), args)?;
+    configure_vault(args, &location, &mut vault)?;
+    match command {
+        Commands::List { since, group } => list_entries(&vault, since.as_deref(), *group)?,
+        Commands::Get {
+            name,
+            no_interactive,
+            all,
+            qr,
+        } => print_entry(args, &vault, name.as_deref(), *no_interactive, *all, *qr)?,
+        Commands::Cat { name } => cat_entry(&vault, name)?,
+        Commands::Set {
+            name,
+            value,
+            allow_large,
+            allow_empty_value,
+            allow_control_chars,
+            metadata,
+        } => {
+            if *allow_large {
+                vault.set_max_value_size(None);
+            }
+            vault.set_allow_empty_values(*allow_empty_value);
+            vault.set_allow_control_chars(*allow_control_chars);
+            set_entry(args, &mut vault, &location, name, value, metadata)?;
+        }
+        Commands::Remove {
+            name: Some(name), ..
+        } => {
+            if !vault.remove(name) {
                 eprintln!("{name} not found");
                 std::process::exit(1);
-            };
-            println!("{value}");
+            }
+            save_vault(&vault, &location)?;
+        }
+        Commands::Remove {
+            entries_from: Some(path),
+            ..
+        } => remove_listed(&mut vault, &location, path)?,
+        Commands::Remove { .. } => unreachable!("clap requires a name or --entries-from"),
+        Commands::Touch { name } => {
+            vault.touch(name)?;
+            save_vault(&vault, &location)?;
+        }
+        Commands::Rotate { name, length } => {
+            let password = vault.rotate(name, &PasswordPolicy::with_length(*length))?;
+            save_vault(&vault, &location)?;
+            print_secret(args, &password);
+        }
+        Commands::Gen {
+            name: Some(name),
+            flags,
+            ..
+        } => generate_entry(args, &mut vault, &location, name, flags)?,
+        Commands::Import { source, flags } => {
+            import(&mut vault, &location, source, flags.options())?;
+        }
+        Commands::Export {
+            split_by_tag: Some(dir),
+            ..
+        } => export_by_tag(&vault, dir)?,
+        Commands::Export {
+            format,
+            secrets_only,
+            encrypted_for,
+            ..
+        } => export(&vault, *format, *secrets_only, encrypted_for.as_deref())?,
+        Commands::Stats => println!("{}", vault.stats()),
+        Commands::Audit {
+            check_policy,
+            remove_empty,
+        } => audit(&mut vault, &location, *check_policy, *remove_empty)?,
+        Commands::UpgradeEntries => upgrade(&mut vault, &location)?,
+        Commands::Field { command } => edit_field(args, &mut vault, &location, command)?,
+        Commands::Prune { keep } => prune(&mut vault, &location, *keep)?,
+        Commands::Hint { text, .. } => {
+            vault.set_hint(text.clone())?;
+            save_vault(&vault, &location)?;
+        }
+        Commands::Vaults
+        | Commands::Which
+        | Commands::Doctor
+        | Commands::RenameVault { .. }
+        | Commands::DebugFormat
+        | Commands::Gen { name: None, .. } => {
+            unreachable!("handled without opening the vault")
+        }
+    }
+    Ok(())
+}
+
+/// Applies the settings from the flags and config that saving the vault uses.
+fn configure_vault(args: &Args, location: &Location, vault: &mut Vault) -> anyhow::Result<()> {
+    if matches!(location, Location::File(path) if !path.exists()) {
+        vault.set_kdf_params(args.config.kdf())?;
+    }
+    if let Some(padding) = args.config.padding {
+        vault.set_padding(padding)?;
+    }
+    vault.set_create_dirs(!args.no_create_dirs);
+    vault.set_secure_delete(args.secure_delete);
+    vault.set_write_checksum(args.checksum);
+    Ok(())
+}
+
+fn list_entries(vault: &Vault, since: Option<&str>, group: bool) -> anyhow::Result<()> {
+    let entries = match since {
+        Some(since) => {
+            vault.entries_modified_since(passmate::time::parse_since(since, SystemTime::now())?)
         }
-        Commands::Set { name, value } => {
-            vault.set(name, value);
-            vault.save()?;
+        None => vault.entries(),
+    };
+    if group {
+        print!("{}", group_by_prefix(&entries));
+        return Ok(());
+    }
+    for entry in entries {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+/// Lists sorted names with those sharing a top-level prefix, like `aws/`,
+/// indented under a header for it. Names without a prefix stay as they are.
+fn group_by_prefix(names: &[String]) -> String {
+    let mut output = String::new();
+    let mut current = None;
+    for name in names {
+        match name.split_once('/') {
+            Some((prefix, rest)) if !prefix.is_empty() => {
+                if current != Some(prefix) {
+                    output.push_str(prefix);
+                    output.push_str("/\n");
+                    current = Some(prefix);
+                }
+                output.push_str("  ");
+                output.push_str(rest);
+                output.push('\n');
+            }
+            _ => {
+                output.push_str(name);
+                output.push('\n');
+                current = None;
+            }
         }
-        Commands::Remove { name } => {
-            vault.remove(&name);
-            vault.save()?;
+    }
+    output
+}
+
+/// Stores the value and metadata and saves the vault. An existing entry
+/// keeps its history, notes and custom fields, and its previous value
+/// is pushed to its history.
+fn set_entry(
+    args: &Args,
+    vault: &mut Vault,
+    location: &Location,
+    name: &str,
+    value: &str,
+    metadata: &Metadata,
+) -> anyhow::Result<()> {
+    vault.set(name, value)?;
+    if !metadata.is_empty() {
+        let mut entry = vault
+            .get_entry(name)
+            .cloned()
+            .expect("the entry was just set");
+        metadata.apply(&mut entry);
+        vault.set_entry(name, entry)?;
+    }
+    save_vault(vault, location)?;
+    if let (true, false, Location::File(path)) = (vault.is_new(), args.quiet, location) {
+        eprintln!("Created new vault {}", path.display());
+    }
+    Ok(())
+}
+
+fn policy(length: usize, exclude: &str) -> PasswordPolicy {
+    PasswordPolicy {
+        exclude: exclude.into(),
+        ..PasswordPolicy::with_length(length)
+    }
+}
+
+fn generate_entry(
+    args: &Args,
+    vault: &mut Vault,
+    location: &Location,
+    name: &str,
+    flags: &GenFlags,
+) -> anyhow::Result<()> {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    let password = if flags.confirm && interactive && !args.quiet {
+        let masked = args.config.mask_secrets();
+        let reviewed = generate::review(
+            || flags.generate(Some(vault), args.quiet),
+            |password| {
+                if masked {
+                    format!("{MASKED_SECRET} ({} characters)", password.chars().count())
+                } else {
+                    password.to_string()
+                }
+            },
+            std::io::stdin().lock(),
+            std::io::stderr(),
+        )?;
+        let Some(password) = reviewed else {
+            anyhow::bail!("No password accepted; {name} was not changed");
+        };
+        password
+    } else {
+        flags.generate(Some(vault), args.quiet)?
+    };
+    vault.set(name, password.as_str())?;
+    save_vault(vault, location)?;
+    print_secret(args, &password);
+    Ok(())
+}
+
+fn print_entry(
+    args: &Args,
+    vault: &Vault,
+    name: Option<&str>,
+    no_interactive: bool,
+    all: bool,
+    qr: bool,
+) -> anyhow::Result<()> {
+    let name = match name {
+        Some(name) => name.to_owned(),
+        None => match select_name(vault, no_interactive)? {
+            Some(name) => name,
+            None => return Ok(()),
+        },
+    };
+    let Some(entry) = vault.get_entry(&name) else {
+        eprintln!("{name} not found");
+        std::process::exit(1);
+    };
+    if qr {
+        style::eprintln(paint(
+            style::WARNING,
+            format_args!(
+                "WARNING: the QR code shows the secret of {name} to anyone who can see it"
+            ),
+        ));
+        print!("{}", render_qr(&entry.secret)?);
+    } else if all {
+        println!("{}", entry.details(!args.config.mask_secrets()));
+    } else {
+        print_secret(args, &entry.secret);
+    }
+    Ok(())
+}
+
+fn upgrade(vault: &mut Vault, location: &Location) -> anyhow::Result<()> {
+    let old_format = matches!(vault.needs_upgrade(), Some(UpgradeReason::OldFormat { .. }));
+    if vault.upgrade_entries() || old_format {
+        save_vault(vault, location)?;
+        eprintln!("Upgraded the vault to the current format");
+    } else {
+        eprintln!("Entries are already up to date");
+    }
+    Ok(())
+}
+
+fn prune(vault: &mut Vault, location: &Location, keep: usize) -> anyhow::Result<()> {
+    let removed = vault.prune_history(keep);
+    if removed > 0 {
+        save_vault(vault, location)?;
+    }
+    eprintln!("Removed {removed} old versions");
+    Ok(())
+}
+
+fn edit_field(
+    args: &Args,
+    vault: &mut Vault,
+    location: &Location,
+    command: &FieldCommand,
+) -> anyhow::Result<()> {
+    match command {
+        FieldCommand::Set {
+            entry,
+            field,
+            value,
+        } => vault.set_field(entry, field, value)?,
+        FieldCommand::Get { entry, field } => {
+            let Some(value) = vault.field(entry, field) else {
+                eprintln!("{field} not found in {entry}");
+                std::process::exit(1);
+            };
+            print_secret(args, value);
+            return Ok(());
         }
+        FieldCommand::Rename { entry, old, new } => vault.rename_field(entry, old, new)?,
+        FieldCommand::Remove { entry, field } => vault.remove_field(entry, field)?,
     }
+    save_vault(vault, location)?;
+    Ok(())
+}
+
+/// Writes the secret as is, never masked and without a trailing newline.
+fn cat_entry(vault: &Vault, name: &str) -> anyhow::Result<()> {
+    let Some(secret) = vault.get(name) else {
+        eprintln!("{name} not found");
+        std::process::exit(1);
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(secret.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Prints the secret, or a placeholder if secrets are masked.
+fn print_secret(args: &Args, secret: &str) {
+    if args.config.mask_secrets() {
+        println!("{MASKED_SECRET}");
+    } else {
+        println!("{secret}");
+    }
+}
+
+fn remove_listed(vault: &mut Vault, location: &Location, path: &Path) -> anyhow::Result<()> {
+    let names = read_names(path, location)?;
+    let report = vault.remove_many(names);
+    if !report.removed.is_empty() {
+        save_vault(vault, location)?;
+    }
+    for name in &report.removed {
+        eprintln!("Removed {name}");
+    }
+    for name in &report.not_found {
+        eprintln!("{name} not found");
+    }
+    Ok(())
+}
+
+fn import(
+    vault: &mut Vault,
+    location: &Location,
+    source: &ImportSource,
+    options: ImportOptions,
+) -> anyhow::Result<()> {
+    let input = source.read(location)?;
+    let report = if source.from_keepass {
+        vault.import_keepass_csv(&input, options)?
+    } else {
+        let format = source
+            .format
+            .or_else(|| DataFormat::from_path(&source.file));
+        vault.import(&input, format.unwrap_or_default(), options)?
+    };
+    if !report.added.is_empty() || !report.updated.is_empty() {
+        save_vault(vault, location)?;
+    }
+    for (name, err) in &report.rejected {
+        eprintln!("Rejected {name}: {err}");
+    }
+    eprintln!(
+        "Added {}, updated {}, unchanged {}, skipped {}",
+        report.added.len(),
+        report.updated.len(),
+        report.unchanged.len(),
+        report.skipped.len()
+    );
+    Ok(())
+}
+
+fn audit(
+    vault: &mut Vault,
+    location: &Location,
+    check_policy: bool,
+    remove_empty: bool,
+) -> anyhow::Result<()> {
+    let mut empty = vault.empty_value_entries();
+    if remove_empty && !empty.is_empty() {
+        vault.remove_many(empty.drain(..));
+        save_vault(vault, location)?;
+    }
+    for name in &empty {
+        style::println(paint(
+            style::WARNING,
+            format_args!("{name} has an empty value"),
+        ));
+    }
+    let collisions = vault.case_collisions();
+    for group in &collisions {
+        style::println(paint(
+            style::WARNING,
+            format_args!("Names differ only by case: {}", group.join(", ")),
+        ));
+    }
+    let violations = if check_policy {
+        vault.validate_all(&PasswordPolicy::default())
+    } else {
+        Vec::new()
+    };
+    for (name, broken) in &violations {
+        let broken: Vec<String> = broken.iter().map(ToString::to_string).collect();
+        style::println(paint(
+            style::WARNING,
+            format_args!("{name}: {}", broken.join(", ")),
+        ));
+    }
+    if empty.is_empty() && collisions.is_empty() && violations.is_empty() {
+        style::println(paint(style::OK, "No problems found"));
+    }
+    Ok(())
+}
+
+fn list_vaults(args: &Args) -> anyhow::Result<()> {
+    for name in vault_dirs(args)?.list_vaults()? {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+fn which(args: &Args) -> anyhow::Result<()> {
+    let name = args.config.vault();
+    if name == STDIO_VAULT {
+        anyhow::bail!("The vault is read from stdin");
+    }
+    println!("{}", resolve_vault_path(args, name)?.display());
+    Ok(())
+}
+
+fn doctor(args: &Args) -> anyhow::Result<()> {
+    let name = args.config.vault();
+    if name == STDIO_VAULT {
+        anyhow::bail!("Can't check a vault read from stdin");
+    }
+    let report = doctor::diagnose(&vault_dirs(args)?, &resolve_vault_path(args, name)?);
+    for check in &report.checks {
+        let check_style = match check.status {
+            doctor::Status::Ok => style::OK,
+            doctor::Status::Warning => style::WARNING,
+            doctor::Status::Error => style::ERROR,
+        };
+        style::println(paint(check_style, check));
+    }
+    if report.status() == doctor::Status::Error {
+        anyhow::bail!("Found problems that need fixing");
+    }
+    Ok(())
+}
+
+fn rename_vault(args: &Args, old: &str, new: &str) -> anyhow::Result<()> {
+    let (Location::File(old_path), Location::File(new_path)) =
+        (vault_location(args, old)?, vault_location(args, new)?)
+    else {
+        anyhow::bail!("Can't rename a vault read from stdin");
+    };
+    if !old_path.exists() {
+        anyhow::bail!("Vault not found: {old}");
+    }
+    let mut vault = open_vault(&Location::File(old_path), old, args)?;
+    vault.set_create_dirs(!args.no_create_dirs);
+    vault.set_secure_delete(args.secure_delete);
+    vault.set_write_checksum(args.checksum);
+    vault.move_to(new_path)?;
+    Ok(())
+}
+
+/// Reads the file, or stdin for `-` unless the vault is read from stdin.
+fn read_input(path: &Path, location: &Location) -> anyhow::Result<String> {
+    if path != Path::new(STDIO_VAULT) {
+        return Ok(std::fs::read_to_string(path)?);
+    }
+    if let Location::Stdio = location {
+        anyhow::bail!("Can't read input from stdin when the vault is read from stdin");
+    }
+    Ok(std::io::read_to_string(std::io::stdin())?)
+}
+
+/// Reads entry names, one per line, from the file or from stdin for `-`.
+fn read_names(path: &Path, location: &Location) -> anyhow::Result<Vec<String>> {
+    let contents = read_input(path, location)?;
+    Ok(contents
+        .lines()
+        .map(str::trim_end)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Asks the user to pick an entry when `get` is run without a name.
+fn select_name(vault: &Vault, no_interactive: bool) -> anyhow::Result<Option<String>> {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if no_interactive || !interactive {
+        anyhow::bail!("An entry name is required when not running interactively");
+    }
+    pick_entry(vault)
+}
+
+#[cfg(feature = "interactive")]
+#[mutants::skip]
+fn pick_entry(vault: &Vault) -> anyhow::Result<Option<String>> {
+    let mut selector = passmate::select::FuzzySelector;
+    Ok(passmate::select::select_entry(vault, &mut selector)?)
+}
+
+#[cfg(not(feature = "interactive"))]
+fn pick_entry(_vault: &Vault) -> anyhow::Result<Option<String>> {
+    anyhow::bail!("An entry name is required; passmate was built without the interactive feature")
+}
+
+fn show_hint(args: &Args) -> anyhow::Result<()> {
+    let bytes = read_vault_bytes(&vault_location(args, args.config.vault())?)?;
+    if let Some(hint) = passmate::format::inspect(&bytes)?.hint {
+        println!("{hint}");
+    }
+    Ok(())
+}
+
+fn export(
+    vault: &Vault,
+    format: DataFormat,
+    secrets_only: bool,
+    encrypted_for: Option<&str>,
+) -> anyhow::Result<()> {
+    let text = Zeroizing::new(if secrets_only {
+        vault.export_secrets(format)?
+    } else {
+        vault.export_entries(format)?
+    });
+    match encrypted_for {
+        Some(recipient) => println!("{}", encrypt_for(recipient, &text)?.trim_end()),
+        None => println!("{}", text.trim_end()),
+    }
+    Ok(())
+}
+
+fn export_by_tag(vault: &Vault, dir: &Path) -> anyhow::Result<()> {
+    let passphrase =
+        passmate::passphrase::prompt_new_secret("passphrase for the exported vaults", |prompt| {
+            read_passphrase(prompt).map(Zeroizing::new)
+        })?;
+    for path in vault.export_by_tag(dir, &passphrase)? {
+        eprintln!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn export_raw(args: &Args) -> anyhow::Result<()> {
+    let bytes = read_vault_bytes(&vault_location(args, args.config.vault())?)?;
+    std::io::stdout().write_all(&bytes)?;
+    Ok(())
+}
+
+fn debug_format(args: &Args) -> anyhow::Result<()> {
+    let bytes = read_vault_bytes(&vault_location(args, args.config.vault())?)?;
+    println!("{}", passmate::format::inspect(&bytes)?);
     Ok(())
 }
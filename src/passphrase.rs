@@ -0,0 +1,416 @@
+//! Reading the passphrase from somewhere other than the terminal.
+use crate::PassmateError;
+use std::{
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroizing;
+
+/// The environment variable systemd sets to the directory holding a
+/// service's credentials.
+pub const CREDENTIALS_DIRECTORY_VAR: &str = "CREDENTIALS_DIRECTORY";
+
+/// Where to read the passphrase from instead of prompting for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassphraseSource {
+    /// An open file descriptor, read with [`read_from_fd`].
+    Fd(i32),
+    /// A systemd credential by name, read from the file of that name in
+    /// [`CREDENTIALS_DIRECTORY_VAR`]. Only supported on Unix.
+    Credential(String),
+}
+
+impl PassphraseSource {
+    /// Reads the passphrase from the first line of the source.
+    ///
+    /// # Errors
+    /// Returns an error if the source can't be found or read.
+    pub fn read(&self) -> Result<Zeroizing<String>, PassmateError> {
+        match self {
+            PassphraseSource::Fd(fd) => read_from_fd(*fd),
+            PassphraseSource::Credential(name) => {
+                let dir = std::env::var_os(CREDENTIALS_DIRECTORY_VAR).ok_or_else(|| {
+                    PassmateError::Credential(format!(
+                        "${CREDENTIALS_DIRECTORY_VAR} is not set, so there are no credentials to read {name} from"
+                    ))
+                })?;
+                read_credential(Path::new(&dir), name)
+            }
+        }
+    }
+}
+
+/// Returns the path of the named credential in a systemd credentials
+/// directory, rejecting names that would point outside of it.
+///
+/// # Errors
+/// Returns an error if the name is empty or contains a path separator.
+pub fn credential_path(dir: &Path, name: &str) -> Result<PathBuf, PassmateError> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(PassmateError::Credential(format!(
+            "invalid credential name {name:?}"
+        )));
+    }
+    Ok(dir.join(name))
+}
+
+/// Reads the passphrase from the first line of the named credential in a
+/// systemd credentials directory.
+///
+/// # Errors
+/// Returns an error if the name is invalid or the credential can't be read.
+#[cfg(unix)]
+pub fn read_credential(dir: &Path, name: &str) -> Result<Zeroizing<String>, PassmateError> {
+    let path = credential_path(dir, name)?;
+    let file = std::fs::File::open(&path)
+        .map_err(|e| PassmateError::Credential(format!("can't read {}: {e}", path.display())))?;
+    read_first_line(io::BufReader::new(file))
+}
+
+/// Reads the passphrase from a systemd credential on Unix.
+///
+/// # Errors
+/// Always returns an error, as systemd credentials only exist on Unix.
+#[cfg(not(unix))]
+pub fn read_credential(_dir: &Path, _name: &str) -> Result<Zeroizing<String>, PassmateError> {
+    Err(PassmateError::Credential(
+        "reading the passphrase from a systemd credential is only supported on Unix".into(),
+    ))
+}
+
+/// Reads the passphrase from the first line of an open file descriptor,
+/// such as one passed by systemd credentials or a parent process.
+/// The line ending is not part of the passphrase.
+///
+/// The descriptor is left open. Only supported on Unix; elsewhere this
+/// always fails.
+///
+/// # Errors
+/// Returns an error if the descriptor isn't open or reading it fails.
+#[cfg(unix)]
+pub fn read_from_fd(fd: i32) -> Result<Zeroizing<String>, PassmateError> {
+    use std::os::fd::BorrowedFd;
+    // SAFETY: the descriptor is only borrowed to duplicate it, which fails
+    // with EBADF instead of touching anything if it isn't open.
+    let owned = unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .map_err(PassmateError::IO)?;
+    read_first_line(io::BufReader::new(std::fs::File::from(owned)))
+}
+
+/// Reads the passphrase from an open file descriptor on Unix.
+///
+/// # Errors
+/// Always returns an error, as file descriptors can only be passed on Unix.
+#[cfg(not(unix))]
+pub fn read_from_fd(_fd: i32) -> Result<Zeroizing<String>, PassmateError> {
+    Err(PassmateError::IO(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading the passphrase from a file descriptor is only supported on Unix",
+    )))
+}
+
+/// How many times [`prompt_new_secret`] asks before giving up.
+pub const NEW_SECRET_ATTEMPTS: u32 = 3;
+
+/// Asks for a new secret twice with `read`, which shows the prompt it's
+/// given and returns what was entered, until both entries match, then
+/// returns it. `label` names the secret in the prompts, as in
+/// "Enter the new passphrase: ".
+///
+/// # Errors
+/// Returns the error of `read`, or [`PassmateError::SecretMismatch`] if
+/// the entries didn't match in [`NEW_SECRET_ATTEMPTS`] attempts.
+pub fn prompt_new_secret(
+    label: &str,
+    mut read: impl FnMut(&str) -> Result<Zeroizing<String>, PassmateError>,
+) -> Result<Zeroizing<String>, PassmateError> {
+    let mut prompt = format!("Enter the new {label}: ");
+    for _ in 0..NEW_SECRET_ATTEMPTS {
+        let secret = read(&prompt)?;
+        if *read(&format!("Confirm the new {label}: "))? == *secret {
+            return Ok(secret);
+        }
+        prompt = format!("The entries didn't match. Enter the new {label}: ");
+    }
+    Err(PassmateError::SecretMismatch {
+        attempts: NEW_SECRET_ATTEMPTS,
+    })
+}
+
+/// Caches passphrases between runs, one for each vault name.
+pub trait PassphraseStore {
+    /// Returns the passphrase cached for the vault, if there is one.
+    ///
+    /// # Errors
+    /// Returns an error if the store can't be read.
+    fn get(&self, vault: &str) -> Result<Option<Zeroizing<String>>, PassmateError>;
+
+    /// Caches the passphrase for the vault, replacing any cached before.
+    ///
+    /// # Errors
+    /// Returns an error if the store can't be written.
+    fn set(&self, vault: &str, passphrase: &str) -> Result<(), PassmateError>;
+
+    /// Removes the passphrase cached for the vault, if there is one.
+    ///
+    /// # Errors
+    /// Returns an error if the store can't be written.
+    fn clear(&self, vault: &str) -> Result<(), PassmateError>;
+}
+
+/// Opens a vault with `open`, passing it the passphrase cached in the
+/// store for the vault, or else the one `prompt` returns, which is cached
+/// once it has opened the vault. A cached passphrase that no longer opens
+/// the vault is cleared before prompting.
+///
+/// # Errors
+/// Returns an error if prompting, opening the vault or using the store fails.
+pub fn open_with_store<T>(
+    store: &impl PassphraseStore,
+    vault: &str,
+    prompt: impl FnOnce() -> Result<Zeroizing<String>, PassmateError>,
+    mut open: impl FnMut(&str) -> Result<T, PassmateError>,
+) -> Result<T, PassmateError> {
+    if let Some(cached) = store.get(vault)? {
+        match open(&cached) {
+            Err(PassmateError::InvalidPassphrase { .. }) => store.clear(vault)?,
+            result => return result,
+        }
+    }
+    let passphrase = prompt()?;
+    let opened = open(&passphrase)?;
+    store.set(vault, &passphrase)?;
+    Ok(opened)
+}
+
+/// The keyring of the operating system, such as the macOS Keychain, the
+/// Windows Credential Manager or the Linux kernel keyring, storing each
+/// passphrase under the vault's name.
+#[cfg(feature = "keyring")]
+#[derive(Debug, Default)]
+pub struct Keyring;
+
+#[cfg(feature = "keyring")]
+impl Keyring {
+    /// The service the passphrases are stored under.
+    pub const SERVICE: &'static str = "passmate";
+
+    fn entry(vault: &str) -> Result<keyring::Entry, PassmateError> {
+        keyring::Entry::new(Self::SERVICE, vault).map_err(keyring_error)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl PassphraseStore for Keyring {
+    #[mutants::skip]
+    fn get(&self, vault: &str) -> Result<Option<Zeroizing<String>>, PassmateError> {
+        match Self::entry(vault)?.get_password() {
+            Ok(passphrase) => Ok(Some(Zeroizing::new(passphrase))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    #[mutants::skip]
+    fn set(&self, vault: &str, passphrase: &str) -> Result<(), PassmateError> {
+        Self::entry(vault)?
+            .set_password(passphrase)
+            .map_err(keyring_error)
+    }
+
+    #[mutants::skip]
+    fn clear(&self, vault: &str) -> Result<(), PassmateError> {
+        match Self::entry(vault)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+#[allow(clippy::needless_pass_by_value)]
+fn keyring_error(e: keyring::Error) -> PassmateError {
+    PassmateError::Keyring(e.to_string())
+}
+
+fn read_first_line(mut reader: impl BufRead) -> Result<Zeroizing<String>, PassmateError> {
+    let mut line = Zeroizing::new(String::new());
+    reader.read_line(&mut line).map_err(PassmateError::IO)?;
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::HashMap};
+
+    #[derive(Default)]
+    struct MemoryStore(RefCell<HashMap<String, String>>);
+
+    impl PassphraseStore for MemoryStore {
+        fn get(&self, vault: &str) -> Result<Option<Zeroizing<String>>, PassmateError> {
+            Ok(self.0.borrow().get(vault).cloned().map(Zeroizing::new))
+        }
+
+        fn set(&self, vault: &str, passphrase: &str) -> Result<(), PassmateError> {
+            self.0.borrow_mut().insert(vault.into(), passphrase.into());
+            Ok(())
+        }
+
+        fn clear(&self, vault: &str) -> Result<(), PassmateError> {
+            self.0.borrow_mut().remove(vault);
+            Ok(())
+        }
+    }
+
+    fn unlock(passphrase: &str) -> Result<String, PassmateError> {
+        if passphrase == "testpwd" {
+            Ok(passphrase.to_string())
+        } else {
+            Err(PassmateError::InvalidPassphrase { hint: None })
+        }
+    }
+
+    fn prompt(passphrase: &str) -> impl FnOnce() -> Result<Zeroizing<String>, PassmateError> + '_ {
+        move || Ok(Zeroizing::new(passphrase.to_string()))
+    }
+
+    #[test]
+    fn open_with_store_prompts_once_and_then_uses_the_cached_passphrase() {
+        let store = MemoryStore::default();
+
+        open_with_store(&store, "default", prompt("testpwd"), unlock).unwrap();
+        let opened = open_with_store(
+            &store,
+            "default",
+            || panic!("prompted although the passphrase was cached"),
+            unlock,
+        );
+
+        assert_eq!(opened.unwrap(), "testpwd");
+        assert_eq!(store.get("work").unwrap(), None);
+    }
+
+    #[test]
+    fn open_with_store_only_caches_a_passphrase_that_opens_the_vault() {
+        let store = MemoryStore::default();
+
+        let opened = open_with_store(&store, "default", prompt("wrong"), unlock);
+
+        assert!(matches!(
+            opened,
+            Err(PassmateError::InvalidPassphrase { .. })
+        ));
+        assert_eq!(store.get("default").unwrap(), None);
+    }
+
+    #[test]
+    fn open_with_store_replaces_a_cached_passphrase_that_no_longer_works() {
+        let store = MemoryStore::default();
+        store.set("default", "stale").unwrap();
+
+        open_with_store(&store, "default", prompt("testpwd"), unlock).unwrap();
+
+        assert_eq!(store.get("default").unwrap().unwrap().as_str(), "testpwd");
+    }
+
+    /// Returns a reader answering with the given entries in order,
+    /// recording the prompts it's shown.
+    fn scripted<'a>(
+        entries: &'a [&str],
+        prompts: &'a mut Vec<String>,
+    ) -> impl FnMut(&str) -> Result<Zeroizing<String>, PassmateError> + 'a {
+        let mut entries = entries.iter();
+        move |prompt| {
+            prompts.push(prompt.to_string());
+            Ok(Zeroizing::new(entries.next().unwrap().to_string()))
+        }
+    }
+
+    #[test]
+    fn prompt_new_secret_returns_the_secret_once_both_entries_match() {
+        let mut prompts = Vec::new();
+
+        let secret = prompt_new_secret(
+            "passphrase",
+            scripted(&["hunter2", "hunter2"], &mut prompts),
+        );
+
+        assert_eq!(secret.unwrap().as_str(), "hunter2");
+        assert_eq!(
+            prompts,
+            ["Enter the new passphrase: ", "Confirm the new passphrase: "]
+        );
+    }
+
+    #[test]
+    fn prompt_new_secret_asks_again_after_a_mismatch() {
+        let mut prompts = Vec::new();
+
+        let secret = prompt_new_secret(
+            "passphrase",
+            scripted(&["hunter2", "hunter3", "correct", "correct"], &mut prompts),
+        );
+
+        assert_eq!(secret.unwrap().as_str(), "correct");
+        assert_eq!(prompts.len(), 4);
+        assert!(prompts[2].starts_with("The entries didn't match."));
+    }
+
+    #[test]
+    fn prompt_new_secret_gives_up_after_the_last_attempt() {
+        let mut prompts = Vec::new();
+
+        let secret = prompt_new_secret(
+            "passphrase",
+            scripted(&["a", "b", "a", "b", "a", "b"], &mut prompts),
+        );
+
+        assert!(matches!(
+            secret,
+            Err(PassmateError::SecretMismatch { attempts }) if attempts == NEW_SECRET_ATTEMPTS
+        ));
+        assert_eq!(prompts.len(), 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_credential_reads_the_named_file_in_the_credentials_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("passmate.passphrase"), "testpwd\n").unwrap();
+
+        let passphrase = read_credential(temp_dir.path(), "passmate.passphrase").unwrap();
+
+        assert_eq!(passphrase.as_str(), "testpwd");
+        assert_eq!(
+            credential_path(temp_dir.path(), "passmate.passphrase").unwrap(),
+            temp_dir.path().join("passmate.passphrase")
+        );
+        for name in ["", ".", "..", "../passmate.passphrase", "/etc/shadow"] {
+            assert!(matches!(
+                credential_path(temp_dir.path(), name),
+                Err(PassmateError::Credential(_))
+            ));
+        }
+        assert!(matches!(
+            read_credential(temp_dir.path(), "missing"),
+            Err(PassmateError::Credential(message)) if message.contains("missing")
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_from_fd_reads_the_first_line_of_a_pipe() {
+        use std::{io::Write, os::fd::AsRawFd};
+        let (reader, mut writer) = io::pipe().unwrap();
+        writer.write_all(b"correct horse\r\nignored\n").unwrap();
+        drop(writer);
+
+        let passphrase = read_from_fd(reader.as_raw_fd()).unwrap();
+
+        assert_eq!(passphrase.as_str(), "correct horse");
+    }
+}
@@ -0,0 +1,239 @@
+//! Resolution of where vaults are stored.
+use crate::PassmateError;
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// The file extension of vault files.
+pub const VAULT_EXTENSION: &str = "vault";
+/// The file extension of unencrypted vaults, kept distinct so they're never
+/// mistaken for encrypted ones.
+pub const PLAINTEXT_EXTENSION: &str = "plain";
+
+/// The directories vaults are stored in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultDirs {
+    /// Where vaults are stored, usually `$XDG_DATA_HOME/passmate`.
+    pub data_dir: PathBuf,
+    /// Where earlier versions stored vaults, usually `$XDG_CONFIG_HOME/passmate`.
+    pub legacy_dir: PathBuf,
+}
+
+impl VaultDirs {
+    /// Returns the path of the vault with the given file name.
+    ///
+    /// A vault that only exists in the legacy directory is still
+    /// found there, otherwise the path is in the data directory.
+    #[must_use]
+    pub fn vault_path(&self, file_name: &str) -> PathBuf {
+        let path = self.data_dir.join(file_name);
+        let legacy_path = self.legacy_dir.join(file_name);
+        if !path.exists() && legacy_path.exists() {
+            return legacy_path;
+        }
+        path
+    }
+
+    /// Moves a vault that only exists in the legacy directory into
+    /// the data directory, returning its new path if it was moved.
+    ///
+    /// # Errors
+    /// Returns an error if the data directory can't be created or the vault can't be moved.
+    pub fn migrate_legacy_vault(&self, file_name: &str) -> Result<Option<PathBuf>, PassmateError> {
+        let path = self.data_dir.join(file_name);
+        let legacy_path = self.legacy_dir.join(file_name);
+        if path.exists() || !legacy_path.exists() {
+            return Ok(None);
+        }
+        std::fs::create_dir_all(&self.data_dir).map_err(PassmateError::IO)?;
+        std::fs::rename(&legacy_path, &path).map_err(PassmateError::IO)?;
+        Ok(Some(path))
+    }
+
+    /// Returns the names of the vaults in the data and legacy
+    /// directories in alphabetical order.
+    ///
+    /// # Errors
+    /// Returns an error if either directory exists but can't be read.
+    pub fn list_vaults(&self) -> Result<Vec<String>, PassmateError> {
+        let mut names = list_vaults(&self.data_dir)?;
+        names.extend(list_vaults(&self.legacy_dir)?);
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+/// Returns the file name of the named vault with the extension, rejecting
+/// names that would point outside of the vault directories.
+///
+/// # Errors
+/// Returns an error if the name is empty, `.` or `..`, or contains a path separator.
+pub fn vault_file_name(name: &str, extension: &str) -> Result<String, PassmateError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(PassmateError::InvalidVaultName(name.into()));
+    }
+    Ok(format!("{name}.{extension}"))
+}
+
+/// Returns the names, without the extension, of the vault files in the
+/// directory in alphabetical order. A missing directory has no vaults.
+///
+/// # Errors
+/// Returns an error if the directory exists but can't be read.
+pub fn list_vaults(dir: &Path) -> Result<Vec<String>, PassmateError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(PassmateError::IO(e)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(PassmateError::IO)?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != VAULT_EXTENSION) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_none, assert_ok};
+    use tempfile::TempDir;
+
+    struct TempDirs {
+        _temp_dir: TempDir,
+        dirs: VaultDirs,
+    }
+
+    impl TempDirs {
+        fn new() -> Self {
+            let temp_dir = TempDir::new().unwrap();
+            let dirs = VaultDirs {
+                data_dir: temp_dir.path().join("data"),
+                legacy_dir: temp_dir.path().join("config"),
+            };
+            Self {
+                _temp_dir: temp_dir,
+                dirs,
+            }
+        }
+    }
+
+    fn create_vault_file(dir: &Path) {
+        create_named_vault_file(dir, "default.vault");
+    }
+
+    fn create_named_vault_file(dir: &Path, file_name: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(file_name), "vault").unwrap();
+    }
+
+    #[test]
+    fn vault_path_defaults_to_the_data_dir() {
+        let tmp = TempDirs::new();
+
+        let path = tmp.dirs.vault_path("default.vault");
+
+        assert_eq!(path, tmp.dirs.data_dir.join("default.vault"));
+    }
+
+    #[test]
+    fn vault_path_finds_a_vault_in_the_legacy_dir() {
+        let tmp = TempDirs::new();
+        create_vault_file(&tmp.dirs.legacy_dir);
+
+        let path = tmp.dirs.vault_path("default.vault");
+
+        assert_eq!(path, tmp.dirs.legacy_dir.join("default.vault"));
+    }
+
+    #[test]
+    fn vault_path_prefers_a_vault_in_the_data_dir() {
+        let tmp = TempDirs::new();
+        create_vault_file(&tmp.dirs.legacy_dir);
+        create_vault_file(&tmp.dirs.data_dir);
+
+        let path = tmp.dirs.vault_path("default.vault");
+
+        assert_eq!(path, tmp.dirs.data_dir.join("default.vault"));
+    }
+
+    #[test]
+    fn migrate_legacy_vault_moves_the_vault_into_the_data_dir() {
+        let tmp = TempDirs::new();
+        create_vault_file(&tmp.dirs.legacy_dir);
+
+        let moved = tmp.dirs.migrate_legacy_vault("default.vault").unwrap();
+
+        let path = tmp.dirs.data_dir.join("default.vault");
+        assert_eq!(moved, Some(path.clone()));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "vault");
+        assert!(!tmp.dirs.legacy_dir.join("default.vault").exists());
+    }
+
+    #[test]
+    fn migrate_legacy_vault_does_nothing_without_a_legacy_vault() {
+        let tmp = TempDirs::new();
+
+        assert_none!(tmp.dirs.migrate_legacy_vault("default.vault").unwrap());
+        assert_ok!(tmp.dirs.migrate_legacy_vault("default.vault"));
+        assert!(!tmp.dirs.data_dir.exists());
+    }
+
+    #[test]
+    fn list_vaults_returns_the_names_of_vault_files_in_the_directory() {
+        let tmp = TempDirs::new();
+        create_named_vault_file(&tmp.dirs.data_dir, "work.vault");
+        create_named_vault_file(&tmp.dirs.data_dir, "personal.vault");
+        create_named_vault_file(&tmp.dirs.data_dir, "notes.txt");
+        std::fs::create_dir(tmp.dirs.data_dir.join("dir.vault")).unwrap();
+
+        let names = list_vaults(&tmp.dirs.data_dir).unwrap();
+
+        assert_eq!(names, vec!["personal".to_string(), "work".into()]);
+    }
+
+    #[test]
+    fn vault_file_name_rejects_names_that_point_outside_the_directory() {
+        assert_eq!(
+            vault_file_name("work", VAULT_EXTENSION).unwrap(),
+            "work.vault"
+        );
+        for name in ["", ".", "..", "../x", "a/b", "a\\b", "/etc/passwd"] {
+            assert!(
+                matches!(
+                    vault_file_name(name, VAULT_EXTENSION),
+                    Err(PassmateError::InvalidVaultName(invalid)) if invalid == name
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn list_vaults_returns_nothing_for_a_missing_directory() {
+        let tmp = TempDirs::new();
+
+        assert!(list_vaults(&tmp.dirs.data_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn vault_dirs_list_vaults_includes_vaults_in_the_legacy_dir() {
+        let tmp = TempDirs::new();
+        create_named_vault_file(&tmp.dirs.data_dir, "work.vault");
+        create_named_vault_file(&tmp.dirs.legacy_dir, "default.vault");
+        create_named_vault_file(&tmp.dirs.legacy_dir, "work.vault");
+
+        let names = tmp.dirs.list_vaults().unwrap();
+
+        assert_eq!(names, vec!["default".to_string(), "work".into()]);
+    }
+}
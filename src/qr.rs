@@ -0,0 +1,102 @@
+//! Rendering secrets as QR codes in the terminal, to move them to a phone.
+use crate::PassmateError;
+use qrcode::{Color, QrCode};
+
+/// The light border around the code that scanners need, in modules.
+pub const QUIET_ZONE: usize = 4;
+
+/// Returns the modules of the QR code encoding the text, row by row,
+/// with `true` for dark modules.
+///
+/// # Errors
+/// Returns an error if the text is too long for a QR code.
+pub fn modules(text: &str) -> Result<Vec<Vec<bool>>, PassmateError> {
+    let code = QrCode::new(text).map_err(|e| PassmateError::Qr(e.to_string()))?;
+    Ok(code
+        .to_colors()
+        .chunks(code.width())
+        .map(|row| row.iter().map(|&color| color == Color::Dark).collect())
+        .collect())
+}
+
+/// Renders the QR code encoding the text with Unicode half blocks, two
+/// rows of modules per line, surrounded by the [`QUIET_ZONE`].
+///
+/// Light modules are drawn as blocks, so the code reads as dark on
+/// light in a terminal with a dark background.
+///
+/// # Errors
+/// Returns an error if the text is too long for a QR code.
+pub fn render(text: &str) -> Result<String, PassmateError> {
+    let modules = modules(text)?;
+    let width = modules.len() + 2 * QUIET_ZONE;
+    let dark = |row: usize, col: usize| {
+        let (Some(row), Some(col)) = (row.checked_sub(QUIET_ZONE), col.checked_sub(QUIET_ZONE))
+        else {
+            return false;
+        };
+        modules
+            .get(row)
+            .and_then(|row| row.get(col))
+            .copied()
+            .unwrap_or(false)
+    };
+    let mut output = String::new();
+    for row in (0..width).step_by(2) {
+        for col in 0..width {
+            output.push(match (dark(row, col), dark(row + 1, col)) {
+                (false, false) => '█',
+                (false, true) => '▀',
+                (true, false) => '▄',
+                (true, true) => ' ',
+            });
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that the 7x7 finder pattern, a dark ring around a light ring
+    /// around a dark 3x3 square, starts at the given module.
+    fn assert_finder_pattern(modules: &[Vec<bool>], top: usize, left: usize) {
+        for row in 0..7 {
+            for col in 0..7 {
+                let ring = row.min(col).min(6 - row).min(6 - col);
+                assert_eq!(modules[top + row][left + col], ring != 1);
+            }
+        }
+    }
+
+    #[test]
+    fn modules_encodes_a_short_text_as_a_version_1_code() {
+        let modules = modules("JBSWY3DPEHPK3PXP").unwrap();
+
+        assert_eq!(modules.len(), 21);
+        assert!(modules.iter().all(|row| row.len() == 21));
+        assert_finder_pattern(&modules, 0, 0);
+        assert_finder_pattern(&modules, 0, 14);
+        assert_finder_pattern(&modules, 14, 0);
+        let timing: Vec<bool> = (8..13).map(|col| modules[6][col]).collect();
+        assert_eq!(timing, [true, false, true, false, true]);
+        assert_eq!(modules, super::modules("JBSWY3DPEHPK3PXP").unwrap());
+    }
+
+    #[test]
+    fn render_draws_two_rows_per_line_inside_the_quiet_zone() {
+        let rendered = render("JBSWY3DPEHPK3PXP").unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines.len(), 15);
+        assert!(lines.iter().all(|line| line.chars().count() == 29));
+        assert!(lines[..2].iter().all(|line| line.chars().all(|c| c == '█')));
+        assert!(lines[2].starts_with("████ ▄▄▄▄▄ █"));
+        assert!(matches!(
+            render(&"x".repeat(8000)),
+            Err(PassmateError::Qr(_))
+        ));
+    }
+}
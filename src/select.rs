@@ -0,0 +1,109 @@
+//! Interactive selection of an entry when its name isn't given.
+
+use crate::{PassmateError, Vault};
+
+/// Chooses one item from a list, typically by asking the user.
+pub trait Selector {
+    /// Returns the index of the chosen item, or `None` if the selection was cancelled.
+    ///
+    /// # Errors
+    /// Returns an error if interacting with the user fails.
+    fn select(&mut self, prompt: &str, items: &[String]) -> Result<Option<usize>, PassmateError>;
+}
+
+/// Asks the selector to choose one of the vault's entries and returns its name,
+/// or `None` if the vault is empty or the selection was cancelled.
+///
+/// # Errors
+/// Returns an error if the selector fails.
+pub fn select_entry(
+    vault: &Vault,
+    selector: &mut impl Selector,
+) -> Result<Option<String>, PassmateError> {
+    let mut entries = vault.entries();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let index = selector.select("Select an entry", &entries)?;
+    Ok(index
+        .filter(|&index| index < entries.len())
+        .map(|index| entries.swap_remove(index)))
+}
+
+/// A selector that presents a fuzzy-filterable list on the terminal.
+#[cfg(feature = "interactive")]
+#[derive(Debug, Default)]
+pub struct FuzzySelector;
+
+#[cfg(feature = "interactive")]
+impl Selector for FuzzySelector {
+    #[mutants::skip]
+    fn select(&mut self, prompt: &str, items: &[String]) -> Result<Option<usize>, PassmateError> {
+        dialoguer::FuzzySelect::new()
+            .with_prompt(prompt)
+            .items(items)
+            .interact_opt()
+            .map_err(|dialoguer::Error::IO(e)| PassmateError::IO(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct ScriptedSelector {
+        choice: Option<usize>,
+        shown: Vec<String>,
+    }
+
+    impl Selector for ScriptedSelector {
+        fn select(
+            &mut self,
+            _prompt: &str,
+            items: &[String],
+        ) -> Result<Option<usize>, PassmateError> {
+            self.shown = items.to_vec();
+            Ok(self.choice)
+        }
+    }
+
+    fn vault_with(names: &[&str]) -> (TempDir, Vault) {
+        let temp_dir = TempDir::new().unwrap();
+        let mut vault = Vault::open(temp_dir.path().join("test.vault"), "testpwd").unwrap();
+        for name in names {
+            vault.set(*name, "secret").unwrap();
+        }
+        (temp_dir, vault)
+    }
+
+    #[test]
+    fn select_entry_returns_the_entry_chosen_from_the_sorted_names() {
+        let (_temp_dir, vault) = vault_with(&["gitlab", "github"]);
+        let mut selector = ScriptedSelector {
+            choice: Some(1),
+            shown: Vec::new(),
+        };
+
+        let selected = select_entry(&vault, &mut selector).unwrap();
+
+        assert_eq!(selector.shown, ["github", "gitlab"]);
+        assert_eq!(selected.as_deref(), Some("gitlab"));
+    }
+
+    #[test]
+    fn select_entry_returns_none_when_cancelled_or_empty() {
+        let (_temp_dir, vault) = vault_with(&["github"]);
+        let mut selector = ScriptedSelector {
+            choice: None,
+            shown: Vec::new(),
+        };
+        assert_eq!(select_entry(&vault, &mut selector).unwrap(), None);
+
+        let (_temp_dir, empty) = vault_with(&[]);
+        selector.choice = Some(0);
+        selector.shown.clear();
+        assert_eq!(select_entry(&empty, &mut selector).unwrap(), None);
+        assert!(selector.shown.is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+//! Encrypting exports for someone else with [age](https://age-encryption.org),
+//! so they can read them with their own identity instead of the passphrase.
+use crate::PassmateError;
+use zeroize::Zeroizing;
+
+/// Encrypts the data to an age X25519 recipient, a public key like
+/// `age1...`, returning it ASCII-armored.
+///
+/// # Errors
+/// Returns an error if the recipient isn't a valid public key.
+pub fn encrypt_for(recipient: &str, data: &[u8]) -> Result<String, PassmateError> {
+    let recipient: age::x25519::Recipient = recipient
+        .trim()
+        .parse()
+        .map_err(|e: &str| PassmateError::Age(format!("invalid recipient: {e}")))?;
+    age::encrypt_and_armor(&recipient, data).map_err(|e| PassmateError::Age(e.to_string()))
+}
+
+/// Decrypts data written by [`encrypt_for`], armored or not, with the
+/// recipient's identity. The identity is read from the first line of the
+/// text that isn't blank or a `#` comment, the layout of the files
+/// `age-keygen` writes.
+///
+/// # Errors
+/// Returns an error if there's no valid identity or decrypting fails.
+pub fn decrypt_with(identity: &str, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, PassmateError> {
+    let identity: age::x25519::Identity = identity
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default()
+        .parse()
+        .map_err(|e: &str| PassmateError::Age(format!("invalid identity: {e}")))?;
+    age::decrypt(&identity, data)
+        .map(Zeroizing::new)
+        .map_err(|e| PassmateError::Age(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataFormat, ImportOptions, Vault};
+    use age::secrecy::ExposeSecret;
+    use tempfile::TempDir;
+
+    #[test]
+    fn an_export_encrypted_for_a_recipient_imports_with_their_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut vault = Vault::open(temp_dir.path().join("ours.vault"), "testpwd").unwrap();
+        vault.set("github", "secret").unwrap();
+        let identity = age::x25519::Identity::generate();
+        let identity_file = format!(
+            "# public key: {}\n{}\n",
+            identity.to_public(),
+            identity.to_string().expose_secret()
+        );
+
+        let exported = vault.export_entries(DataFormat::Json).unwrap();
+        let encrypted =
+            encrypt_for(&identity.to_public().to_string(), exported.as_bytes()).unwrap();
+        let decrypted = decrypt_with(&identity_file, encrypted.as_bytes()).unwrap();
+        let mut theirs = Vault::open(temp_dir.path().join("theirs.vault"), "other").unwrap();
+        theirs
+            .import(
+                std::str::from_utf8(&decrypted).unwrap(),
+                DataFormat::Json,
+                ImportOptions::default(),
+            )
+            .unwrap();
+
+        assert!(encrypted.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert!(!encrypted.contains("secret"));
+        assert!(theirs.data_eq(&vault));
+        let stranger = age::x25519::Identity::generate();
+        assert!(matches!(
+            decrypt_with(stranger.to_string().expose_secret(), encrypted.as_bytes()),
+            Err(PassmateError::Age(_))
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+//! Summary statistics about the secrets in a vault.
+use crate::generate::{estimate_strength, Strength};
+use std::{collections::BTreeMap, fmt};
+
+/// An overview of a vault's secrets that never includes their values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VaultStats {
+    pub entries: usize,
+    /// Length of the shortest secret in characters, or 0 for an empty vault.
+    pub min_length: usize,
+    /// Length of the longest secret in characters, or 0 for an empty vault.
+    pub max_length: usize,
+    /// Mean secret length in characters, or 0 for an empty vault.
+    pub mean_length: f64,
+    /// Number of secrets with each estimated strength.
+    pub strength: BTreeMap<Strength, usize>,
+}
+
+impl VaultStats {
+    /// Computes the statistics of the given secrets.
+    pub fn from_values<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut stats = Self {
+            entries: 0,
+            min_length: usize::MAX,
+            max_length: 0,
+            mean_length: 0.0,
+            strength: Strength::ALL.into_iter().map(|s| (s, 0)).collect(),
+        };
+        let mut total_length = 0;
+        for value in values {
+            let length = value.chars().count();
+            stats.entries += 1;
+            stats.min_length = stats.min_length.min(length);
+            stats.max_length = stats.max_length.max(length);
+            total_length += length;
+            *stats.strength.entry(estimate_strength(value)).or_default() += 1;
+        }
+        if stats.entries == 0 {
+            stats.min_length = 0;
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            let mean = total_length as f64 / stats.entries as f64;
+            stats.mean_length = mean;
+        }
+        stats
+    }
+}
+
+impl fmt::Display for VaultStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "entries: {}", self.entries)?;
+        write!(
+            f,
+            "length: min {}, max {}, mean {:.1}",
+            self.min_length, self.max_length, self.mean_length
+        )?;
+        for (strength, count) in &self.strength {
+            write!(f, "\n{strength}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_values_computes_the_length_and_strength_distribution() {
+        let stats = VaultStats::from_values(["password", "password12", "Xy7!kQ2@mN9#pL4$wR8%"]);
+
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.min_length, 8);
+        assert_eq!(stats.max_length, 20);
+        assert!((stats.mean_length - 38.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(
+            stats.strength.into_iter().collect::<Vec<_>>(),
+            [
+                (Strength::Weak, 1),
+                (Strength::Fair, 1),
+                (Strength::Good, 0),
+                (Strength::Strong, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn from_values_of_nothing_is_all_zeros() {
+        let stats = VaultStats::from_values([]);
+
+        assert_eq!(stats.entries, 0);
+        assert_eq!((stats.min_length, stats.max_length), (0, 0));
+        assert!(stats.strength.values().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn display_never_includes_the_values() {
+        let stats = VaultStats::from_values(["hunter2", "password12"]);
+
+        let output = stats.to_string();
+
+        assert!(output.starts_with("entries: 2\nlength: min 7, max 10, mean 8.5\n"));
+        assert!(output.ends_with("\nweak: 1\nfair: 1\ngood: 0\nstrong: 0"));
+        assert!(!output.contains("hunter2") && !output.contains("password12"));
+    }
+}
@@ -0,0 +1,67 @@
+//! Colors for the command-line output.
+//!
+//! Styled text is meant to be written with [`println`] and [`eprintln`],
+//! which strip the colors when the output isn't a terminal, `NO_COLOR`
+//! is set, or they've been turned off with [`disable`].
+use anstyle::{AnsiColor, Style};
+use std::{fmt, io::Write};
+
+/// Errors that stop a command.
+pub const ERROR: Style = AnsiColor::Red.on_default().bold();
+/// Problems worth the user's attention that don't stop a command.
+pub const WARNING: Style = AnsiColor::Yellow.on_default();
+/// Checks that found nothing wrong.
+pub const OK: Style = AnsiColor::Green.on_default();
+
+/// Text displayed in a style.
+#[derive(Debug, Clone, Copy)]
+pub struct Styled<T> {
+    style: Style,
+    text: T,
+}
+
+/// Displays the text in the style.
+pub fn paint<T: fmt::Display>(style: Style, text: T) -> Styled<T> {
+    Styled { style, text }
+}
+
+impl<T: fmt::Display> fmt::Display for Styled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.style.render(),
+            self.text,
+            self.style.render_reset()
+        )
+    }
+}
+
+/// Prints the line to stdout, without colors unless they're supported.
+pub fn println(line: impl fmt::Display) {
+    let _ = writeln!(anstream::stdout(), "{line}");
+}
+
+/// Prints the line to stderr, without colors unless they're supported.
+pub fn eprintln(line: impl fmt::Display) {
+    let _ = writeln!(anstream::stderr(), "{line}");
+}
+
+/// Turns colors off for the rest of the process, even on a terminal.
+pub fn disable() {
+    anstream::ColorChoice::Never.write_global();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_wraps_the_text_in_the_style_and_a_reset() {
+        assert_eq!(
+            paint(ERROR, "Error:").to_string(),
+            "\x1b[1m\x1b[31mError:\x1b[0m"
+        );
+        assert_eq!(paint(Style::new(), "plain").to_string(), "plain");
+    }
+}
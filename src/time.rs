@@ -0,0 +1,89 @@
+//! Parsing of the points in time accepted by `ls --since`.
+use crate::PassmateError;
+use std::time::{Duration, SystemTime};
+
+/// Parses either a duration before `now`, such as `7d` or `12h 30m`,
+/// or an RFC 3339 date or timestamp, such as `2024-05-01` or
+/// `2024-05-01T12:00:00Z`. Dates and timestamps without an offset are UTC.
+///
+/// # Errors
+/// Returns an error if the text is neither a duration nor a date,
+/// or the duration reaches back before the Unix epoch.
+pub fn parse_since(spec: &str, now: SystemTime) -> Result<SystemTime, PassmateError> {
+    let spec = spec.trim();
+    if let Ok(duration) = humantime::parse_duration(spec) {
+        return now
+            .checked_sub(duration)
+            .filter(|time| *time >= SystemTime::UNIX_EPOCH)
+            .ok_or_else(|| PassmateError::InvalidTime(format!("{spec} is too far in the past")));
+    }
+    let timestamp = if spec.len() == "YYYY-MM-DD".len() {
+        humantime::parse_rfc3339(&format!("{spec}T00:00:00Z"))
+    } else {
+        humantime::parse_rfc3339_weak(spec)
+    };
+    timestamp.map_err(|_| {
+        PassmateError::InvalidTime(format!(
+            "expected a duration like 7d or a date like 2024-05-01, got {spec}"
+        ))
+    })
+}
+
+/// Returns the time as whole seconds since the Unix epoch.
+pub(crate) fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Returns the time the given number of seconds after the Unix epoch.
+#[must_use]
+pub fn from_unix_secs(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_since_subtracts_durations_from_now() {
+        let now = from_unix_secs(1_000_000);
+
+        assert_eq!(
+            parse_since("7d", now).unwrap(),
+            from_unix_secs(1_000_000 - 7 * 86_400)
+        );
+        assert_eq!(
+            parse_since("1h 30m", now).unwrap(),
+            from_unix_secs(1_000_000 - 5_400)
+        );
+    }
+
+    #[test]
+    fn parse_since_reads_dates_and_timestamps_as_utc() {
+        let now = SystemTime::now();
+
+        assert_eq!(
+            parse_since("1970-01-02", now).unwrap(),
+            from_unix_secs(86_400)
+        );
+        assert_eq!(
+            parse_since("1970-01-02T00:00:10Z", now).unwrap(),
+            from_unix_secs(86_410)
+        );
+    }
+
+    #[test]
+    fn parse_since_rejects_invalid_input() {
+        let now = from_unix_secs(60);
+
+        assert!(matches!(
+            parse_since("last week", now),
+            Err(PassmateError::InvalidTime(_))
+        ));
+        assert!(matches!(
+            parse_since("2m", now),
+            Err(PassmateError::InvalidTime(_))
+        ));
+    }
+}
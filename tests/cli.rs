@@ -1,7 +1,9 @@
 use assert_cmd::Command;
+use predicates::prelude::*;
 use tempfile::TempDir;
 
 const CONFIG_HOME: &str = "XDG_CONFIG_HOME";
+const DATA_HOME: &str = "XDG_DATA_HOME";
 
 #[test]
 fn binary_with_version_flag_prints_the_version() {
@@ -41,6 +43,7 @@ fn binary_with_set_command_adds_a_password_to_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["set", "mypass", "test"])
         .assert()
         .success();
@@ -53,6 +56,7 @@ fn binary_with_get_command_retrieves_password_from_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["set", "mypass", "testpass"])
         .assert()
         .success();
@@ -60,12 +64,214 @@ fn binary_with_get_command_retrieves_password_from_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
-        .args(["get", "mypass"])
+        .env(DATA_HOME, temp_config.path())
+        .args(["get", "--reveal", "mypass"])
         .assert()
         .success()
         .stdout(predicates::str::contains("testpass"));
 }
 
+#[cfg(unix)]
+#[test]
+fn binary_with_passphrase_fd_reads_the_passphrase_from_the_descriptor() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--passphrase-fd", "0", "set", "mypass", "testpass"])
+        .write_stdin("otherpwd\n")
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--passphrase-fd", "0", "cat", "mypass"])
+        .write_stdin("otherpwd\n")
+        .assert()
+        .success()
+        .stdout("testpass");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["cat", "mypass"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid passphrase"));
+}
+
+#[cfg(unix)]
+#[test]
+fn binary_with_credential_reads_the_passphrase_from_the_credentials_directory() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let credentials = temp_dir.path().join("credentials");
+    std::fs::create_dir(&credentials).unwrap();
+    std::fs::write(credentials.join("passmate"), "otherpwd\n").unwrap();
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .env("CREDENTIALS_DIRECTORY", &credentials)
+        .args(["--credential", "passmate", "set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--passphrase-fd", "0", "cat", "mypass"])
+        .write_stdin("otherpwd\n")
+        .assert()
+        .success()
+        .stdout("testpass");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .env_remove("CREDENTIALS_DIRECTORY")
+        .args(["--credential", "passmate", "cat", "mypass"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "CREDENTIALS_DIRECTORY is not set",
+        ));
+}
+
+#[test]
+fn binary_with_set_command_rejects_control_characters_unless_allowed() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "two\nlines"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("control character"));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "two\nlines", "--allow-control-chars"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn binary_with_import_command_imports_escaped_newlines_of_a_lines_file() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let file = temp_dir.path().join("keys.lines");
+    std::fs::write(
+        &file,
+        "ssh\t-----BEGIN KEY-----\\nabc\\n-----END KEY-----\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("import")
+        .arg(&file)
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Added 1, updated 0, unchanged 0, skipped 0\n",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["cat", "ssh"])
+        .assert()
+        .success()
+        .stdout("-----BEGIN KEY-----\nabc\n-----END KEY-----");
+}
+
+#[cfg(feature = "qr")]
+#[test]
+fn binary_with_get_command_and_qr_flag_renders_the_secret_as_a_qr_code() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "otp-seed", "JBSWY3DPEHPK3PXP"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "otp-seed", "--qr"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("█".repeat(29)))
+        .stdout(predicates::str::contains("JBSWY3DPEHPK3PXP").not())
+        .stderr(predicates::str::contains(
+            "WARNING: the QR code shows the secret",
+        ));
+}
+
+#[test]
+fn binary_with_touch_command_touches_only_existing_entries() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["touch", "mypass"])
+        .assert()
+        .success();
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["touch", "other"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Entry not found: other"));
+}
+
+#[test]
+fn binary_with_cat_command_writes_exactly_the_stored_value() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "test pass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["--mask-stdout", "cat", "mypass"])
+        .assert()
+        .success()
+        .stdout(predicates::ord::eq(b"test pass" as &[u8]));
+}
+
 #[test]
 fn binary_with_get_command_prints_not_found_if_a_password_with_given_name_does_not_exist() {
     let temp_config =
@@ -74,6 +280,7 @@ fn binary_with_get_command_prints_not_found_if_a_password_with_given_name_does_n
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["get", "mypass"])
         .assert()
         .failure()
@@ -87,6 +294,7 @@ fn binary_with_remove_command_deletes_a_password_from_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["set", "mypass", "testpass"])
         .assert()
         .success();
@@ -94,6 +302,7 @@ fn binary_with_remove_command_deletes_a_password_from_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["remove", "mypass"])
         .assert()
         .success();
@@ -101,10 +310,20 @@ fn binary_with_remove_command_deletes_a_password_from_the_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["get", "mypass"])
         .assert()
         .failure()
         .stderr(predicates::str::contains("mypass not found"));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["remove", "mypass"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::ends_with("mypass not found\n"));
 }
 
 #[test]
@@ -114,12 +333,14 @@ fn binary_with_ls_command_lists_the_entry_names_in_a_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["set", "pass1", "secretpass1"])
         .assert()
         .success();
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .args(["set", "pass2", "secretpass2"])
         .assert()
         .success();
@@ -127,8 +348,1473 @@ fn binary_with_ls_command_lists_the_entry_names_in_a_vault() {
     Command::cargo_bin("passmate")
         .unwrap()
         .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
         .arg("ls")
         .assert()
         .success()
         .stdout(predicates::str::contains("pass1\npass2\n"));
 }
+
+#[test]
+fn binary_with_rotate_command_replaces_the_password_in_the_vault() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["rotate", "--reveal", "mypass", "--length", "16"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let password = String::from_utf8(output).unwrap();
+    assert_eq!(password.trim_end().len(), 16);
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout(password);
+}
+
+#[test]
+fn binary_with_rotate_command_fails_if_the_entry_does_not_exist() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["rotate", "--reveal", "mypass"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Entry not found: mypass"));
+}
+
+#[test]
+fn binary_with_debug_format_command_prints_the_header_without_decrypting() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .arg("debug-format")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("magic: PASSMATE\nversion: 6\n"))
+        .stdout(predicates::str::contains("testpass").not());
+}
+
+#[test]
+fn binary_finds_and_moves_a_vault_in_the_legacy_config_dir() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    let temp_data =
+        TempDir::with_prefix("data-").expect("failed to create temporary data directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_data.path())
+        .args(["get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("testpass"));
+
+    assert!(temp_data.path().join("passmate/default.vault").exists());
+    assert!(!temp_config.path().join("passmate/default.vault").exists());
+}
+
+#[test]
+fn binary_with_config_dir_flag_creates_the_missing_directory_on_save() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let config_dir = temp_dir.path().join("nested/vaults");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(&config_dir)
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    assert!(config_dir.join("default.vault").exists());
+}
+
+#[test]
+fn binary_with_no_create_dirs_flag_fails_if_the_directory_is_missing() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let config_dir = temp_dir.path().join("nested/vaults");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(&config_dir)
+        .args(["--no-create-dirs", "set", "mypass", "testpass"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Directory does not exist"));
+
+    assert!(!config_dir.exists());
+}
+
+#[test]
+fn binary_prints_a_message_while_deriving_the_key() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["get", "mypass"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Deriving key..."));
+}
+
+#[test]
+fn binary_with_quiet_flag_does_not_print_a_message_while_deriving_the_key() {
+    let temp_config =
+        TempDir::with_prefix("config-").expect("failed to create temporary config directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env(CONFIG_HOME, temp_config.path())
+        .env(DATA_HOME, temp_config.path())
+        .args(["--quiet", "get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("testpass"))
+        .stderr(predicates::str::is_empty());
+}
+
+#[test]
+fn binary_with_stdin_vault_reads_the_encrypted_vault_from_stdin() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+    let vault = std::fs::read(temp_dir.path().join("default.vault")).unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .args(["--vault", "-", "get", "--reveal", "mypass"])
+        .write_stdin(vault)
+        .assert()
+        .success()
+        .stdout("testpass\n");
+}
+
+#[test]
+fn binary_with_stdin_vault_writes_the_saved_vault_to_stdout() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+    let vault = std::fs::read(temp_dir.path().join("default.vault")).unwrap();
+
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .args(["--vault", "-", "set", "otherpass", "otherval"])
+        .write_stdin(vault)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .args(["--vault", "-", "ls"])
+        .write_stdin(output)
+        .assert()
+        .success()
+        .stdout("mypass\notherpass\n");
+}
+
+#[test]
+fn binary_with_stdin_vault_rejects_reading_the_passphrase_from_stdin() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .env("PASSMATE_VAULT", "-")
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--passphrase-fd", "0", "ls"])
+        .write_stdin("testpwd\n")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Can't read the passphrase from stdin when the vault is read from stdin",
+        ));
+}
+
+#[test]
+fn binary_with_vaults_command_lists_the_available_vaults() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for vault in ["work", "personal"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["--vault", vault, "set", "mypass", "testpass"])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("vaults")
+        .assert()
+        .success()
+        .stdout("personal\nwork\n");
+}
+
+#[test]
+fn binary_output_is_only_colored_when_forced_and_not_disabled() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let audit = |args: &[&str], force: bool| {
+        let mut command = Command::cargo_bin("passmate").unwrap();
+        command
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR_FORCE")
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(args)
+            .arg("audit");
+        if force {
+            command.env("CLICOLOR_FORCE", "1");
+        }
+        command.assert().success()
+    };
+
+    audit(&[], false).stdout("No problems found\n");
+    audit(&[], true).stdout(
+        predicates::str::contains("\x1b[").and(predicates::str::contains("No problems found")),
+    );
+    audit(&["--no-color"], true).stdout("No problems found\n");
+}
+
+#[test]
+fn binary_with_doctor_command_reports_a_corrupt_vault_and_invalid_config() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    std::fs::write(temp_dir.path().join("default.vault"), b"PASSMATE").unwrap();
+    std::fs::write(temp_dir.path().join("config.toml"), "vualt = \"work\"").unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("is corrupt: truncated header"))
+        .stdout(predicates::str::contains("config.toml is invalid"))
+        .stderr(predicates::str::contains("Found problems that need fixing"));
+}
+
+#[test]
+fn binary_rejects_a_vault_name_that_points_outside_the_vault_directory() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let data_dir = temp_dir.path().join("data");
+
+    for name in ["../escaped", "nested/vault"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(&data_dir)
+            .args(["--vault", name, "set", "mypass", "testpass"])
+            .write_stdin("testpwd\n")
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(format!(
+                "Invalid vault name: {name:?}"
+            )));
+    }
+    assert!(!temp_dir.path().join("escaped.vault").exists());
+    assert!(!data_dir.exists());
+}
+
+#[test]
+fn binary_with_which_command_prints_the_resolved_vault_path() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("which")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n",
+            temp_dir.path().join("default.vault").display()
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--vault", "work", "--plaintext", "which"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n",
+            temp_dir.path().join("work.plain").display()
+        ));
+    assert!(!temp_dir.path().join("work.plain").exists());
+}
+
+#[test]
+fn binary_with_rename_vault_command_moves_the_vault_to_the_new_name() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--vault", "work", "set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["rename-vault", "work", "job"])
+        .assert()
+        .success();
+
+    assert!(!temp_dir.path().join("work.vault").exists());
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--vault", "job", "get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout("testpass\n");
+}
+
+#[test]
+fn binary_with_hint_command_sets_and_shows_the_passphrase_hint() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["hint", "first pet"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("hint")
+        .assert()
+        .success()
+        .stdout("first pet\n");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["hint", "--clear"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("hint")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn binary_with_hint_command_rejects_the_passphrase_as_the_hint() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["hint", "testpwd"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "the hint must not be the passphrase",
+        ));
+}
+
+#[test]
+fn binary_with_get_command_and_all_flag_prints_all_fields_with_the_secret_masked() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "mypass", "--all"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("secret: ********\ncreated: "))
+        .stdout(predicates::str::contains("\nupdated: "))
+        .stdout(predicates::str::contains("testpass").not());
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "mypass", "--all", "--reveal"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("secret: testpass\n"));
+}
+
+#[test]
+fn binary_with_set_command_and_metadata_flags_stores_the_metadata() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass", "--username", "alice"])
+        .args([
+            "--url",
+            "https://example.com",
+            "--tag",
+            "work",
+            "--tag",
+            "dev",
+        ])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "mypass", "--all", "--reveal"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(
+            "secret: testpass\nusername: alice\nurl: https://example.com\ntags: work, dev\n",
+        ));
+}
+
+#[test]
+fn binary_with_set_command_and_metadata_flags_keeps_the_history_of_an_existing_entry() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let passmate = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("passmate").unwrap();
+        cmd.arg("--config-dir").arg(temp_dir.path()).args(args);
+        cmd.assert().success()
+    };
+    passmate(&["set", "github", "oldvalue", "--tag", "work"]);
+    passmate(&["field", "set", "github", "recovery", "codes"]);
+
+    passmate(&[
+        "set",
+        "github",
+        "newvalue",
+        "--username",
+        "x",
+        "--tag",
+        "dev",
+    ]);
+
+    let output = passmate(&["export"]).get_output().stdout.clone();
+    let exported: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let entry = &exported["github"];
+    assert_eq!(entry["secret"], "newvalue");
+    assert_eq!(entry["history"], serde_json::json!(["oldvalue"]));
+    assert_eq!(entry["username"], "x");
+    assert_eq!(entry["tags"], serde_json::json!(["work", "dev"]));
+    assert_eq!(entry["fields"]["recovery"], "codes");
+}
+
+#[test]
+fn binary_with_field_commands_edits_custom_fields() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let passmate = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("passmate").unwrap();
+        cmd.arg("--config-dir").arg(temp_dir.path()).args(args);
+        cmd.assert()
+    };
+    passmate(&["set", "bank", "testpass"]).success();
+    passmate(&["field", "set", "bank", "security question", "Rex"]).success();
+    passmate(&["field", "set", "bank", "pin", "1234"]).success();
+
+    passmate(&["field", "get", "bank", "security question"])
+        .success()
+        .stdout("********\n");
+    passmate(&["field", "get", "bank", "security question", "--reveal"])
+        .success()
+        .stdout("Rex\n");
+    passmate(&["field", "rename", "bank", "pin", "card pin"]).success();
+    passmate(&["field", "remove", "bank", "security question"]).success();
+
+    passmate(&["--reveal", "field", "get", "bank", "card pin"])
+        .success()
+        .stdout("1234\n");
+    passmate(&["field", "get", "bank", "security question"])
+        .failure()
+        .stderr(predicates::str::contains("security question not found"));
+}
+
+#[test]
+fn binary_with_set_command_rejects_an_empty_value_unless_allowed() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", ""])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Value is empty"));
+    assert!(!temp_dir.path().join("default.vault").exists());
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "", "--allow-empty-value"])
+        .assert()
+        .success();
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout("\n");
+}
+
+#[test]
+fn binary_with_get_command_masks_the_secret_unless_revealed() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let passmate = |args: &[&str]| {
+        let mut command = Command::cargo_bin("passmate").unwrap();
+        command.arg("--config-dir").arg(temp_dir.path()).args(args);
+        command.assert().success()
+    };
+    passmate(&["gen", "mypass"]).stdout("********\n");
+    passmate(&["set", "mypass", "testpass"]);
+
+    passmate(&["get", "mypass"]).stdout("********\n");
+    passmate(&["get", "mypass", "--reveal"]).stdout("testpass\n");
+
+    std::fs::write(temp_dir.path().join("config.toml"), "mask_secrets = false").unwrap();
+    passmate(&["get", "mypass"]).stdout("testpass\n");
+    passmate(&["get", "mypass", "--mask-stdout"]).stdout("********\n");
+}
+
+#[test]
+fn binary_pads_the_vault_to_the_configured_block_size() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let passmate = |args: &[&str]| {
+        let mut command = Command::cargo_bin("passmate").unwrap();
+        command.arg("--config-dir").arg(temp_dir.path()).args(args);
+        command.assert()
+    };
+    std::fs::write(temp_dir.path().join("config.toml"), "padding = 4096").unwrap();
+
+    passmate(&["set", "mypass", "testpass"]).success();
+    passmate(&["debug-format"])
+        .success()
+        .stdout(predicates::str::contains("padding: 4096 bytes"));
+
+    std::fs::write(temp_dir.path().join("config.toml"), "padding = 4097").unwrap();
+    passmate(&["list"])
+        .failure()
+        .stderr(predicates::str::contains("padding block size of 4097"));
+}
+
+#[test]
+fn binary_with_export_command_and_split_by_tag_writes_a_vault_per_tag() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for (name, tag) in [("github", "work"), ("email", "home")] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, "secret", "--tag", tag])
+            .assert()
+            .success();
+    }
+    let export_dir = temp_dir.path().join("by-tag");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("export")
+        .arg("--split-by-tag")
+        .arg(&export_dir)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("work.vault"));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(&export_dir)
+        .args(["--vault", "work", "list"])
+        .assert()
+        .success()
+        .stdout("github\n");
+}
+
+#[test]
+fn binary_with_checksum_flag_warns_when_the_vault_file_no_longer_matches() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--checksum", "set", "mypass", "testpass"])
+        .assert()
+        .success();
+    let checksum_path = temp_dir.path().join("default.vault.sha256");
+    assert!(checksum_path.exists());
+
+    std::fs::write(
+        &checksum_path,
+        format!("{}  default.vault\n", "0".repeat(64)),
+    )
+    .unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "mypass"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "doesn't match its .sha256 checksum",
+        ));
+}
+
+#[test]
+fn binary_with_secure_delete_flag_saves_the_vault_without_leaving_a_temp_file() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for value in ["first", "second"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["--secure-delete", "set", "mypass", value])
+            .assert()
+            .success();
+    }
+
+    assert!(!temp_dir.path().join(".default.vault.tmp").exists());
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout("second\n");
+}
+
+#[test]
+fn binary_with_get_command_and_no_name_fails_when_not_interactive() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for args in [&["get"][..], &["get", "--no-interactive"]] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(
+                "An entry name is required when not running interactively",
+            ));
+    }
+}
+
+#[test]
+fn binary_with_audit_command_reports_names_differing_only_by_case() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("audit")
+        .assert()
+        .success()
+        .stdout("No problems found\n");
+
+    for name in ["GitHub", "github", "gitlab"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, "testpass"])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("audit")
+        .assert()
+        .success()
+        .stdout("Names differ only by case: GitHub, github\n");
+}
+
+#[test]
+fn binary_with_plaintext_flag_stores_the_vault_unencrypted_with_a_warning() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--plaintext", "set", "editor", "vim"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("NOT encrypted"));
+
+    let contents = std::fs::read(temp_dir.path().join("default.plain")).unwrap();
+    assert!(String::from_utf8_lossy(&contents).contains("vim"));
+    assert!(!temp_dir.path().join("default.vault").exists());
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--plaintext", "get", "--reveal", "editor"])
+        .assert()
+        .success()
+        .stdout("vim\n")
+        .stderr(predicates::str::contains("NOT encrypted"));
+}
+
+#[test]
+fn binary_refuses_a_plaintext_file_in_place_of_an_encrypted_vault() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--plaintext", "set", "editor", "vim"])
+        .assert()
+        .success();
+    std::fs::rename(
+        temp_dir.path().join("default.plain"),
+        temp_dir.path().join("default.vault"),
+    )
+    .unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "github", "secret"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("the vault is not encrypted"));
+    let contents = std::fs::read(temp_dir.path().join("default.vault")).unwrap();
+    assert!(!String::from_utf8_lossy(&contents).contains("github"));
+}
+
+#[test]
+fn binary_with_remove_command_and_entries_from_removes_each_listed_entry() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for name in ["github", "gitlab", "bank"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, "testpass"])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["remove", "--entries-from", "-"])
+        .write_stdin("github\nmissing\n\nbank\n")
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Removed github\nRemoved bank\nmissing not found\n",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("ls")
+        .assert()
+        .success()
+        .stdout("gitlab\n");
+}
+
+#[test]
+fn binary_with_stats_command_summarizes_the_vault_without_revealing_values() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("stats")
+        .assert()
+        .success()
+        .stdout(
+            "entries: 1\nlength: min 8, max 8, mean 8.0\nweak: 1\nfair: 0\ngood: 0\nstrong: 0\n",
+        );
+}
+
+#[test]
+fn binary_uses_the_config_file_defaults_unless_overridden() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    std::fs::write(
+        temp_dir.path().join("config.toml"),
+        "vault = \"work\"\n[kdf]\nm_cost = 8192\nt_cost = 1\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .env_remove("PASSMATE_VAULT")
+        .assert()
+        .success();
+
+    assert!(temp_dir.path().join("work.vault").exists());
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--vault", "work", "debug-format"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("m_cost=8192 t_cost=1"));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "mypass"])
+        .env("PASSMATE_VAULT", "home")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("mypass not found"));
+}
+
+#[test]
+fn binary_with_export_command_prints_the_entries_or_the_raw_vault_bytes() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("export")
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(
+            "{\n  \"mypass\": {\n    \"secret\": \"testpass\",\n    \"created_at\": ",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["export", "--secrets-only"])
+        .assert()
+        .success()
+        .stdout("{\n  \"mypass\": \"testpass\"\n}\n");
+
+    let vault = std::fs::read(temp_dir.path().join("default.vault")).unwrap();
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["export", "--raw"])
+        .assert()
+        .success()
+        .stdout(vault.clone());
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .args(["--vault", "-", "export", "--raw"])
+        .write_stdin(vault.clone())
+        .assert()
+        .success()
+        .stdout(vault);
+}
+
+#[test]
+fn binary_with_import_and_export_commands_reads_and_writes_yaml() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let yaml = temp_dir.path().join("secrets.yml");
+    std::fs::write(&yaml, "github:\n  secret: testpass\n  username: me\n").unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("import")
+        .arg(&yaml)
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["export", "--format", "yaml"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(
+            "github:\n  secret: testpass\n  username: me\n",
+        ));
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["export", "--format", "yaml", "--secrets-only"])
+        .assert()
+        .success()
+        .stdout("github: testpass\n");
+}
+
+#[test]
+fn binary_with_set_command_reports_when_it_creates_a_new_vault() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let path = temp_dir.path().join("default.vault");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success()
+        .stderr(format!("Created new vault {}\n", path.display()));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "other", "testpass"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Created new vault").not());
+}
+
+#[test]
+fn binary_with_gen_command_and_no_store_flag_only_prints_a_password() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["gen", "--length", "20", "--no-store"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim_end().len(),
+        20
+    );
+    assert!(output.stderr.is_empty());
+    assert!(!temp_dir.path().join("default.vault").exists());
+}
+
+#[test]
+fn binary_with_gen_command_and_pronounceable_flag_prints_its_entropy() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["gen", "--pronounceable", "--length", "8", "--no-store"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let password = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(password.trim_end().len(), 8);
+    assert!(password.trim_end().chars().all(|c| c.is_ascii_lowercase()));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "About 25 bits of entropy\n"
+    );
+}
+
+#[test]
+fn binary_with_gen_command_and_confirm_flag_stores_without_asking_when_not_on_a_terminal() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["gen", "github", "--confirm"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Accept").not());
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "github"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn binary_with_gen_command_and_like_flag_matches_the_profile_of_an_entry() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "bank", "PIN12345"])
+        .assert()
+        .success();
+
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["--reveal", "gen", "bank-new", "--like", "bank"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let password = String::from_utf8(output.stdout).unwrap();
+    let password = password.trim_end();
+    assert_eq!(password.len(), 8);
+    assert!(password
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()));
+}
+
+#[test]
+fn binary_with_gen_command_stores_a_password_without_excluded_characters() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    let output = Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args([
+            "gen",
+            "--reveal",
+            "mypass",
+            "--length",
+            "40",
+            "--exclude",
+            "<>&",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let password = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(password.trim_end().len(), 40);
+    assert!(!password.contains(['<', '>', '&']));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "mypass"])
+        .assert()
+        .success()
+        .stdout(password);
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["gen", "--reveal", "other", "--exclude", "0123456789"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("all digits are excluded"));
+}
+
+#[test]
+fn binary_with_list_command_and_since_flag_lists_recently_changed_entries() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["ls", "--since", "1h"])
+        .assert()
+        .success()
+        .stdout("mypass\n");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["ls", "--since", "2999-01-01"])
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["ls", "--since", "yesterday"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid time"));
+}
+
+#[test]
+fn binary_with_list_command_and_group_flag_groups_entries_by_prefix() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for name in ["github/work", "email", "aws/staging", "aws/prod", "zoom"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, "testpass"])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["ls", "--group"])
+        .assert()
+        .success()
+        .stdout("aws/\n  prod\n  staging\nemail\ngithub/\n  work\nzoom\n");
+}
+
+#[test]
+fn binary_with_upgrade_entries_command_is_a_no_op_for_current_vaults() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+    let before = std::fs::read(temp_dir.path().join("default.vault")).unwrap();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .arg("upgrade-entries")
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Entries are already up to date\n",
+        ));
+
+    assert_eq!(
+        std::fs::read(temp_dir.path().join("default.vault")).unwrap(),
+        before
+    );
+}
+
+#[test]
+fn binary_with_prune_command_drops_the_oldest_versions() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for value in ["v1", "v2", "v3"] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", "mypass", value])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["prune", "--keep", "1"])
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with("Removed 1 old versions\n"));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["prune", "--keep", "1"])
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with("Removed 0 old versions\n"));
+}
+
+#[test]
+fn binary_with_import_command_and_dedupe_flag_counts_identical_entries_as_unchanged() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for (name, value) in [("same", "secret"), ("changed", "old")] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, value])
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["import", "-", "--overwrite", "--dedupe"])
+        .write_stdin(r#"{"same": "secret", "changed": "new", "added": "value"}"#)
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Added 1, updated 1, unchanged 1, skipped 0\n",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "changed"])
+        .assert()
+        .success()
+        .stdout("new\n");
+}
+
+#[test]
+fn binary_with_import_command_and_strategy_flag_keeps_theirs_or_rejects_unknown_strategies() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "github", "old"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["import", "-", "--strategy", "keep-theirs"])
+        .write_stdin(r#"{"github": "new"}"#)
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Added 0, updated 1, unchanged 0, skipped 0\n",
+        ));
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["import", "-", "--strategy", "keep-mine"])
+        .write_stdin(r#"{"github": "newer"}"#)
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Added 0, updated 0, unchanged 0, skipped 1\n",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "github"])
+        .assert()
+        .success()
+        .stdout("new\n");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["import", "-", "--strategy", "keep-oldest"])
+        .write_stdin("{}")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "unknown strategy \"keep-oldest\"",
+        ));
+}
+
+#[test]
+fn binary_with_import_command_and_from_keepass_flag_imports_a_keepass_export() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["import", "-", "--from-keepass"])
+        .write_stdin(concat!(
+            "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\"\n",
+            "\"Root/Internet\",\"github\",\"octocat\",\"hunter2\",\"\",\"two\nlines\"\n",
+        ))
+        .assert()
+        .success()
+        .stderr(predicates::str::ends_with(
+            "Added 1, updated 0, unchanged 0, skipped 0\n",
+        ));
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["get", "--reveal", "Internet/github"])
+        .assert()
+        .success()
+        .stdout("hunter2\n");
+}
+
+#[test]
+fn binary_with_audit_command_reports_and_removes_entries_with_empty_values() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    for (name, value) in [("github", "secret"), ("broken", "")] {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .args(["set", name, value, "--allow-empty-value"])
+            .assert()
+            .success();
+    }
+    let audit = |flags: &[&str]| {
+        Command::cargo_bin("passmate")
+            .unwrap()
+            .arg("--config-dir")
+            .arg(temp_dir.path())
+            .arg("audit")
+            .args(flags)
+            .assert()
+            .success()
+    };
+
+    audit(&[]).stdout("broken has an empty value\n");
+    audit(&["--remove-empty"]).stdout("No problems found\n");
+    audit(&[]).stdout("No problems found\n");
+}
+
+#[test]
+fn binary_with_audit_command_and_check_policy_flag_reports_policy_violations() {
+    let temp_dir = TempDir::with_prefix("vaults-").expect("failed to create temporary directory");
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["set", "mypass", "testpass"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("passmate")
+        .unwrap()
+        .arg("--config-dir")
+        .arg(temp_dir.path())
+        .args(["audit", "--check-policy"])
+        .assert()
+        .success()
+        .stdout("mypass: has 8 characters, fewer than 20, has no uppercase letters, has no digits, has no symbols\n");
+}